@@ -0,0 +1,222 @@
+use super::transforms::Transform;
+use super::*;
+
+/// How a track gets from a keyframe to the next one
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Eases along a cubic Bezier from (0, 0) to (1, 1) with the control points `[x1, y1, x2, y2]`,
+    /// like a CSS timing function. x is how far the time has got between the keyframes and y how far the value has,
+    /// so `[0.42, 0.0, 0.58, 1.0]` eases in and out
+    Bezier([Float; 4]),
+}
+
+impl Interpolation {
+    fn validate(&self) -> Result<()> {
+        if let Interpolation::Bezier([x1, y1, x2, y2]) = *self {
+            // Outside of [0, 1] the curve could go back in time
+            let valid_x = (0.0..=1.0).contains(&x1) && (0.0..=1.0).contains(&x2);
+            if !valid_x || !y1.is_finite() || !y2.is_finite() {
+                return Err(Error::invalid_parameter(format!(
+                    "Bezier control points must have x between 0 and 1 and a finite y, got {:?}",
+                    [x1, y1, x2, y2]
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Maps how far the time has got between two keyframes to how far the value has
+    fn ease(&self, x: Float) -> Float {
+        match *self {
+            Interpolation::Linear => x,
+            Interpolation::Bezier([x1, y1, x2, y2]) => {
+                let bezier = |p1: Float, p2: Float, u: Float| {
+                    let v = 1.0 - u;
+                    3.0 * v * v * u * p1 + 3.0 * v * u * u * p2 + u * u * u
+                };
+
+                // x only ever grows along the curve, so bisection finds where it reaches `x`
+                let (mut low, mut high) = (0.0, 1.0);
+                for _ in 0..32 {
+                    let middle = 0.5 * (low + high);
+                    if bezier(x1, x2, middle) < x {
+                        low = middle;
+                    } else {
+                        high = middle;
+                    }
+                }
+                bezier(y1, y2, 0.5 * (low + high))
+            }
+        }
+    }
+}
+
+/// Values that can be interpolated between keyframes
+pub trait Animatable: Copy {
+    /// `a` at 0 and `b` at 1, but `t` can go past either with Bezier interpolation
+    fn lerp(a: Self, b: Self, t: Float) -> Self;
+}
+
+impl Animatable for Float {
+    fn lerp(a: Float, b: Float, t: Float) -> Float {
+        (1.0 - t) * a + t * b
+    }
+}
+
+impl Animatable for Vector3 {
+    fn lerp(a: Vector3, b: Vector3, t: Float) -> Vector3 {
+        (1.0 - t) * a + t * b
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe<T> {
+    /// In frames
+    pub time: Float,
+    pub value: T,
+    /// How to get from this keyframe to the next one
+    pub interpolation: Interpolation,
+}
+
+/// A value that changes over time by going through keyframes
+#[derive(Clone, Debug)]
+pub struct Track<T> {
+    keyframes: Vec<Keyframe<T>>,
+}
+
+impl<T: Animatable> Track<T> {
+    /// `keyframes` must be in order of time. Before the first and after the last one the value stays the same
+    pub fn new(keyframes: Vec<Keyframe<T>>) -> Result<Track<T>> {
+        if keyframes.is_empty() {
+            return Err(Error::invalid_parameter(
+                "an animation track needs at least one keyframe",
+            ));
+        }
+        for keyframe in &keyframes {
+            if !keyframe.time.is_finite() {
+                return Err(Error::invalid_parameter(format!(
+                    "keyframe times must be finite, got {}",
+                    keyframe.time
+                )));
+            }
+            keyframe.interpolation.validate()?;
+        }
+        if let Some(pair) = keyframes
+            .windows(2)
+            .find(|pair| pair[0].time >= pair[1].time)
+        {
+            return Err(Error::invalid_parameter(format!(
+                "keyframes must be in order of time, got {} before {}",
+                pair[0].time, pair[1].time
+            )));
+        }
+
+        Ok(Track { keyframes })
+    }
+
+    /// Stays at `value` the whole time
+    pub fn constant(value: T) -> Track<T> {
+        Track {
+            keyframes: vec![Keyframe {
+                time: 0.0,
+                value,
+                interpolation: Interpolation::Linear,
+            }],
+        }
+    }
+
+    pub fn at(&self, time: Float) -> T {
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 {
+            return self.keyframes[0].value;
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].value;
+        }
+
+        let (from, to) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let x = (time - from.time) / (to.time - from.time);
+        T::lerp(from.value, to.value, from.interpolation.ease(x))
+    }
+}
+
+/// Where an instance is. It's scaled first, then rotated around x, y and z in that order by angles in degrees,
+/// then translated. Unlike a transform, poses can be interpolated
+#[derive(Clone, Copy, Debug)]
+pub struct Pose {
+    pub translation: Vector3,
+    pub rotation: Vector3,
+    pub scale: Vector3,
+}
+
+impl Default for Pose {
+    fn default() -> Pose {
+        Pose {
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Vector3::new(0.0, 0.0, 0.0),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Pose {
+    pub fn transform(&self) -> Transform {
+        Transform::translate(&self.translation)
+            * Transform::rotate_z(self.rotation.z)
+            * Transform::rotate_y(self.rotation.y)
+            * Transform::rotate_x(self.rotation.x)
+            * Transform::scale(&self.scale)
+    }
+}
+
+impl Animatable for Pose {
+    fn lerp(a: Pose, b: Pose, t: Float) -> Pose {
+        Pose {
+            translation: Vector3::lerp(a.translation, b.translation, t),
+            rotation: Vector3::lerp(a.rotation, b.rotation, t),
+            scale: Vector3::lerp(a.scale, b.scale, t),
+        }
+    }
+}
+
+/// A perspective camera whose position, target, field of view and focus distance follow tracks
+#[derive(Clone)]
+pub struct CameraAnimation {
+    pub look_from: Track<Point3>,
+    pub look_at: Track<Point3>,
+    pub view_up: Vector3,
+    /// Vertical field of view in degrees
+    pub fov: Track<Float>,
+    pub aspect_ratio: Float,
+    pub aperture: Float,
+    pub focus_distance: Track<Float>,
+    pub lens: ThinLens,
+}
+
+impl CameraAnimation {
+    pub fn camera_at(&self, time: Float) -> Result<PerspectiveCamera> {
+        PerspectiveCamera::new(
+            self.look_from.at(time),
+            self.look_at.at(time),
+            self.view_up,
+            self.fov.at(time),
+            self.aspect_ratio,
+            self.aperture,
+            self.focus_distance.at(time),
+        )?
+        .with_thin_lens(self.lens.clone())
+    }
+}
+
+/// Everything in a scene that moves
+#[derive(Clone, Default)]
+pub struct Animation {
+    /// `None` leaves the scene's camera where it is
+    pub camera: Option<CameraAnimation>,
+    /// Object ids of `Hittable::Instance`s in the world and their poses
+    pub instances: Vec<(u32, Track<Pose>)>,
+}
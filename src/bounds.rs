@@ -109,6 +109,20 @@ impl Bounds3 {
         }
     }
 
+    /// Grows the bounds to at least `extent` along every axis, so that rays can't slip through
+    /// the bounds of flat shapes between the two sides rounding to the same value
+    pub fn with_min_extent(&self, extent: Float) -> Bounds3 {
+        let mut bounds = *self;
+        for axis in 0..3 {
+            let missing = extent - (bounds.p_max[axis] - bounds.p_min[axis]);
+            if missing > 0.0 {
+                bounds.p_min[axis] -= missing / 2.0;
+                bounds.p_max[axis] += missing / 2.0;
+            }
+        }
+        bounds
+    }
+
     pub fn diagonal(&self) -> Vector3 {
         self.p_max - self.p_min
     }
@@ -1,70 +1,125 @@
 use super::*;
 
-#[derive(Clone)]
-pub struct Camera {
+mod aperture;
+mod equirectangular;
+mod fisheye;
+mod orthographic;
+mod perspective;
+mod realistic;
+mod stereo;
+
+pub use aperture::{Aperture, ApertureMask};
+pub use equirectangular::EquirectangularCamera;
+pub use fisheye::{FisheyeCamera, FisheyeProjection};
+pub use orthographic::OrthographicCamera;
+pub use perspective::{PerspectiveCamera, ThinLens};
+pub use realistic::{read_lens_file, LensElement, RealisticCamera};
+pub use stereo::StereoRig;
+
+/// Turns a position on the film into a ray
+pub trait Camera: Send + Sync {
+    /// `s` and `t` go from 0 to 1 across the film, starting at the bottom left.
+    /// `None` if nothing is projected onto that part of the film, like outside the image circle of a fisheye
+    fn get_ray(&self, s: Float, t: Float, rng: &mut SmallRng) -> Option<CameraRay>;
+
+    /// The left and right eye versions of this camera, each rendered to a film of its own
+    fn stereo_pair(&self, _rig: &StereoRig) -> Result<[Box<dyn Camera>; 2]> {
+        Err(Error::invalid_parameter(
+            "this camera can't render in stereo, use a perspective or equirectangular one",
+        ))
+    }
+
+    /// This camera with the aperture shape, tilt and everything else `ThinLens` has replaced by `lens`
+    fn with_thin_lens(&self, _lens: &ThinLens) -> Result<Box<dyn Camera>> {
+        Err(Error::invalid_parameter(
+            "only perspective cameras have a thin lens",
+        ))
+    }
+}
+
+/// So that a camera picked at run time, like one read from a scene file, can be passed where a camera is expected
+impl Camera for Box<dyn Camera> {
+    fn get_ray(&self, s: Float, t: Float, rng: &mut SmallRng) -> Option<CameraRay> {
+        (**self).get_ray(s, t, rng)
+    }
+
+    fn stereo_pair(&self, rig: &StereoRig) -> Result<[Box<dyn Camera>; 2]> {
+        (**self).stereo_pair(rig)
+    }
+
+    fn with_thin_lens(&self, lens: &ThinLens) -> Result<Box<dyn Camera>> {
+        (**self).with_thin_lens(lens)
+    }
+}
+
+/// A ray leaving the camera and how much the light arriving along it counts towards the pixel
+#[derive(Clone, Copy)]
+pub struct CameraRay {
+    pub ray: Ray,
+    /// 1 for most cameras, less where a lens lets less light through.
+    /// Per channel so that a sample can stand in for a single colour
+    pub weight: Color3,
+}
+
+impl From<Ray> for CameraRay {
+    fn from(ray: Ray) -> CameraRay {
+        CameraRay {
+            ray,
+            weight: Color3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+/// Position and orientation shared by all the cameras.
+/// The camera looks along `-w`, `u` points right and `v` up
+#[derive(Clone, Copy, Debug)]
+struct CameraFrame {
     origin: Point3,
-    lower_left_corner: Point3,
-    horizontal: Vector3,
-    vertical: Vector3,
     u: Vector3,
     v: Vector3,
     w: Vector3,
-    lens_radius: Float,
 }
 
-impl Camera {
-    pub fn new(
-        look_from: Point3,
-        look_at: Point3,
-        view_up: Vector3,
-        fov: Float,
-        aspect_ratio: Float,
-        aperture: Float,
-        focus_dist: Float,
-    ) -> Self {
-        let theta = fov.to_radians();
-        let h = Float::tan(theta / 2.0);
-        let viewport_height = 2.0 * h;
-        let viewport_width = aspect_ratio * viewport_height;
+impl CameraFrame {
+    fn new(look_from: Point3, look_at: Point3, view_up: Vector3) -> Result<CameraFrame> {
+        if !look_from.is_finite() || !look_at.is_finite() {
+            return Err(Error::invalid_parameter(format!(
+                "camera position and target must be finite, got {:?} and {:?}",
+                look_from, look_at
+            )));
+        }
+        if (look_from - look_at).near_zero()
+            || Vector3::cross(&view_up, &(look_from - look_at)).near_zero()
+        {
+            return Err(Error::invalid_parameter(
+                "camera view direction must be non-zero and not parallel to the up vector",
+            ));
+        }
 
         let w = Vector3::unit_vector(look_from - look_at);
         let u = Vector3::unit_vector(Vector3::cross(&view_up, &w));
         let v = Vector3::cross(&w, &u);
 
-        let origin = look_from;
-        let horizontal = focus_dist * viewport_width * u;
-        let vertical = focus_dist * viewport_height * v;
-        let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - focus_dist * w;
-
-        Camera {
-            origin,
-            horizontal,
-            vertical,
-            lower_left_corner,
-            w,
+        Ok(CameraFrame {
+            origin: look_from,
             u,
             v,
-            lens_radius: aperture / 2.0,
-        }
+            w,
+        })
     }
 
-    pub fn get_ray(&self, s: Float, t: Float, rng: &mut SmallRng) -> Ray {
-        let rd = self.lens_radius * random_in_unit_disk(rng);
-        let offset = self.u * rd.x + self.v * rd.y;
-
-        Ray {
-            origin: self.origin + offset,
-            direction: self.lower_left_corner + s * self.horizontal + t * self.vertical
-                - self.origin
-                - offset,
-        }
+    /// Turns a direction where x is right, y up and z backwards from the camera into world space
+    fn local_to_world(&self, direction: Vector3) -> Vector3 {
+        direction.x * self.u + direction.y * self.v + direction.z * self.w
     }
 }
 
-static PI: Float = std::f64::consts::PI as Float;
-
-fn random_in_unit_disk(rng: &mut SmallRng) -> Vector3 {
-    let r = Float::sqrt(rng.gen());
-    let theta = 2.0 * PI * rng.gen::<Float>();
-    Vector3::new(r * Float::cos(theta), r * Float::sin(theta), 0.0)
+fn validate_aspect_ratio(aspect_ratio: Float) -> Result<()> {
+    if !(aspect_ratio > 0.0 && aspect_ratio.is_finite()) {
+        return Err(Error::invalid_parameter(format!(
+            "camera aspect ratio must be positive, got {}",
+            aspect_ratio
+        )));
+    }
+    Ok(())
 }
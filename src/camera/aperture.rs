@@ -0,0 +1,124 @@
+use super::*;
+use crate::light::AliasTable;
+use std::path::Path;
+
+/// Shape of the opening of a thin lens, which is the shape out of focus highlights take
+#[derive(Clone, Default)]
+pub enum Aperture {
+    #[default]
+    Circle,
+    /// A regular polygon like the iris of a real lens, `rotation` is in degrees
+    Polygon {
+        blades: usize,
+        rotation: Float,
+    },
+    Mask(ApertureMask),
+}
+
+impl Aperture {
+    pub(super) fn validate(&self) -> Result<()> {
+        if let Aperture::Polygon { blades, rotation } = self {
+            if *blades < 3 {
+                return Err(Error::invalid_parameter(format!(
+                    "aperture needs at least 3 blades, got {}",
+                    blades
+                )));
+            }
+            if !rotation.is_finite() {
+                return Err(Error::invalid_parameter(format!(
+                    "aperture rotation must be finite, got {}",
+                    rotation
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// A point in the [-1, 1] square, and inside the unit circle unless it's a mask
+    pub(super) fn sample(&self, rng: &mut SmallRng) -> (Float, Float) {
+        match self {
+            Aperture::Circle => {
+                let r = Float::sqrt(rng.gen());
+                let theta = 2.0 * PI * rng.gen::<Float>();
+                (r * Float::cos(theta), r * Float::sin(theta))
+            }
+            Aperture::Polygon { blades, rotation } => {
+                // All the triangles between the centre and an edge have the same area
+                let blade =
+                    usize::min((rng.gen::<Float>() * *blades as Float) as usize, blades - 1);
+                let angle =
+                    |i: usize| rotation.to_radians() + 2.0 * PI * i as Float / *blades as Float;
+                let (a, b) = (angle(blade), angle(blade + 1));
+
+                // Uniform in the triangle of the centre and the two corners
+                let (mut u, mut v) = (rng.gen::<Float>(), rng.gen::<Float>());
+                if u + v > 1.0 {
+                    u = 1.0 - u;
+                    v = 1.0 - v;
+                }
+                (
+                    u * Float::cos(a) + v * Float::cos(b),
+                    u * Float::sin(a) + v * Float::sin(b),
+                )
+            }
+            Aperture::Mask(mask) => mask.sample(rng),
+        }
+    }
+}
+
+/// A greyscale image of the aperture, stretched over the square around the lens.
+/// Brighter pixels let more light through, so they're sampled more often
+#[derive(Clone)]
+pub struct ApertureMask {
+    width: usize,
+    height: usize,
+    distribution: AliasTable,
+}
+
+impl ApertureMask {
+    pub fn read(path: &Path) -> Result<ApertureMask> {
+        let image = image::open(path)?.to_luma8();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let weights: Vec<Float> = image.pixels().map(|p| p.0[0] as Float).collect();
+
+        ApertureMask::new(width, height, &weights)
+    }
+
+    /// `weights` are row by row from the top
+    pub fn new(width: usize, height: usize, weights: &[Float]) -> Result<ApertureMask> {
+        if width == 0 || height == 0 || weights.len() != width * height {
+            return Err(Error::invalid_parameter(format!(
+                "aperture mask of {}x{} needs that many weights, got {}",
+                width,
+                height,
+                weights.len()
+            )));
+        }
+        if weights.iter().any(|&w| !(w >= 0.0 && w.is_finite())) {
+            return Err(Error::invalid_parameter(
+                "aperture mask weights can't be negative",
+            ));
+        }
+        if weights.iter().all(|&w| w == 0.0) {
+            return Err(Error::invalid_parameter(
+                "aperture mask is black, so it lets no light through",
+            ));
+        }
+
+        Ok(ApertureMask {
+            width,
+            height,
+            distribution: AliasTable::new(weights),
+        })
+    }
+
+    fn sample(&self, rng: &mut SmallRng) -> (Float, Float) {
+        let mut pmf = 0.0;
+        let index = self.distribution.sample(rng.gen(), &mut pmf);
+        let (column, row) = (index % self.width, index / self.width);
+
+        let x = (column as Float + rng.gen::<Float>()) / self.width as Float;
+        let y = (row as Float + rng.gen::<Float>()) / self.height as Float;
+        (2.0 * x - 1.0, 1.0 - 2.0 * y)
+    }
+}
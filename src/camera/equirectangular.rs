@@ -0,0 +1,70 @@
+use super::*;
+
+/// 360° panorama, longitude across and latitude up the film, with `look_at` in the middle.
+/// The film should be twice as wide as it is high for the pixels to be square
+#[derive(Clone)]
+pub struct EquirectangularCamera {
+    frame: CameraFrame,
+    /// How far right of the centre the eye is for omni-directional stereo, negative for the left eye
+    eye_offset: Float,
+    convergence_distance: Option<Float>,
+}
+
+impl EquirectangularCamera {
+    pub fn new(look_from: Point3, look_at: Point3, view_up: Vector3) -> Result<Self> {
+        Ok(EquirectangularCamera {
+            frame: CameraFrame::new(look_from, look_at, view_up)?,
+            eye_offset: 0.0,
+            convergence_distance: None,
+        })
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn get_ray(&self, s: Float, t: Float, _rng: &mut SmallRng) -> Option<CameraRay> {
+        let phi = (s - 0.5) * 2.0 * PI;
+        let theta = (t - 0.5) * PI;
+        let cos_theta = Float::cos(theta);
+
+        let direction = Vector3::new(
+            cos_theta * Float::sin(phi),
+            Float::sin(theta),
+            -cos_theta * Float::cos(phi),
+        );
+        if self.eye_offset == 0.0 {
+            return Some(CameraRay::from(Ray {
+                origin: self.frame.origin,
+                direction: self.frame.local_to_world(direction),
+            }));
+        }
+
+        // Every column is seen by an eye on a circle around the centre, to the side of where it looks,
+        // like turning the head. The circle shrinks towards the poles, where there's no side to be on
+        let right = Vector3::new(Float::cos(phi), 0.0, Float::sin(phi));
+        let offset = self.eye_offset * cos_theta * right;
+        let direction = match self.convergence_distance {
+            Some(distance) => distance * direction - offset,
+            None => direction,
+        };
+
+        Some(CameraRay::from(Ray {
+            origin: self.frame.origin + self.frame.local_to_world(offset),
+            direction: self.frame.local_to_world(direction),
+        }))
+    }
+
+    /// Omni-directional stereo, so that the panorama is in stereo whichever way the viewer turns
+    fn stereo_pair(&self, rig: &StereoRig) -> Result<[Box<dyn Camera>; 2]> {
+        rig.validate()?;
+
+        let eye = |side: Float| {
+            Box::new(EquirectangularCamera {
+                eye_offset: side * rig.interocular_distance / 2.0,
+                convergence_distance: rig.convergence_distance,
+                ..self.clone()
+            }) as Box<dyn Camera>
+        };
+
+        Ok([eye(-1.0), eye(1.0)])
+    }
+}
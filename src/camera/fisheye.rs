@@ -0,0 +1,88 @@
+use super::*;
+
+/// How the angle from the view direction maps to the distance from the centre of the image circle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FisheyeProjection {
+    /// The distance is proportional to the angle, so angles can be measured off the image
+    Equidistant,
+    /// The distance is proportional to `sin(angle / 2)`, which keeps areas of solid angle the same
+    Equisolid,
+}
+
+/// A circular fisheye whose image circle fits the shorter side of the film, the corners outside it stay black
+#[derive(Clone)]
+pub struct FisheyeCamera {
+    frame: CameraFrame,
+    projection: FisheyeProjection,
+    /// Half the field of view in radians
+    max_theta: Float,
+    /// Half the size of the film relative to the radius of the image circle
+    half_width: Float,
+    half_height: Float,
+}
+
+impl FisheyeCamera {
+    /// `fov` is the field of view across the image circle in degrees, at 360 the edge of the circle looks straight back
+    pub fn new(
+        look_from: Point3,
+        look_at: Point3,
+        view_up: Vector3,
+        fov: Float,
+        aspect_ratio: Float,
+        projection: FisheyeProjection,
+    ) -> Result<Self> {
+        if !(fov > 0.0 && fov <= 360.0) {
+            return Err(Error::invalid_parameter(format!(
+                "fisheye field of view must be between 0 and 360 degrees, got {}",
+                fov
+            )));
+        }
+        validate_aspect_ratio(aspect_ratio)?;
+        let frame = CameraFrame::new(look_from, look_at, view_up)?;
+
+        let (half_width, half_height) = if aspect_ratio >= 1.0 {
+            (aspect_ratio, 1.0)
+        } else {
+            (1.0, 1.0 / aspect_ratio)
+        };
+
+        Ok(FisheyeCamera {
+            frame,
+            projection,
+            max_theta: fov.to_radians() / 2.0,
+            half_width,
+            half_height,
+        })
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn get_ray(&self, s: Float, t: Float, _rng: &mut SmallRng) -> Option<CameraRay> {
+        let x = (2.0 * s - 1.0) * self.half_width;
+        let y = (2.0 * t - 1.0) * self.half_height;
+        let r = Float::sqrt(x * x + y * y);
+        if r > 1.0 {
+            return None;
+        }
+
+        let theta = match self.projection {
+            FisheyeProjection::Equidistant => r * self.max_theta,
+            FisheyeProjection::Equisolid => {
+                2.0 * Float::asin(Float::min(r * Float::sin(self.max_theta / 2.0), 1.0))
+            }
+        };
+        let phi = Float::atan2(y, x);
+        let sin_theta = Float::sin(theta);
+
+        let direction = Vector3::new(
+            sin_theta * Float::cos(phi),
+            sin_theta * Float::sin(phi),
+            -Float::cos(theta),
+        );
+
+        Some(CameraRay::from(Ray {
+            origin: self.frame.origin,
+            direction: self.frame.local_to_world(direction),
+        }))
+    }
+}
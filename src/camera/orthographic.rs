@@ -0,0 +1,52 @@
+use super::*;
+
+/// Parallel projection without perspective, so that sizes can be measured off the image
+#[derive(Clone)]
+pub struct OrthographicCamera {
+    frame: CameraFrame,
+    /// Centre of the bottom left corner of the view
+    lower_left_corner: Point3,
+    horizontal: Vector3,
+    vertical: Vector3,
+}
+
+impl OrthographicCamera {
+    /// `height` is how much of the scene the view covers vertically, in world units.
+    /// Rays start on the plane through `look_from`, so anything behind it isn't seen
+    pub fn new(
+        look_from: Point3,
+        look_at: Point3,
+        view_up: Vector3,
+        height: Float,
+        aspect_ratio: Float,
+    ) -> Result<Self> {
+        if !(height > 0.0 && height.is_finite()) {
+            return Err(Error::invalid_parameter(format!(
+                "orthographic camera height must be positive, got {}",
+                height
+            )));
+        }
+        validate_aspect_ratio(aspect_ratio)?;
+        let frame = CameraFrame::new(look_from, look_at, view_up)?;
+
+        let horizontal = aspect_ratio * height * frame.u;
+        let vertical = height * frame.v;
+        let lower_left_corner = frame.origin - horizontal / 2.0 - vertical / 2.0;
+
+        Ok(OrthographicCamera {
+            frame,
+            lower_left_corner,
+            horizontal,
+            vertical,
+        })
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn get_ray(&self, s: Float, t: Float, _rng: &mut SmallRng) -> Option<CameraRay> {
+        Some(CameraRay::from(Ray {
+            origin: self.lower_left_corner + s * self.horizontal + t * self.vertical,
+            direction: -self.frame.w,
+        }))
+    }
+}
@@ -0,0 +1,211 @@
+use super::*;
+
+/// Pinhole camera, or a thin lens one if it has an aperture
+#[derive(Clone)]
+pub struct PerspectiveCamera {
+    frame: CameraFrame,
+    lower_left_corner: Point3,
+    horizontal: Vector3,
+    vertical: Vector3,
+    lens_radius: Float,
+    focus_dist: Float,
+    lens: ThinLens,
+    /// Normal of the plane in focus, which is only `frame.w` without tilt or swing
+    focal_normal: Vector3,
+}
+
+/// Everything about a thin lens beyond its size and focus, the default is a plain circular lens
+#[derive(Clone, Default)]
+pub struct ThinLens {
+    pub aperture: Aperture,
+    /// How far the lens barrel cuts into the aperture towards the edges of the image, in aperture radii at the
+    /// corners. This turns out of focus highlights there into cat's eyes and darkens the corners, 0 turns it off.
+    /// Rays the barrel blocks still leave the camera, with a weight of 0
+    pub cats_eye: Float,
+    /// How much further red and how much closer blue is in focus than green, relative to the focus distance.
+    /// Gives out of focus edges coloured fringes
+    pub chromatic_aberration: Float,
+    /// Rotates the plane in focus around the horizontal axis in degrees, so that it's closer at the bottom
+    /// of the image, like a tilt-shift lens tilted down to keep the ground sharp from near to far
+    pub tilt: Float,
+    /// Rotates the plane in focus around the vertical axis in degrees, so that it's closer on the right
+    pub swing: Float,
+    /// Moves the image across the film as a fraction of its width and height without turning the camera,
+    /// which keeps vertical lines vertical when shifting up to take in a tall building
+    pub shift: [Float; 2],
+}
+
+impl ThinLens {
+    fn validate(&self) -> Result<()> {
+        self.aperture.validate()?;
+        if !(self.cats_eye >= 0.0 && self.cats_eye < 2.0) {
+            return Err(Error::invalid_parameter(format!(
+                "cat's eye must be at least 0 and less than 2, where it would close the corners, got {}",
+                self.cats_eye
+            )));
+        }
+        if self.chromatic_aberration.is_nan() || self.chromatic_aberration.abs() >= 1.0 {
+            return Err(Error::invalid_parameter(format!(
+                "chromatic aberration must be between -1 and 1, got {}",
+                self.chromatic_aberration
+            )));
+        }
+        if !(self.tilt.abs() < 90.0 && self.swing.abs() < 90.0) {
+            return Err(Error::invalid_parameter(format!(
+                "tilt and swing must be between -90 and 90 degrees, got {} and {}",
+                self.tilt, self.swing
+            )));
+        }
+        if !self.shift.iter().all(|shift| shift.is_finite()) {
+            return Err(Error::invalid_parameter(format!(
+                "shift must be finite, got {:?}",
+                self.shift
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl PerspectiveCamera {
+    /// `fov` is the vertical field of view in degrees
+    pub fn new(
+        look_from: Point3,
+        look_at: Point3,
+        view_up: Vector3,
+        fov: Float,
+        aspect_ratio: Float,
+        aperture: Float,
+        focus_dist: Float,
+    ) -> Result<Self> {
+        if !(fov > 0.0 && fov < 180.0) {
+            return Err(Error::invalid_parameter(format!(
+                "camera field of view must be between 0 and 180 degrees, got {}",
+                fov
+            )));
+        }
+        validate_aspect_ratio(aspect_ratio)?;
+        if !(aperture >= 0.0 && aperture.is_finite()) {
+            return Err(Error::invalid_parameter(format!(
+                "camera aperture can't be negative, got {}",
+                aperture
+            )));
+        }
+        if !(focus_dist > 0.0 && focus_dist.is_finite()) {
+            return Err(Error::invalid_parameter(format!(
+                "camera focus distance must be positive, got {}",
+                focus_dist
+            )));
+        }
+        let frame = CameraFrame::new(look_from, look_at, view_up)?;
+
+        let theta = fov.to_radians();
+        let h = Float::tan(theta / 2.0);
+        let viewport_height = 2.0 * h;
+        let viewport_width = aspect_ratio * viewport_height;
+
+        let horizontal = focus_dist * viewport_width * frame.u;
+        let vertical = focus_dist * viewport_height * frame.v;
+        let lower_left_corner =
+            frame.origin - horizontal / 2.0 - vertical / 2.0 - focus_dist * frame.w;
+
+        Ok(PerspectiveCamera {
+            frame,
+            horizontal,
+            vertical,
+            lower_left_corner,
+            lens_radius: aperture / 2.0,
+            focus_dist,
+            lens: ThinLens::default(),
+            focal_normal: frame.w,
+        })
+    }
+
+    pub fn with_thin_lens(mut self, lens: ThinLens) -> Result<Self> {
+        lens.validate()?;
+
+        self.focal_normal = Vector3::unit_vector(
+            self.frame.w
+                + Float::tan(lens.tilt.to_radians()) * self.frame.v
+                + Float::tan(lens.swing.to_radians()) * self.frame.u,
+        );
+        self.lens = lens;
+
+        Ok(self)
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn get_ray(&self, s: Float, t: Float, rng: &mut SmallRng) -> Option<CameraRay> {
+        let s = s + self.lens.shift[0];
+        let t = t + self.lens.shift[1];
+
+        // Through the centre of the lens, which reaches the plane in focus at `focus_t`
+        let direction =
+            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.frame.origin;
+        let along_normal = Vector3::dot(&direction, &self.focal_normal);
+        let mut focus_t = if along_normal < 0.0 {
+            -self.focus_dist * Vector3::dot(&self.frame.w, &self.focal_normal) / along_normal
+        } else {
+            // The plane is tilted so far that this part of the image is in focus at infinity
+            Float::INFINITY
+        };
+
+        // Each sample only traces one channel, which is focused at its own distance
+        let mut weight = Color3::new(1.0, 1.0, 1.0);
+        if self.lens.chromatic_aberration != 0.0 {
+            let channel = usize::min((3.0 * rng.gen::<Float>()) as usize, 2);
+            weight = Color3::default();
+            weight[channel] = 3.0;
+            focus_t *= 1.0 + self.lens.chromatic_aberration * (1.0 - channel as Float);
+        }
+
+        let (x, y) = self.lens.aperture.sample(rng);
+        if self.lens.cats_eye > 0.0 {
+            // The barrel is a circle as large as the aperture that moves off centre towards the edges
+            let barrel_x = self.lens.cats_eye * (2.0 * s - 1.0) / Float::sqrt(2.0);
+            let barrel_y = self.lens.cats_eye * (2.0 * t - 1.0) / Float::sqrt(2.0);
+            if (x - barrel_x).powi(2) + (y - barrel_y).powi(2) > 1.0 {
+                weight = Color3::default();
+            }
+        }
+        let offset = self.lens_radius * (self.frame.u * x + self.frame.v * y);
+
+        let direction = if focus_t.is_finite() {
+            focus_t * direction - offset
+        } else {
+            direction
+        };
+
+        Some(CameraRay {
+            ray: Ray {
+                origin: self.frame.origin + offset,
+                direction,
+            },
+            weight,
+        })
+    }
+
+    /// Off-axis stereo: both eyes look the same way and their films slide towards each other
+    /// until they line up at the convergence distance, which avoids the vertical parallax of turning the eyes in
+    fn stereo_pair(&self, rig: &StereoRig) -> Result<[Box<dyn Camera>; 2]> {
+        rig.validate()?;
+
+        let eye = |side: Float| {
+            let offset = side * rig.interocular_distance / 2.0 * self.frame.u;
+            let mut camera = self.clone();
+            camera.frame.origin += offset;
+            camera.lower_left_corner += offset;
+            if let Some(convergence_distance) = rig.convergence_distance {
+                // `lower_left_corner` is on the plane in focus
+                camera.lower_left_corner -= self.focus_dist / convergence_distance * offset;
+            }
+            Box::new(camera) as Box<dyn Camera>
+        };
+
+        Ok([eye(-1.0), eye(1.0)])
+    }
+
+    fn with_thin_lens(&self, lens: &ThinLens) -> Result<Box<dyn Camera>> {
+        Ok(Box::new(self.clone().with_thin_lens(lens.clone())?))
+    }
+}
@@ -0,0 +1,584 @@
+//! A camera that traces rays through the spherical elements of a real lens, after pbrt's `RealisticCamera`.
+//!
+//! Everything is computed in lens space, which is the camera's local frame with the film at z = 0
+//! and the lens towards -z, so lens space rays go to world space with `CameraFrame::local_to_world`.
+
+use super::*;
+use std::convert::TryInto;
+use std::path::Path;
+
+#[cfg(test)]
+mod tests;
+
+/// Number of rings across the film that the exit pupil is bounded for
+const EXIT_PUPIL_RINGS: usize = 64;
+/// Rays traced to bound the exit pupil of each ring
+const EXIT_PUPIL_SAMPLES: usize = 1 << 16;
+
+/// One surface of a lens prescription, listed from the front of the lens to the back.
+/// All lengths are in metres
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LensElement {
+    /// Radius of the spherical surface, positive if it bulges towards the scene. 0 for the aperture stop
+    pub curvature_radius: Float,
+    /// Distance along the axis to the next surface, or to the film for the last one
+    pub thickness: Float,
+    /// Index of refraction behind the surface, 0 for the aperture stop which is in air
+    pub eta: Float,
+    pub aperture_radius: Float,
+}
+
+impl LensElement {
+    fn is_stop(&self) -> bool {
+        self.curvature_radius == 0.0
+    }
+}
+
+/// Reads a lens prescription in pbrt's format: one surface per line from the front of the lens to the back,
+/// each with its curvature radius, thickness, index of refraction and aperture diameter in millimetres.
+/// `#` starts a comment
+pub fn read_lens_file(path: &Path) -> Result<Vec<LensElement>> {
+    let text = std::fs::read_to_string(path)?;
+    parse_lens_file(&path.display().to_string(), &text)
+}
+
+fn parse_lens_file(file: &str, text: &str) -> Result<Vec<LensElement>> {
+    let mut elements = vec![];
+
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let error =
+            |message: String| Error::parse(file, format!("line {}: {}", line_number + 1, message));
+
+        let values = line
+            .split_whitespace()
+            .map(|value| {
+                value
+                    .parse::<Float>()
+                    .ok()
+                    .filter(|value| value.is_finite())
+                    .ok_or_else(|| error(format!("{} isn't a number", value)))
+            })
+            .collect::<Result<Vec<Float>>>()?;
+        let [curvature_radius, thickness, eta, aperture_diameter]: [Float; 4] =
+            values.try_into().map_err(|values: Vec<Float>| {
+                error(format!(
+                    "expected a radius, thickness, index of refraction and aperture, got {} values",
+                    values.len()
+                ))
+            })?;
+
+        if thickness < 0.0 {
+            return Err(error(format!(
+                "thickness can't be negative, got {}",
+                thickness
+            )));
+        }
+        if eta < 0.0 || (eta == 0.0) != (curvature_radius == 0.0) {
+            return Err(error(format!(
+                "index of refraction must be positive, or 0 for the aperture stop, got {}",
+                eta
+            )));
+        }
+        if aperture_diameter <= 0.0 {
+            return Err(error(format!(
+                "aperture must be positive, got {}",
+                aperture_diameter
+            )));
+        }
+
+        elements.push(LensElement {
+            curvature_radius: curvature_radius * 0.001,
+            thickness: thickness * 0.001,
+            eta,
+            aperture_radius: aperture_diameter * 0.001 / 2.0,
+        });
+    }
+
+    if elements.is_empty() {
+        return Err(Error::parse(file, "the lens has no elements"));
+    }
+
+    Ok(elements)
+}
+
+/// Lens vignetting, distortion and the cat's eye shape of out of focus highlights come from the prescription
+/// rather than being modelled. Scene units are taken to be metres
+#[derive(Clone)]
+pub struct RealisticCamera {
+    frame: CameraFrame,
+    elements: Vec<LensElement>,
+    /// Half the size of the film in metres
+    film_half_width: Float,
+    film_half_height: Float,
+    /// Bounds of the exit pupil on the plane of the rear element, as seen from each ring of the film
+    /// along the +x axis
+    exit_pupil_bounds: Vec<PupilBounds>,
+}
+
+impl RealisticCamera {
+    /// `aperture_diameter` and `film_diagonal` are in millimetres, the aperture stop of `elements` is set to
+    /// `aperture_diameter` and the film is moved so that objects `focus_distance` away are sharp
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        look_from: Point3,
+        look_at: Point3,
+        view_up: Vector3,
+        mut elements: Vec<LensElement>,
+        aperture_diameter: Float,
+        focus_distance: Float,
+        film_diagonal: Float,
+        aspect_ratio: Float,
+    ) -> Result<Self> {
+        validate_aspect_ratio(aspect_ratio)?;
+        if !(film_diagonal > 0.0 && film_diagonal.is_finite()) {
+            return Err(Error::invalid_parameter(format!(
+                "film diagonal must be positive, got {}",
+                film_diagonal
+            )));
+        }
+        if !(focus_distance > 0.0 && focus_distance.is_finite()) {
+            return Err(Error::invalid_parameter(format!(
+                "camera focus distance must be positive, got {}",
+                focus_distance
+            )));
+        }
+        if elements.is_empty() {
+            return Err(Error::invalid_parameter("the lens has no elements"));
+        }
+        let frame = CameraFrame::new(look_from, look_at, view_up)?;
+
+        for element in elements.iter_mut().filter(|element| element.is_stop()) {
+            let aperture_radius = aperture_diameter * 0.001 / 2.0;
+            if !(aperture_radius > 0.0 && aperture_radius <= element.aperture_radius) {
+                return Err(Error::invalid_parameter(format!(
+                    "aperture diameter must be positive and at most the {}mm of the lens, got {}",
+                    element.aperture_radius * 2000.0,
+                    aperture_diameter
+                )));
+            }
+            element.aperture_radius = aperture_radius;
+        }
+
+        let film_diagonal = film_diagonal * 0.001;
+        let film_half_width =
+            film_diagonal / 2.0 / Float::sqrt(1.0 + 1.0 / (aspect_ratio * aspect_ratio));
+        let film_half_height = film_half_width / aspect_ratio;
+
+        let mut camera = RealisticCamera {
+            frame,
+            elements,
+            film_half_width,
+            film_half_height,
+            exit_pupil_bounds: vec![],
+        };
+
+        let film_distance = camera.focus_thick_lens(focus_distance, film_diagonal)?;
+        camera.elements.last_mut().unwrap().thickness = film_distance;
+
+        let film_radius = film_diagonal / 2.0;
+        camera.exit_pupil_bounds = (0..EXIT_PUPIL_RINGS)
+            .map(|i| {
+                camera.bound_exit_pupil(
+                    film_radius * i as Float / EXIT_PUPIL_RINGS as Float,
+                    film_radius * (i + 1) as Float / EXIT_PUPIL_RINGS as Float,
+                )
+            })
+            .collect();
+
+        Ok(camera)
+    }
+
+    fn rear_z(&self) -> Float {
+        self.elements.last().unwrap().thickness
+    }
+
+    fn front_z(&self) -> Float {
+        self.elements.iter().map(|element| element.thickness).sum()
+    }
+
+    fn rear_radius(&self) -> Float {
+        self.elements.last().unwrap().aperture_radius
+    }
+
+    /// Traces `ray` from the film out through the front of the lens, `None` if the lens blocks it
+    fn trace_from_film(&self, ray: &Ray) -> Option<Ray> {
+        let mut ray = *ray;
+        let mut element_z = 0.0;
+
+        for (i, element) in self.elements.iter().enumerate().rev() {
+            element_z -= element.thickness;
+
+            let (t, normal) = if element.is_stop() {
+                if ray.direction.z >= 0.0 {
+                    return None;
+                }
+                ((element_z - ray.origin.z) / ray.direction.z, None)
+            } else {
+                let (t, normal) = intersect_element(element, element_z, &ray)?;
+                (t, Some(normal))
+            };
+
+            let p = ray.at(t);
+            if p.x * p.x + p.y * p.y > element.aperture_radius * element.aperture_radius {
+                return None;
+            }
+            ray.origin = p;
+
+            if let Some(normal) = normal {
+                let eta_i = element.eta;
+                let eta_t = match i.checked_sub(1).map(|i| self.elements[i].eta) {
+                    Some(eta) if eta != 0.0 => eta,
+                    _ => 1.0,
+                };
+                ray.direction = refract(
+                    &-Vector3::unit_vector(ray.direction),
+                    &normal,
+                    eta_i / eta_t,
+                )?;
+            }
+        }
+
+        Some(ray)
+    }
+
+    /// Traces `ray` from the scene in through the front of the lens to the film, `None` if the lens blocks it
+    fn trace_from_scene(&self, ray: &Ray) -> Option<Ray> {
+        let mut ray = *ray;
+        let mut element_z = -self.front_z();
+
+        for (i, element) in self.elements.iter().enumerate() {
+            let (t, normal) = if element.is_stop() {
+                ((element_z - ray.origin.z) / ray.direction.z, None)
+            } else {
+                let (t, normal) = intersect_element(element, element_z, &ray)?;
+                (t, Some(normal))
+            };
+
+            let p = ray.at(t);
+            if p.x * p.x + p.y * p.y > element.aperture_radius * element.aperture_radius {
+                return None;
+            }
+            ray.origin = p;
+
+            if let Some(normal) = normal {
+                let eta_i = match i.checked_sub(1).map(|i| self.elements[i].eta) {
+                    Some(eta) if eta != 0.0 => eta,
+                    _ => 1.0,
+                };
+                let eta_t = if element.eta != 0.0 { element.eta } else { 1.0 };
+                ray.direction = refract(
+                    &-Vector3::unit_vector(ray.direction),
+                    &normal,
+                    eta_i / eta_t,
+                )?;
+            }
+
+            element_z += element.thickness;
+        }
+
+        Some(ray)
+    }
+
+    /// The z of the principal plane and the focal point of a thick lens that approximates the real one,
+    /// on the scene side first and then on the film side
+    fn thick_lens_approximation(&self, film_diagonal: Float) -> Result<([Float; 2], [Float; 2])> {
+        // Close enough to the axis for the paraxial approximation
+        let x = 0.001 * film_diagonal;
+        let no_paraxial_ray =
+            || Error::invalid_parameter("the lens doesn't let a ray along its axis through");
+
+        let from_scene = Ray {
+            origin: Point3::new(x, 0.0, -(self.front_z() + 1.0)),
+            direction: Vector3::new(0.0, 0.0, 1.0),
+        };
+        let to_film = self
+            .trace_from_scene(&from_scene)
+            .ok_or_else(no_paraxial_ray)?;
+        let (principal_scene, focal_scene) = cardinal_points(&from_scene, &to_film);
+
+        let from_film = Ray {
+            origin: Point3::new(x, 0.0, 1.0 - self.rear_z()),
+            direction: Vector3::new(0.0, 0.0, -1.0),
+        };
+        let to_scene = self
+            .trace_from_film(&from_film)
+            .ok_or_else(no_paraxial_ray)?;
+        let (principal_film, focal_film) = cardinal_points(&from_film, &to_scene);
+
+        Ok(([principal_scene, principal_film], [focal_scene, focal_film]))
+    }
+
+    /// Distance from the rear element to the film that brings `focus_distance` into focus
+    fn focus_thick_lens(&self, focus_distance: Float, film_diagonal: Float) -> Result<Float> {
+        let (pz, fz) = self.thick_lens_approximation(film_diagonal)?;
+        let f = fz[0] - pz[0];
+        let z = -focus_distance;
+
+        let c = (pz[1] - z - pz[0]) * (pz[1] - z - 4.0 * f - pz[0]);
+        if c.is_nan() || c <= 0.0 {
+            return Err(Error::invalid_parameter(format!(
+                "the lens can't focus at {}, it's closer than its minimum focus distance",
+                focus_distance
+            )));
+        }
+        let delta = 0.5 * (pz[1] - z + pz[0] - Float::sqrt(c));
+        let film_distance = self.rear_z() + delta;
+        if film_distance <= 0.0 {
+            return Err(Error::invalid_parameter(format!(
+                "the lens can't focus at {}, the film would have to be inside it",
+                focus_distance
+            )));
+        }
+
+        Ok(film_distance)
+    }
+
+    /// Bounds of the points on the rear element that rays from the film between `x0` and `x1`
+    /// along the +x axis make it through the lens from
+    fn bound_exit_pupil(&self, x0: Float, x1: Float) -> PupilBounds {
+        let rear_radius = 1.5 * self.rear_radius();
+        let rear_bounds = PupilBounds {
+            min: [-rear_radius, -rear_radius],
+            max: [rear_radius, rear_radius],
+        };
+
+        let mut pupil_bounds = PupilBounds::EMPTY;
+        for i in 0..EXIT_PUPIL_SAMPLES {
+            let film_x = lerp((i as Float + 0.5) / EXIT_PUPIL_SAMPLES as Float, x0, x1);
+            let rear = rear_bounds.lerp([radical_inverse(2, i), radical_inverse(3, i)]);
+
+            let p_film = Point3::new(film_x, 0.0, 0.0);
+            let p_rear = Point3::new(rear[0], rear[1], -self.rear_z());
+            if pupil_bounds.contains(rear)
+                || self
+                    .trace_from_film(&Ray {
+                        origin: p_film,
+                        direction: p_rear - p_film,
+                    })
+                    .is_some()
+            {
+                pupil_bounds.add(rear);
+            }
+        }
+
+        if pupil_bounds.is_empty() {
+            return rear_bounds;
+        }
+
+        // Grow by about the spacing of the samples, as the pupil is likely a bit larger than what they found
+        pupil_bounds
+            .expand(2.0 * rear_bounds.diagonal() / Float::sqrt(EXIT_PUPIL_SAMPLES as Float));
+        pupil_bounds
+    }
+
+    /// A point on the rear element to trace a ray from `p_film` through and the area it was sampled from
+    fn sample_exit_pupil(&self, p_film: [Float; 2], rng: &mut SmallRng) -> (Point3, Float) {
+        let film_radius = Float::hypot(p_film[0], p_film[1]);
+        let film_diagonal_radius = Float::hypot(self.film_half_width, self.film_half_height);
+        let ring = usize::min(
+            (film_radius / film_diagonal_radius * EXIT_PUPIL_RINGS as Float) as usize,
+            EXIT_PUPIL_RINGS - 1,
+        );
+        let pupil_bounds = self.exit_pupil_bounds[ring];
+        let p_lens = pupil_bounds.lerp([rng.gen(), rng.gen()]);
+
+        // The bounds are for film points along +x, so rotate them around to `p_film`
+        let (sin_theta, cos_theta) = if film_radius != 0.0 {
+            (p_film[1] / film_radius, p_film[0] / film_radius)
+        } else {
+            (0.0, 1.0)
+        };
+
+        (
+            Point3::new(
+                cos_theta * p_lens[0] - sin_theta * p_lens[1],
+                sin_theta * p_lens[0] + cos_theta * p_lens[1],
+                -self.rear_z(),
+            ),
+            pupil_bounds.area(),
+        )
+    }
+}
+
+impl Camera for RealisticCamera {
+    fn get_ray(&self, s: Float, t: Float, rng: &mut SmallRng) -> Option<CameraRay> {
+        // The lens flips the image, so the film is flipped too
+        let p_film = [
+            (1.0 - 2.0 * s) * self.film_half_width,
+            (1.0 - 2.0 * t) * self.film_half_height,
+        ];
+        let (p_rear, pupil_area) = self.sample_exit_pupil(p_film, rng);
+
+        let from_film = Ray {
+            origin: Point3::new(p_film[0], p_film[1], 0.0),
+            direction: p_rear - Point3::new(p_film[0], p_film[1], 0.0),
+        };
+        let ray = self.trace_from_film(&from_film)?;
+
+        // Relative to the centre of the film, so the exposure matches the other cameras there
+        let cos_theta = -Vector3::unit_vector(from_film.direction).z;
+        let cos4_theta = (cos_theta * cos_theta) * (cos_theta * cos_theta);
+        let weight = cos4_theta * pupil_area / self.exit_pupil_bounds[0].area();
+
+        Some(CameraRay {
+            ray: Ray {
+                origin: self.frame.origin + self.frame.local_to_world(ray.origin),
+                direction: Vector3::unit_vector(self.frame.local_to_world(ray.direction)),
+            },
+            weight: Color3::new(weight, weight, weight),
+        })
+    }
+}
+
+/// Axis aligned bounds on the plane of the rear element
+#[derive(Clone, Copy, Debug)]
+struct PupilBounds {
+    min: [Float; 2],
+    max: [Float; 2],
+}
+
+impl PupilBounds {
+    const EMPTY: PupilBounds = PupilBounds {
+        min: [Float::MAX, Float::MAX],
+        max: [-Float::MAX, -Float::MAX],
+    };
+
+    fn is_empty(&self) -> bool {
+        self.min[0] > self.max[0] || self.min[1] > self.max[1]
+    }
+
+    fn contains(&self, p: [Float; 2]) -> bool {
+        (0..2).all(|i| self.min[i] <= p[i] && p[i] <= self.max[i])
+    }
+
+    fn add(&mut self, p: [Float; 2]) {
+        for (i, p) in p.iter().enumerate() {
+            self.min[i] = Float::min(self.min[i], *p);
+            self.max[i] = Float::max(self.max[i], *p);
+        }
+    }
+
+    fn expand(&mut self, delta: Float) {
+        for i in 0..2 {
+            self.min[i] -= delta;
+            self.max[i] += delta;
+        }
+    }
+
+    fn lerp(&self, t: [Float; 2]) -> [Float; 2] {
+        [
+            lerp(t[0], self.min[0], self.max[0]),
+            lerp(t[1], self.min[1], self.max[1]),
+        ]
+    }
+
+    fn diagonal(&self) -> Float {
+        Float::hypot(self.max[0] - self.min[0], self.max[1] - self.min[1])
+    }
+
+    fn area(&self) -> Float {
+        (self.max[0] - self.min[0]) * (self.max[1] - self.min[1])
+    }
+}
+
+/// Intersects `ray` with the spherical surface of `element`, whose vertex is on the axis at `element_z`.
+/// The normal faces back along the ray
+fn intersect_element(
+    element: &LensElement,
+    element_z: Float,
+    ray: &Ray,
+) -> Option<(Float, Vector3)> {
+    let radius = element.curvature_radius as f64;
+    let z_center = element_z as f64 + radius;
+    let o = [
+        ray.origin.x as f64,
+        ray.origin.y as f64,
+        ray.origin.z as f64 - z_center,
+    ];
+    let d = [
+        ray.direction.x as f64,
+        ray.direction.y as f64,
+        ray.direction.z as f64,
+    ];
+
+    let a = d[0] * d[0] + d[1] * d[1] + d[2] * d[2];
+    let b = 2.0 * (d[0] * o[0] + d[1] * o[1] + d[2] * o[2]);
+    let c = o[0] * o[0] + o[1] * o[1] + o[2] * o[2] - radius * radius;
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    // Numerically stable roots
+    let q = if b < 0.0 {
+        -0.5 * (b - discriminant.sqrt())
+    } else {
+        -0.5 * (b + discriminant.sqrt())
+    };
+    let (t0, t1) = (q / a, c / q);
+    let (t0, t1) = (f64::min(t0, t1), f64::max(t0, t1));
+
+    // Which of the two hits is the lens surface depends on the way the ray goes and the way the surface bulges
+    let use_closer = (ray.direction.z > 0.0) != (element.curvature_radius < 0.0);
+    let t = if use_closer { t0 } else { t1 };
+    if t.is_nan() || t < 0.0 {
+        return None;
+    }
+
+    let normal = Vector3::unit_vector(Vector3::new(
+        (o[0] + t * d[0]) as Float,
+        (o[1] + t * d[1]) as Float,
+        (o[2] + t * d[2]) as Float,
+    ));
+    let normal = if Vector3::dot(&normal, &ray.direction) > 0.0 {
+        -normal
+    } else {
+        normal
+    };
+
+    Some((t as Float, normal))
+}
+
+/// Refracts `wi`, which points away from the surface on the same side as `normal`,
+/// `None` on total internal reflection
+fn refract(wi: &Vector3, normal: &Vector3, eta: Float) -> Option<Vector3> {
+    let cos_theta_i = Vector3::dot(normal, wi);
+    let sin2_theta_i = Float::max(0.0, 1.0 - cos_theta_i * cos_theta_i);
+    let sin2_theta_t = eta * eta * sin2_theta_i;
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+    let cos_theta_t = Float::sqrt(1.0 - sin2_theta_t);
+
+    Some(eta * -*wi + (eta * cos_theta_i - cos_theta_t) * *normal)
+}
+
+/// Where the ray that left the lens parallel to the axis crosses the axis, which is the focal point,
+/// and where it crosses the height it came in at, which is the principal plane
+fn cardinal_points(ray_in: &Ray, ray_out: &Ray) -> (Float, Float) {
+    let t_focal = -ray_out.origin.x / ray_out.direction.x;
+    let t_principal = (ray_in.origin.x - ray_out.origin.x) / ray_out.direction.x;
+    (ray_out.at(t_principal).z, ray_out.at(t_focal).z)
+}
+
+fn lerp(t: Float, a: Float, b: Float) -> Float {
+    (1.0 - t) * a + t * b
+}
+
+/// Van der Corput sequence in `base`, which gives the Halton sequence over a few prime bases
+fn radical_inverse(base: usize, mut i: usize) -> Float {
+    let inverse_base = 1.0 / base as f64;
+    let mut inverse = inverse_base;
+    let mut result = 0.0;
+    while i > 0 {
+        result += (i % base) as f64 * inverse;
+        i /= base;
+        inverse *= inverse_base;
+    }
+    Float::min(result as Float, ONE_MINUS_EPSILON)
+}
@@ -0,0 +1,128 @@
+//! Checks the realistic camera against the double Gauss lens that ships in `lenses/`
+
+use super::*;
+
+fn dgauss() -> Vec<LensElement> {
+    read_lens_file(&Path::new(env!("CARGO_MANIFEST_DIR")).join("lenses/dgauss.50mm.dat")).unwrap()
+}
+
+/// Looks down -z from the origin, f/5 and focused 5 metres away on a 35mm film
+fn camera() -> RealisticCamera {
+    RealisticCamera::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(0.0, 0.0, -1.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        dgauss(),
+        10.0,
+        5.0,
+        35.0,
+        1.5,
+    )
+    .unwrap()
+}
+
+#[test]
+fn reads_a_lens_file() {
+    let elements = dgauss();
+    assert_eq!(elements.len(), 11);
+
+    // Millimetres and diameters in the file, metres and radii once read
+    let front = elements[0];
+    let expected = [0.029475, 0.00376, 1.67, 0.0126];
+    let read = [
+        front.curvature_radius,
+        front.thickness,
+        front.eta,
+        front.aperture_radius,
+    ];
+    for (read, expected) in read.iter().zip(&expected) {
+        assert!((read - expected).abs() < 1e-6, "{:?}", front);
+    }
+    assert!(elements[5].is_stop());
+    assert_eq!(elements[5].eta, 0.0);
+    assert_eq!(
+        elements.iter().filter(|element| element.is_stop()).count(),
+        1
+    );
+
+    let broken = [
+        "",
+        "# only a comment",
+        "29.475 3.76 1.67",
+        "29.475 3.76 1.67 25.2 1",
+        "29.475 -3.76 1.67 25.2",
+        "29.475 3.76 1.67 0",
+        "29.475 3.76 0 25.2",
+        "0 3.76 1.67 25.2",
+        "29.475 3.76 1.67 wide",
+    ];
+    for text in broken {
+        assert!(
+            parse_lens_file("broken.dat", text).is_err(),
+            "{:?} should be rejected",
+            text
+        );
+    }
+}
+
+#[test]
+fn rays_from_the_centre_of_the_film_meet_at_the_focus_distance() {
+    let camera = camera();
+
+    // Close to the axis so that spherical aberration doesn't move the focus
+    for &height in &[0.0001, 0.0002, 0.0005] {
+        let from_film = Ray {
+            origin: Point3::new(0.0, 0.0, 0.0),
+            direction: Point3::new(height, 0.0, -camera.rear_z()),
+        };
+        let ray = camera.trace_from_film(&from_film).unwrap();
+
+        let t = -ray.origin.x / ray.direction.x;
+        let focus = -ray.at(t).z;
+        assert!(
+            (focus - 5.0).abs() < 0.05,
+            "a ray through {} from the axis is focused at {}",
+            height,
+            focus
+        );
+    }
+}
+
+#[test]
+fn exit_pupil_bounds_hold_every_ray_that_gets_through() {
+    let camera = camera();
+    let film_radius = Float::hypot(camera.film_half_width, camera.film_half_height);
+    let rear_radius = 1.5 * camera.rear_radius();
+    let mut rng = SmallRng::seed_from_u64(0);
+
+    assert_eq!(camera.exit_pupil_bounds.len(), EXIT_PUPIL_RINGS);
+    // Straight down the axis always gets through
+    assert!(camera.exit_pupil_bounds[0].contains([0.0, 0.0]));
+
+    for (ring, bounds) in camera.exit_pupil_bounds.iter().enumerate() {
+        assert!(!bounds.is_empty());
+        assert!(bounds.area() > 0.0);
+
+        for _ in 0..2000 {
+            let film_x =
+                film_radius * (ring as Float + rng.gen::<Float>()) / EXIT_PUPIL_RINGS as Float;
+            let rear = [
+                rear_radius * (2.0 * rng.gen::<Float>() - 1.0),
+                rear_radius * (2.0 * rng.gen::<Float>() - 1.0),
+            ];
+            let p_film = Point3::new(film_x, 0.0, 0.0);
+            let through = camera.trace_from_film(&Ray {
+                origin: p_film,
+                direction: Point3::new(rear[0], rear[1], -camera.rear_z()) - p_film,
+            });
+            assert!(
+                through.is_none() || bounds.contains(rear),
+                "ring {}: a ray from {} through {:?} gets through outside of {:?}",
+                ring,
+                film_x,
+                rear,
+                bounds
+            );
+        }
+    }
+}
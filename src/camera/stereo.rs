@@ -0,0 +1,42 @@
+use super::*;
+
+/// How far apart two eyes are and where they look, for rendering the left and right views of a scene.
+/// Distances are in scene units
+#[derive(Clone, Copy, Debug)]
+pub struct StereoRig {
+    /// Distance between the centres of the two eyes
+    pub interocular_distance: Float,
+    /// Objects this far away end up in the same place in both views, so they appear at the depth of the screen.
+    /// `None` keeps the eyes parallel, which puts the screen at infinity
+    pub convergence_distance: Option<Float>,
+}
+
+impl Default for StereoRig {
+    /// About the distance between human eyes in metres, looking straight ahead
+    fn default() -> StereoRig {
+        StereoRig {
+            interocular_distance: 0.064,
+            convergence_distance: None,
+        }
+    }
+}
+
+impl StereoRig {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.interocular_distance >= 0.0 && self.interocular_distance.is_finite()) {
+            return Err(Error::invalid_parameter(format!(
+                "interocular distance can't be negative, got {}",
+                self.interocular_distance
+            )));
+        }
+        if let Some(distance) = self.convergence_distance {
+            if !(distance > 0.0 && distance.is_finite()) {
+                return Err(Error::invalid_parameter(format!(
+                    "convergence distance must be positive, got {}",
+                    distance
+                )));
+            }
+        }
+        Ok(())
+    }
+}
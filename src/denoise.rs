@@ -0,0 +1,172 @@
+use super::*;
+
+/// Number of à-trous passes, the filter footprint doubles every pass
+const ITERATIONS: usize = 5;
+
+/// B3 spline used as the base kernel of the wavelet
+const KERNEL: [Float; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+const SIGMA_LUMINANCE: Float = 4.0;
+const SIGMA_NORMAL: Float = 128.0;
+const SIGMA_DEPTH: Float = 1.0;
+const SIGMA_ALBEDO: Float = 0.1;
+
+/// Edge-avoiding à-trous wavelet filter (Dammertz et al. 2010) guided by the albedo, normal and depth AOVs,
+/// with the luminance edge-stopping function scaled by the per-pixel variance like SVGF.
+///
+/// The colour is divided by the albedo before filtering so that texture detail isn't blurred,
+/// and the denoised colour replaces `color` in the film.
+pub fn denoise(film: &mut Film) {
+    let (width, height) = (film.width, film.height);
+
+    let mut illumination = Vec::with_capacity(width * height);
+    let mut variance = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = film.get(x, y);
+            let albedo = demodulation_albedo(pixel);
+            illumination.push(divide(pixel.color, albedo));
+            variance.push(pixel.variance / Float::max(albedo.luminance(), 1e-3).powi(2));
+        }
+    }
+
+    for iteration in 0..ITERATIONS {
+        let step = 1 << iteration;
+        let filtered_variance = blur_variance(&variance, width, height);
+
+        let mut next_illumination = vec![Color3::default(); width * height];
+        let mut next_variance = vec![0.0; width * height];
+
+        for y in 0..height {
+            for x in 0..width {
+                let index = y * width + x;
+                let p = film.get(x, y);
+                let luminance_p = illumination[index].luminance();
+                let sigma_l = SIGMA_LUMINANCE * Float::sqrt(filtered_variance[index]) + 1e-6;
+
+                let mut sum = Color3::default();
+                let mut sum_variance = 0.0;
+                let mut sum_weight = 0.0;
+
+                for (j, &kernel_y) in KERNEL.iter().enumerate() {
+                    let qy = y as isize + (j as isize - 2) * step;
+                    if qy < 0 || qy >= height as isize {
+                        continue;
+                    }
+
+                    for (i, &kernel_x) in KERNEL.iter().enumerate() {
+                        let qx = x as isize + (i as isize - 2) * step;
+                        if qx < 0 || qx >= width as isize {
+                            continue;
+                        }
+
+                        let (qx, qy) = (qx as usize, qy as usize);
+                        let q_index = qy * width + qx;
+                        let q = film.get(qx, qy);
+
+                        let w_luminance = Float::exp(
+                            -Float::abs(luminance_p - illumination[q_index].luminance()) / sigma_l,
+                        );
+                        let w_normal = normal_weight(p, q);
+                        let w_depth = depth_weight(p, q, step as Float);
+                        let w_albedo = Float::exp(
+                            -(p.albedo - q.albedo).length_squared() / (SIGMA_ALBEDO * SIGMA_ALBEDO),
+                        );
+
+                        let h = kernel_x * kernel_y;
+                        let weight = h * w_luminance * w_normal * w_depth * w_albedo;
+
+                        sum += weight * illumination[q_index];
+                        sum_variance += weight * weight * variance[q_index];
+                        sum_weight += weight;
+                    }
+                }
+
+                // The centre pixel always has a non-zero weight so this can't divide by zero
+                next_illumination[index] = sum / sum_weight;
+                next_variance[index] = sum_variance / (sum_weight * sum_weight);
+            }
+        }
+
+        illumination = next_illumination;
+        variance = next_variance;
+    }
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut pixel = *film.get(x, y);
+            pixel.color = illumination[y * width + x] * demodulation_albedo(&pixel);
+            pixel.variance = variance[y * width + x];
+            film.set(x, y, pixel);
+        }
+    }
+}
+
+/// Pixels without an albedo (e.g. a black material) are filtered as is
+fn demodulation_albedo(pixel: &Pixel) -> Color3 {
+    let or_one = |albedo: Float| if albedo > 1e-3 { albedo } else { 1.0 };
+
+    Color3::new(
+        or_one(pixel.albedo.x),
+        or_one(pixel.albedo.y),
+        or_one(pixel.albedo.z),
+    )
+}
+
+fn divide(a: Color3, b: Color3) -> Color3 {
+    Color3::new(a.x / b.x, a.y / b.y, a.z / b.z)
+}
+
+fn normal_weight(p: &Pixel, q: &Pixel) -> Float {
+    // Background pixels have no normal
+    if p.normal.near_zero() || q.normal.near_zero() {
+        return if p.normal.near_zero() == q.normal.near_zero() {
+            1.0
+        } else {
+            0.0
+        };
+    }
+
+    Float::max(0.0, Vector3::dot(&p.normal, &q.normal)).powf(SIGMA_NORMAL)
+}
+
+fn depth_weight(p: &Pixel, q: &Pixel, step: Float) -> Float {
+    if !p.depth.is_finite() || !q.depth.is_finite() {
+        return if p.depth.is_finite() == q.depth.is_finite() {
+            1.0
+        } else {
+            0.0
+        };
+    }
+
+    // Relative to the depth so that the same threshold works near and far from the camera
+    let tolerance = SIGMA_DEPTH * p.depth * 0.01 * step + 1e-6;
+    Float::exp(-Float::abs(p.depth - q.depth) / tolerance)
+}
+
+/// 3x3 gaussian blur of the variance to make the luminance edge-stopping function more robust
+fn blur_variance(variance: &[Float], width: usize, height: usize) -> Vec<Float> {
+    const GAUSSIAN: [Float; 3] = [0.25, 0.5, 0.25];
+
+    let mut blurred = vec![0.0; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = 0.0;
+            let mut sum_weight = 0.0;
+            for (j, &gy) in GAUSSIAN.iter().enumerate() {
+                for (i, &gx) in GAUSSIAN.iter().enumerate() {
+                    let qx = x as isize + i as isize - 1;
+                    let qy = y as isize + j as isize - 1;
+                    if qx < 0 || qy < 0 || qx >= width as isize || qy >= height as isize {
+                        continue;
+                    }
+                    sum += gx * gy * variance[qy as usize * width + qx as usize];
+                    sum_weight += gx * gy;
+                }
+            }
+            blurred[y * width + x] = sum / sum_weight;
+        }
+    }
+
+    blurred
+}
@@ -0,0 +1,199 @@
+use super::*;
+use std::collections::HashMap;
+
+/// Only this many invalid samples are kept for debugging, they are still all counted
+const MAX_RECORDED_SAMPLES: usize = 1000;
+
+/// A sample whose radiance was NaN or infinite.
+/// The pixel and sample index are enough to trace the exact same path again with `trace_sample`.
+#[derive(Clone, Copy, Debug)]
+pub struct InvalidSample {
+    pub x: usize,
+    pub y: usize,
+    pub sample: usize,
+    pub value: Color3,
+    pub material_id: u32,
+}
+
+#[derive(Default)]
+pub struct Diagnostics {
+    /// Number of NaN samples per material id
+    nan: HashMap<u32, usize>,
+    /// Number of infinite samples per material id
+    infinite: HashMap<u32, usize>,
+    samples: Vec<InvalidSample>,
+}
+
+impl Diagnostics {
+    pub fn record(&mut self, sample: InvalidSample) {
+        let counts = if sample.value.is_nan() {
+            &mut self.nan
+        } else {
+            &mut self.infinite
+        };
+        *counts.entry(sample.material_id).or_insert(0) += 1;
+
+        if self.samples.len() < MAX_RECORDED_SAMPLES {
+            self.samples.push(sample);
+        }
+    }
+
+    pub fn merge(&mut self, other: Diagnostics) {
+        for (material_id, count) in other.nan {
+            *self.nan.entry(material_id).or_insert(0) += count;
+        }
+        for (material_id, count) in other.infinite {
+            *self.infinite.entry(material_id).or_insert(0) += count;
+        }
+
+        let space = MAX_RECORDED_SAMPLES - self.samples.len();
+        self.samples.extend(other.samples.into_iter().take(space));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nan.is_empty() && self.infinite.is_empty()
+    }
+
+    /// Counts of NaN and infinite samples broken down by material
+    pub fn report(&self) -> String {
+        let total_nan: usize = self.nan.values().sum();
+        let total_infinite: usize = self.infinite.values().sum();
+
+        let mut report = format!(
+            "{} NaN and {} infinite samples were discarded\n",
+            total_nan, total_infinite
+        );
+
+        let mut material_ids: Vec<u32> = self
+            .nan
+            .keys()
+            .chain(self.infinite.keys())
+            .copied()
+            .collect();
+        material_ids.sort_unstable();
+        material_ids.dedup();
+
+        for material_id in material_ids {
+            report += &format!(
+                "    material {}: {} NaN, {} infinite\n",
+                material_id,
+                self.nan.get(&material_id).unwrap_or(&0),
+                self.infinite.get(&material_id).unwrap_or(&0)
+            );
+        }
+
+        report
+    }
+
+    /// Traces every recorded sample again and writes out each bounce of its path.
+    /// `scene` and `settings` must be the ones the samples were rendered with.
+    pub fn write_paths(
+        &self,
+        path: &std::path::Path,
+        scene: &Scene,
+        settings: &RenderSettings,
+    ) -> Result<()> {
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        // Tracing again isn't part of the render, so nothing is counted
+        stats::set_enabled(false);
+
+        for sample in &self.samples {
+            writeln!(
+                file,
+                "pixel ({}, {}) sample {}: {:?} at material {}",
+                sample.x, sample.y, sample.sample, sample.value, sample.material_id
+            )?;
+
+            let mut vertices = vec![];
+            let mut aov = AovSample::default();
+            trace_sample(
+                scene,
+                settings,
+                sample.x,
+                sample.y,
+                sample.sample,
+                &mut aov,
+                Some(&mut vertices),
+            );
+
+            for (bounce, vertex) in vertices.iter().enumerate() {
+                write_vertex(&mut file, bounce, vertex)?;
+            }
+        }
+
+        file.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Traces every sample of pixel (`x`, `y`) and writes out each bounce of its path,
+/// followed by the resolved pixel
+pub fn trace_pixel(
+    out: &mut impl Write,
+    scene: &Scene,
+    settings: &RenderSettings,
+    x: usize,
+    y: usize,
+) -> Result<()> {
+    if x >= settings.width || y >= settings.height {
+        return Err(Error::invalid_parameter(format!(
+            "pixel ({}, {}) is outside of the {}x{} film",
+            x, y, settings.width, settings.height
+        )));
+    }
+
+    // Nothing is counted, as the statistics of this thread are never collected
+    stats::set_enabled(false);
+    let mut pixel = Pixel::default();
+
+    for sample in 0..settings.samples_per_pixel {
+        let mut vertices = vec![];
+        let mut aov = AovSample::default();
+        let color = trace_sample(scene, settings, x, y, sample, &mut aov, Some(&mut vertices));
+
+        writeln!(
+            out,
+            "sample {}: {} bounces, radiance {:?}",
+            sample,
+            vertices.len(),
+            color
+        )?;
+        for (bounce, vertex) in vertices.iter().enumerate() {
+            write_vertex(out, bounce, vertex)?;
+        }
+
+        if color.is_finite() {
+            pixel.add_sample(color, &aov);
+        }
+    }
+
+    pixel.resolve();
+    writeln!(out, "pixel ({}, {}): {:?}", x, y, pixel.color)?;
+
+    Ok(())
+}
+
+fn write_vertex(out: &mut impl Write, bounce: usize, vertex: &PathVertex) -> io::Result<()> {
+    writeln!(
+        out,
+        "    {}: hit object {} material {} at {:?} normal {:?}",
+        bounce, vertex.object_id, vertex.material_id, vertex.p, vertex.normal
+    )?;
+    writeln!(
+        out,
+        "       in {:?} out {:?}{}",
+        vertex.direction_in,
+        vertex.direction_out,
+        if vertex.is_specular {
+            " (specular)"
+        } else {
+            ""
+        }
+    )?;
+    writeln!(
+        out,
+        "       reflectance {:?} pdf {} light {:?} throughput {:?}",
+        vertex.reflectance, vertex.pdf, vertex.light, vertex.beta
+    )
+}
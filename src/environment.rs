@@ -0,0 +1,24 @@
+use super::*;
+
+/// Light arriving from infinitely far away along every ray that leaves the scene
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Environment {
+    /// Blends from white straight down to light blue straight up
+    #[default]
+    Sky,
+    /// The same radiance from every direction, a white one makes a furnace
+    Constant(Color3),
+}
+
+impl Environment {
+    pub fn radiance(&self, direction: &Vector3) -> Color3 {
+        match self {
+            Environment::Sky => {
+                let unit_direction = Vector3::unit_vector(*direction);
+                let t = 0.5 * (unit_direction.y + 1.0);
+                (1.0 - t) * Color3::new(1.0, 1.0, 1.0) + t * Color3::new(0.5, 0.7, 1.0)
+            }
+            Environment::Constant(radiance) => *radiance,
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    /// Encoding or decoding a PNG or other 8 bit image failed
+    Image(image::ImageError),
+    /// Encoding or decoding an EXR failed
+    Exr(exr::error::Error),
+    /// A scene or other input file is malformed
+    Parse {
+        file: String,
+        message: String,
+    },
+    /// A value that can't be rendered, like a negative radius or a zero-size film
+    InvalidParameter(String),
+}
+
+impl Error {
+    pub fn parse(file: impl Into<String>, message: impl Into<String>) -> Error {
+        Error::Parse {
+            file: file.into(),
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid_parameter(message: impl Into<String>) -> Error {
+        Error::InvalidParameter(message.into())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "I/O error: {}", error),
+            Error::Image(error) => write!(f, "image error: {}", error),
+            Error::Exr(error) => write!(f, "EXR error: {}", error),
+            Error::Parse { file, message } => write!(f, "failed to parse {}: {}", file, message),
+            Error::InvalidParameter(message) => write!(f, "invalid parameter: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            Error::Image(error) => Some(error),
+            Error::Exr(error) => Some(error),
+            Error::Parse { .. } | Error::InvalidParameter(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(error: image::ImageError) -> Error {
+        Error::Image(error)
+    }
+}
+
+impl From<exr::error::Error> for Error {
+    fn from(error: exr::error::Error) -> Error {
+        Error::Exr(error)
+    }
+}
@@ -0,0 +1,336 @@
+use super::*;
+
+/// Arbitrary output variables recorded by `ray_color` for a single sample.
+/// `albedo` and `normal` come from the first non-specular hit so that they see through glass and mirrors,
+/// everything else comes from the first hit.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AovSample {
+    pub albedo: Color3,
+    pub normal: Vector3,
+    /// Distance from the camera to the first hit, infinity if nothing was hit
+    pub depth: Float,
+    pub position: Point3,
+    /// 0 if nothing was hit
+    pub material_id: u32,
+    /// 0 if nothing was hit
+    pub object_id: u32,
+    /// Light that reached the camera after at most one bounce
+    pub direct: Color3,
+    pub indirect: Color3,
+    /// The material where the throughput or direct lighting first became NaN or infinite, 0 if it never did.
+    /// Not written to the film, only used for diagnostics
+    pub invalid_material_id: u32,
+}
+
+/// Accumulated samples for a single pixel
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Pixel {
+    pub color: Color3,
+    pub albedo: Color3,
+    pub normal: Vector3,
+    pub depth: Float,
+    pub position: Point3,
+    pub material_id: u32,
+    pub object_id: u32,
+    pub direct: Color3,
+    pub indirect: Color3,
+    /// Variance of the luminance of `color` as an estimate of the pixel's mean
+    pub variance: Float,
+    luminance_squared: Float,
+    samples: usize,
+    hits: usize,
+}
+
+impl Pixel {
+    pub fn add_sample(&mut self, color: Color3, aov: &AovSample) {
+        if self.samples == 0 {
+            self.material_id = aov.material_id;
+            self.object_id = aov.object_id;
+        }
+        self.samples += 1;
+
+        self.color += color;
+        self.luminance_squared += color.luminance() * color.luminance();
+        self.albedo += aov.albedo;
+        self.normal += aov.normal;
+        self.direct += aov.direct;
+        self.indirect += aov.indirect;
+
+        // Depth and position only make sense for samples that hit something
+        if aov.depth.is_finite() {
+            self.hits += 1;
+            self.depth += aov.depth;
+            self.position += aov.position;
+        }
+    }
+
+    /// The floating point channel with the same name as in `Film::write_exr`
+    fn channel_mut(&mut self, name: &str) -> Option<&mut Float> {
+        Some(match name {
+            "R" => &mut self.color.x,
+            "G" => &mut self.color.y,
+            "B" => &mut self.color.z,
+            "albedo.R" => &mut self.albedo.x,
+            "albedo.G" => &mut self.albedo.y,
+            "albedo.B" => &mut self.albedo.z,
+            "normal.X" => &mut self.normal.x,
+            "normal.Y" => &mut self.normal.y,
+            "normal.Z" => &mut self.normal.z,
+            "position.X" => &mut self.position.x,
+            "position.Y" => &mut self.position.y,
+            "position.Z" => &mut self.position.z,
+            "direct.R" => &mut self.direct.x,
+            "direct.G" => &mut self.direct.y,
+            "direct.B" => &mut self.direct.z,
+            "indirect.R" => &mut self.indirect.x,
+            "indirect.G" => &mut self.indirect.y,
+            "indirect.B" => &mut self.indirect.z,
+            "variance.Y" => &mut self.variance,
+            "depth.Z" => &mut self.depth,
+            _ => return None,
+        })
+    }
+
+    /// Turns the sums into averages, should be called once all the samples are added
+    pub fn resolve(&mut self) {
+        if self.samples > 0 {
+            let scale = 1.0 / self.samples as Float;
+            self.color *= scale;
+            self.albedo *= scale;
+            self.direct *= scale;
+            self.indirect *= scale;
+            if !self.normal.near_zero() {
+                self.normal = Vector3::unit_vector(self.normal);
+            }
+
+            let mean = self.color.luminance();
+            let sample_variance = Float::max(0.0, self.luminance_squared * scale - mean * mean);
+            self.variance = sample_variance * scale;
+        }
+
+        if self.hits > 0 {
+            let scale = 1.0 / self.hits as Float;
+            self.depth *= scale;
+            self.position *= scale;
+        } else {
+            self.depth = Float::INFINITY;
+        }
+    }
+}
+
+/// The rendered image along with all of its AOVs.
+/// Pixel (0, 0) is the bottom left corner.
+pub struct Film {
+    pub width: usize,
+    pub height: usize,
+    /// NaN and infinite samples that were discarded while rendering. Each pixel is the average of its other samples,
+    /// and one without any is black
+    pub diagnostics: Diagnostics,
+    pub stats: RenderStats,
+    pixels: Vec<Pixel>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Film {
+        Film {
+            width,
+            height,
+            diagnostics: Diagnostics::default(),
+            stats: RenderStats::default(),
+            pixels: vec![Pixel::default(); width * height],
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, x: usize, y: usize) -> &Pixel {
+        &self.pixels[y * self.width + x]
+    }
+
+    #[inline]
+    pub fn set(&mut self, x: usize, y: usize, pixel: Pixel) {
+        self.pixels[y * self.width + x] = pixel;
+    }
+
+    /// Only keeps the pixels inside of `crop`, along with the diagnostics and statistics
+    pub fn cropped(self, crop: Crop) -> Film {
+        let mut pixels = Vec::with_capacity(crop.width() * crop.height());
+        for y in crop.y0..crop.y1 {
+            pixels.extend_from_slice(
+                &self.pixels[y * self.width + crop.x0..y * self.width + crop.x1],
+            );
+        }
+
+        Film {
+            width: crop.width(),
+            height: crop.height(),
+            diagnostics: self.diagnostics,
+            stats: self.stats,
+            pixels,
+        }
+    }
+
+    /// Puts views of the same height next to each other from left to right, like the eyes of a stereo pair.
+    /// The statistics are added up, the diagnostics keep the pixel coordinates of the view they came from
+    pub fn side_by_side(views: Vec<Film>) -> Result<Film> {
+        let height = views.first().map_or(0, |view| view.height);
+        if views.iter().any(|view| view.height != height) {
+            return Err(Error::invalid_parameter(
+                "views put side by side must all be the same height",
+            ));
+        }
+
+        let width = views.iter().map(|view| view.width).sum();
+        let mut film = Film::new(width, height);
+        let mut x0 = 0;
+        for view in views {
+            for y in 0..height {
+                film.pixels[y * width + x0..y * width + x0 + view.width]
+                    .clone_from_slice(&view.pixels[y * view.width..(y + 1) * view.width]);
+            }
+            x0 += view.width;
+
+            film.stats.merge(&view.stats);
+            film.stats.bvh_build_time = view.stats.bvh_build_time;
+            film.stats.render_time += view.stats.render_time;
+            film.stats.output_time += view.stats.output_time;
+            film.diagnostics.merge(view.diagnostics);
+        }
+
+        Ok(film)
+    }
+
+    /// The gamma corrected beauty pass as 8 bit RGBA with the top row first, as written by `write_png`
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        let mut image = vec![0u8; self.width * self.height * 4];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let color = self.get(x, y).color;
+                // flip so that the top row comes first
+                let index = ((self.height - 1 - y) * self.width + x) * 4;
+                for k in 0..3 {
+                    // apply gamma correction
+                    image[index + k] = (256.0 * Float::sqrt(color[k]).clamp(0.0, 0.999)) as u8;
+                }
+                image[index + 3] = 255;
+            }
+        }
+
+        image
+    }
+
+    /// Writes the gamma corrected beauty pass
+    pub fn write_png(&self, path: &std::path::Path) -> Result<()> {
+        let image = self.to_rgba8();
+
+        let image_buffer: image::ImageBuffer<image::Rgba<u8>, _> =
+            image::ImageBuffer::from_raw(self.width as u32, self.height as u32, image)
+                .ok_or_else(|| Error::invalid_parameter("film is too large to be written"))?;
+
+        image::DynamicImage::ImageRgba8(image_buffer)
+            .save_with_format(path, image::ImageFormat::Png)?;
+
+        Ok(())
+    }
+
+    /// Writes the linear beauty pass along with every AOV as layers of a single EXR
+    pub fn write_exr(&self, path: &std::path::Path) -> Result<()> {
+        use exr::prelude::*;
+
+        let vector_channels = |name: &str, suffixes: [&str; 3], f: &dyn Fn(&Pixel) -> Vector3| {
+            (0..3)
+                .map(|k| {
+                    let samples = self.top_down().map(|pixel| f(pixel)[k]).collect();
+                    AnyChannel::new(
+                        format!("{}{}", name, suffixes[k]).as_str(),
+                        FlatSamples::F32(samples),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let rgb = ["R", "G", "B"];
+        let xyz = ["X", "Y", "Z"];
+
+        let mut channels = vec![];
+        channels.extend(vector_channels("", rgb, &|pixel| pixel.color));
+        channels.extend(vector_channels("albedo.", rgb, &|pixel| pixel.albedo));
+        channels.extend(vector_channels("normal.", xyz, &|pixel| pixel.normal));
+        channels.extend(vector_channels("position.", xyz, &|pixel| pixel.position));
+        channels.extend(vector_channels("direct.", rgb, &|pixel| pixel.direct));
+        channels.extend(vector_channels("indirect.", rgb, &|pixel| pixel.indirect));
+        channels.push(AnyChannel::new(
+            "variance.Y",
+            FlatSamples::F32(self.top_down().map(|pixel| pixel.variance).collect()),
+        ));
+        channels.push(AnyChannel::new(
+            "depth.Z",
+            FlatSamples::F32(self.top_down().map(|pixel| pixel.depth).collect()),
+        ));
+        channels.push(AnyChannel::new(
+            "material_id.id",
+            FlatSamples::U32(self.top_down().map(|pixel| pixel.material_id).collect()),
+        ));
+        channels.push(AnyChannel::new(
+            "object_id.id",
+            FlatSamples::U32(self.top_down().map(|pixel| pixel.object_id).collect()),
+        ));
+
+        let layer = Layer::new(
+            (self.width, self.height),
+            LayerAttributes::default(),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(SmallVec::from_vec(channels)),
+        );
+
+        Image::from_layer(layer).write().to_file(path)?;
+
+        Ok(())
+    }
+
+    /// Reads back an EXR written by `write_exr`.
+    /// Missing channels are left at their defaults, so plain RGB EXRs can be read as well.
+    pub fn read_exr(path: &std::path::Path) -> Result<Film> {
+        use exr::prelude::*;
+
+        let image = read_first_flat_layer_from_file(path)?;
+        let layer = &image.layer_data;
+        let (width, height) = (layer.size.width(), layer.size.height());
+
+        let mut film = Film::new(width, height);
+
+        for channel in &layer.channel_data.list {
+            let name = channel.name.to_string();
+            let top_down = (0..height)
+                .rev()
+                .flat_map(|y| (0..width).map(move |x| (x, y)));
+
+            for ((x, y), sample) in top_down.zip(channel.sample_data.values()) {
+                let pixel = &mut film.pixels[y * width + x];
+                match name.as_str() {
+                    "material_id.id" => pixel.material_id = to_u32(sample),
+                    "object_id.id" => pixel.object_id = to_u32(sample),
+                    name => {
+                        if let Some(value) = pixel.channel_mut(name) {
+                            *value = sample.to_f32();
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(film)
+    }
+
+    /// Iterates over the pixels with the top row first, which is the order image files expect
+    fn top_down(&self) -> impl Iterator<Item = &Pixel> {
+        self.pixels.chunks(self.width).rev().flatten()
+    }
+}
+
+fn to_u32(sample: exr::prelude::Sample) -> u32 {
+    match sample {
+        exr::prelude::Sample::U32(value) => value,
+        sample => sample.to_f32() as u32,
+    }
+}
@@ -0,0 +1,413 @@
+//! Reads whole scenes from glTF 2.0 files, either `.gltf` with its buffers and images embedded or next to it,
+//! or binary `.glb`. The default scene's node hierarchy is flattened into instances of meshes,
+//! the first camera in it becomes the scene's camera and `KHR_lights_punctual` lights become point,
+//! spot and directional lights.
+//!
+//! Metallic-roughness materials map onto the closest reflectance model: transmissive ones from
+//! `KHR_materials_transmission` onto dielectrics with their `KHR_materials_ior`, metallic ones onto metal
+//! and the rest onto diffuse, all tinted by the base colour texture and vertex colours.
+//! A metal's roughness is kept as its fuzziness, though metal is rendered as a mirror either way.
+//! Partly metallic or transmissive materials go whichever way is closer, diffuse ones lose
+//! their roughness and the base colour's alpha is dropped.
+//!
+//! The base colour texture is read with the uv set it names and its sampler's wrap modes, always bilinearly
+//! filtered without mipmaps whatever the sampler's filters are. Emission, normal, occlusion and
+//! metallic-roughness textures, `KHR_texture_transform`, skins and morph targets are ignored
+
+use super::bounds::Bounds3;
+use super::camera::*;
+use super::hittable::*;
+use super::light::*;
+use super::material::*;
+use super::texture::{srgb_to_linear, ImageTexture, Texture, Wrap};
+use super::transforms::Transform;
+use super::vector::*;
+use super::{Error, Float, Result, Scene};
+use ::gltf::image::Format;
+use ::gltf::khr_lights_punctual::Kind;
+use ::gltf::mesh::Mode;
+use ::gltf::texture::WrappingMode;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Lumens per watt, to turn glTF's photometric light intensities into radiometric ones
+const LUMINOUS_EFFICACY: Float = 683.0;
+/// Vertical field of view of the camera made up for files without one, in degrees
+const DEFAULT_FOV: Float = 40.0;
+
+/// `aspect_ratio` is the film's, which overrides the one a perspective camera in the file may have
+pub fn read_gltf(path: &Path, aspect_ratio: Float) -> Result<Scene> {
+    let file = path.display().to_string();
+    let (document, buffers, images) =
+        ::gltf::import(path).map_err(|error| Error::parse(&file, error.to_string()))?;
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| Error::parse(&file, "there is no scene"))?;
+
+    let mut importer = Importer {
+        file,
+        buffers,
+        images,
+        world: HittableList::default(),
+        materials: MaterialList::default(),
+        lights: LightList::default(),
+        camera: None,
+        bound: Bounds3::default(),
+        meshes: HashMap::new(),
+        material_ids: HashMap::new(),
+        textures: HashMap::new(),
+    };
+    for node in scene.nodes() {
+        importer.add_node(&node, Transform::default(), aspect_ratio)?;
+    }
+
+    // Meshes are only built for primitives that some node shows
+    if importer.meshes.is_empty() {
+        return Err(Error::parse(&importer.file, "the scene has no triangles"));
+    }
+    let camera = match importer.camera {
+        Some(camera) => camera,
+        None => Box::new(default_camera(&importer.bound, aspect_ratio)?),
+    };
+
+    Scene::new(importer.world, importer.materials, importer.lights, camera)
+}
+
+struct Importer {
+    file: String,
+    buffers: Vec<::gltf::buffer::Data>,
+    images: Vec<::gltf::image::Data>,
+    world: HittableList,
+    materials: MaterialList,
+    lights: LightList,
+    camera: Option<Box<dyn Camera>>,
+    /// Of everything in `world`, which isn't initialized yet
+    bound: Bounds3,
+    /// Each primitive is built once however many nodes use its mesh, by mesh and primitive index
+    meshes: HashMap<(usize, usize), Mesh>,
+    /// By glTF material, `None` for the default one, and whether the primitive has vertex colours
+    material_ids: HashMap<(Option<usize>, bool), MaterialId>,
+    /// Decoded base colour images, by glTF image and how they wrap
+    textures: HashMap<(usize, [Wrap; 2]), Arc<ImageTexture>>,
+}
+
+impl Importer {
+    fn add_node(
+        &mut self,
+        node: &::gltf::Node<'_>,
+        parent: Transform,
+        aspect_ratio: Float,
+    ) -> Result<()> {
+        // glTF matrices are stored column by column
+        let columns = node.transform().matrix();
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            *row = columns.map(|column| column[i]);
+        }
+        // A zero scale hides a node and everything under it
+        let transform = match Transform::from_matrix(rows) {
+            Some(local) => parent * local,
+            None => return Ok(()),
+        };
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                if let Some(mesh) = self.primitive_mesh(&mesh, &primitive)? {
+                    let instance = Hittable::Instance(Instance {
+                        object: Box::new(Hittable::Mesh(mesh)),
+                        transform,
+                    });
+                    self.bound = Bounds3::union(&self.bound, &instance.bound());
+                    self.world.add(instance);
+                }
+            }
+        }
+        if let (Some(camera), None) = (node.camera(), &self.camera) {
+            self.camera = Some(self.camera(&camera, &transform, aspect_ratio)?);
+        }
+        if let Some(light) = node.light() {
+            self.lights.add(light_at(&light, &transform));
+        }
+
+        for child in node.children() {
+            self.add_node(&child, transform, aspect_ratio)?;
+        }
+        Ok(())
+    }
+
+    /// `None` for primitives without triangles, like points and lines
+    fn primitive_mesh(
+        &mut self,
+        mesh: &::gltf::Mesh<'_>,
+        primitive: &::gltf::Primitive<'_>,
+    ) -> Result<Option<Mesh>> {
+        let key = (mesh.index(), primitive.index());
+        if let Some(mesh) = self.meshes.get(&key) {
+            return Ok(Some(mesh.clone()));
+        }
+
+        let buffers = &self.buffers;
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data[..]));
+        let positions = match reader.read_positions() {
+            Some(positions) => positions
+                .map(|[x, y, z]| Point3::new(x, y, z))
+                .collect::<Vec<_>>(),
+            None => return Ok(None),
+        };
+        let vertices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        let indices = match primitive.mode() {
+            Mode::Triangles => vertices
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect::<Vec<_>>(),
+            // Every other triangle of a strip is flipped to keep them all facing the same way
+            Mode::TriangleStrip => (2..vertices.len())
+                .map(|i| match i % 2 {
+                    0 => [vertices[i - 2], vertices[i - 1], vertices[i]],
+                    _ => [vertices[i - 1], vertices[i - 2], vertices[i]],
+                })
+                .collect(),
+            Mode::TriangleFan => (2..vertices.len())
+                .map(|i| [vertices[0], vertices[i - 1], vertices[i]])
+                .collect(),
+            _ => return Ok(None),
+        };
+        if indices.is_empty() {
+            return Ok(None);
+        }
+
+        let triangle_mesh = TriangleMesh {
+            positions,
+            normals: reader
+                .read_normals()
+                .map(|normals| normals.map(|[x, y, z]| Vector3::new(x, y, z)).collect()),
+            // The set the base colour texture is looked up with, as it's the only texture used
+            uvs: reader
+                .read_tex_coords(
+                    primitive
+                        .material()
+                        .pbr_metallic_roughness()
+                        .base_color_texture()
+                        .map_or(0, |info| info.tex_coord()),
+                )
+                .map(|uvs| uvs.into_f32().collect()),
+            // Vertex colours are linear already
+            colors: reader.read_colors(0).map(|colors| {
+                colors
+                    .into_rgb_f32()
+                    .map(|[r, g, b]| Color3::new(r, g, b))
+                    .collect()
+            }),
+            indices,
+        };
+        let material = self.material(&primitive.material(), triangle_mesh.colors.is_some())?;
+        let mesh = Mesh::new(triangle_mesh, material).map_err(|error| {
+            Error::parse(
+                &self.file,
+                format!("mesh {} primitive {}: {}", key.0, key.1, error),
+            )
+        })?;
+
+        self.meshes.insert(key, mesh.clone());
+        Ok(Some(mesh))
+    }
+
+    fn material(
+        &mut self,
+        material: &::gltf::Material<'_>,
+        vertex_colors: bool,
+    ) -> Result<MaterialId> {
+        let key = (material.index(), vertex_colors);
+        if let Some(&id) = self.material_ids.get(&key) {
+            return Ok(id);
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        let base_color = Color3::new(r, g, b);
+        let transmission = material
+            .transmission()
+            .map_or(0.0, |transmission| transmission.transmission_factor());
+        let reflectance_model = if transmission > 0.5 {
+            ReflectanceModel::Dielectric(Dielectric {
+                index_of_refraction: material.ior().unwrap_or(1.5),
+            })
+        } else if pbr.metallic_factor() >= 0.5 {
+            ReflectanceModel::Metal(Metal {
+                albedo: base_color,
+                fuzziness: pbr.roughness_factor(),
+            })
+        } else {
+            ReflectanceModel::Diffuse(Diffuse { albedo: base_color })
+        };
+
+        let image = match pbr.base_color_texture() {
+            Some(info) => {
+                let sampler = info.texture().sampler();
+                let wrap = [wrap(sampler.wrap_s()), wrap(sampler.wrap_t())];
+                Some(Texture::Image(
+                    self.texture(info.texture().source().index(), wrap)?,
+                ))
+            }
+            None => None,
+        };
+        let texture = match (image, vertex_colors) {
+            (Some(image), true) => Some(Texture::Product(
+                Box::new(image),
+                Box::new(Texture::VertexColor),
+            )),
+            (Some(image), false) => Some(image),
+            (None, true) => Some(Texture::VertexColor),
+            (None, false) => None,
+        };
+
+        let id = self.materials.add(Material {
+            reflectance_model,
+            texture,
+        });
+        self.material_ids.insert(key, id);
+        Ok(id)
+    }
+
+    /// Base colour images are sRGB encoded unless they're stored as floats
+    fn texture(&mut self, image: usize, wrap: [Wrap; 2]) -> Result<Arc<ImageTexture>> {
+        if let Some(texture) = self.textures.get(&(image, wrap)) {
+            return Ok(texture.clone());
+        }
+
+        let data = self
+            .images
+            .get(image)
+            .ok_or_else(|| Error::parse(&self.file, format!("image {} wasn't loaded", image)))?;
+        let (channels, bytes) = match data.format {
+            Format::R8 => (1, 1),
+            Format::R8G8 => (2, 1),
+            Format::R8G8B8 => (3, 1),
+            Format::R8G8B8A8 => (4, 1),
+            Format::R16 => (1, 2),
+            Format::R16G16 => (2, 2),
+            Format::R16G16B16 => (3, 2),
+            Format::R16G16B16A16 => (4, 2),
+            Format::R32G32B32FLOAT => (3, 4),
+            Format::R32G32B32A32FLOAT => (4, 4),
+        };
+        let channel = |pixel: &[u8], i: usize| {
+            let value = &pixel[i * bytes..(i + 1) * bytes];
+            match bytes {
+                1 => srgb_to_linear(value[0] as Float / u8::MAX as Float),
+                2 => srgb_to_linear(
+                    u16::from_ne_bytes([value[0], value[1]]) as Float / u16::MAX as Float,
+                ),
+                _ => f32::from_ne_bytes([value[0], value[1], value[2], value[3]]) as Float,
+            }
+        };
+        // Grey images, with or without alpha, only have the one colour channel
+        let pixels = data
+            .pixels
+            .chunks_exact(channels * bytes)
+            .map(|pixel| match channels {
+                1 | 2 => Color3::new(1.0, 1.0, 1.0) * channel(pixel, 0),
+                _ => Color3::new(channel(pixel, 0), channel(pixel, 1), channel(pixel, 2)),
+            })
+            .collect();
+
+        let texture = ImageTexture::new(data.width as usize, data.height as usize, pixels, wrap)
+            .map(Arc::new)
+            .map_err(|error| Error::parse(&self.file, format!("image {}: {}", image, error)))?;
+        self.textures.insert((image, wrap), texture.clone());
+        Ok(texture)
+    }
+
+    /// Cameras look down their node's -z axis with +y up
+    fn camera(
+        &self,
+        camera: &::gltf::Camera<'_>,
+        transform: &Transform,
+        aspect_ratio: Float,
+    ) -> Result<Box<dyn Camera>> {
+        let look_from = transform.apply(&Point3::new(0.0, 0.0, 0.0), 1.0);
+        let look_at = look_from + transform.apply(&Vector3::new(0.0, 0.0, -1.0), 0.0);
+        let view_up = transform.apply(&Vector3::new(0.0, 1.0, 0.0), 0.0);
+
+        let camera: Box<dyn Camera> = match camera.projection() {
+            ::gltf::camera::Projection::Perspective(perspective) => {
+                Box::new(PerspectiveCamera::new(
+                    look_from,
+                    look_at,
+                    view_up,
+                    perspective.yfov().to_degrees(),
+                    aspect_ratio,
+                    0.0,
+                    1.0,
+                )?)
+            }
+            // `ymag` is half the height of the view, scaled by the node like everything else
+            ::gltf::camera::Projection::Orthographic(orthographic) => {
+                Box::new(OrthographicCamera::new(
+                    look_from,
+                    look_at,
+                    view_up,
+                    2.0 * orthographic.ymag() * view_up.length(),
+                    aspect_ratio,
+                )?)
+            }
+        };
+        Ok(camera)
+    }
+}
+
+/// Lights shine down their node's -z axis
+fn light_at(light: &::gltf::khr_lights_punctual::Light<'_>, transform: &Transform) -> Light {
+    let [r, g, b] = light.color();
+    let intensity = Color3::new(r, g, b) * light.intensity() / LUMINOUS_EFFICACY;
+    let position = transform.apply(&Point3::new(0.0, 0.0, 0.0), 1.0);
+    let direction = transform.apply(&Vector3::new(0.0, 0.0, -1.0), 0.0);
+
+    match light.kind() {
+        Kind::Directional => Light::Directional(DirectionalLight::new(direction, intensity)),
+        Kind::Point => Light::Point(PointLight {
+            position,
+            intensity,
+        }),
+        Kind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } => Light::Spot(SpotLight::new(
+            position,
+            position + direction,
+            intensity,
+            outer_cone_angle.to_degrees(),
+            inner_cone_angle.to_degrees(),
+        )),
+    }
+}
+
+/// Looks down -z at the whole world from far enough for it to fit in the view
+fn default_camera(bound: &Bounds3, aspect_ratio: Float) -> Result<PerspectiveCamera> {
+    let center = bound.center();
+    let radius = Float::max(bound.diagonal().length() / 2.0, 1e-3);
+    let distance = radius / (DEFAULT_FOV / 2.0).to_radians().sin();
+
+    PerspectiveCamera::new(
+        center + Vector3::new(0.0, 0.0, distance),
+        center,
+        Vector3::new(0.0, 1.0, 0.0),
+        DEFAULT_FOV,
+        aspect_ratio,
+        0.0,
+        distance,
+    )
+}
+
+fn wrap(mode: WrappingMode) -> Wrap {
+    match mode {
+        WrappingMode::Repeat => Wrap::Repeat,
+        WrappingMode::MirroredRepeat => Wrap::MirroredRepeat,
+        WrappingMode::ClampToEdge => Wrap::Clamp,
+    }
+}
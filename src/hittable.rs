@@ -1,24 +1,60 @@
 use super::bounds::Bounds3;
 use super::material::*;
 use super::ray::*;
+use super::transforms::Transform;
 use super::vector::*;
-use super::Float;
+use super::{Error, Float, Result, PI};
 
 mod bvh;
+mod cone;
+mod csg;
+mod cylinder;
+mod disk;
+mod paraboloid;
+mod plane;
+mod quad;
+mod sdf;
+mod triangle;
 
 pub use bvh::HittableList;
+pub use cone::Cone;
+pub use csg::{Csg, CsgOperation};
+pub use cylinder::Cylinder;
+pub use disk::Disk;
+pub use paraboloid::Paraboloid;
+pub use plane::Plane;
+pub use quad::Quad;
+pub use sdf::{Sdf, SdfNode};
+pub use triangle::{Mesh, Triangle, TriangleMesh};
 
 #[derive(Default)]
 pub struct Interaction {
     pub p: Point3,
     pub normal: Vector3,
     pub t: Float,
-    pub material: Option<&'static Material>,
+    pub material: Option<MaterialId>,
+    /// Where on the surface it was hit, both usually from 0 to 1
+    pub uv: [Float; 2],
+    /// Colour carried by the surface itself, like a mesh's vertex colours, for `Texture::VertexColor`
+    pub color: Option<Color3>,
+    /// Set by `HittableList`, 0 means no object
+    pub object_id: u32,
 }
 
 #[derive(Clone)]
 pub enum Hittable {
     Sphere(Sphere),
+    Plane(Plane),
+    Quad(Quad),
+    Disk(Disk),
+    Cylinder(Cylinder),
+    Cone(Cone),
+    Paraboloid(Paraboloid),
+    Sdf(Sdf),
+    Csg(Csg),
+    Triangle(Triangle),
+    Mesh(Mesh),
+    Instance(Instance),
 }
 
 pub use Hittable::*;
@@ -33,12 +69,53 @@ impl Hittable {
     ) -> bool {
         match self {
             Sphere(sphere) => sphere.hit(ray, t_min, t_max, interaction),
+            Plane(plane) => plane.hit(ray, t_min, t_max, interaction),
+            Quad(quad) => quad.hit(ray, t_min, t_max, interaction),
+            Disk(disk) => disk.hit(ray, t_min, t_max, interaction),
+            Cylinder(cylinder) => cylinder.hit(ray, t_min, t_max, interaction),
+            Cone(cone) => cone.hit(ray, t_min, t_max, interaction),
+            Paraboloid(paraboloid) => paraboloid.hit(ray, t_min, t_max, interaction),
+            Sdf(sdf) => sdf.hit(ray, t_min, t_max, interaction),
+            Csg(csg) => csg.hit(ray, t_min, t_max, interaction),
+            Triangle(triangle) => triangle.hit(ray, t_min, t_max, interaction),
+            Mesh(mesh) => mesh.hit(ray, t_min, t_max, interaction),
+            Instance(instance) => instance.hit(ray, t_min, t_max, interaction),
         }
     }
 
+    /// Infinite for planes, which `HittableList` keeps out of its BVH
     pub fn bound(&self) -> Bounds3 {
         match self {
             Sphere(sphere) => sphere.bound(),
+            Plane(plane) => plane.bound(),
+            Quad(quad) => quad.bound(),
+            Disk(disk) => disk.bound(),
+            Cylinder(cylinder) => cylinder.bound(),
+            Cone(cone) => cone.bound(),
+            Paraboloid(paraboloid) => paraboloid.bound(),
+            Sdf(sdf) => sdf.bound(),
+            Csg(csg) => csg.bound(),
+            Triangle(triangle) => triangle.bound(),
+            Mesh(mesh) => mesh.bound(),
+            Instance(instance) => instance.bound(),
+        }
+    }
+
+    /// Checks that the shape is well formed and that its material is in `materials`
+    pub fn validate(&self, materials: &MaterialList) -> Result<()> {
+        match self {
+            Sphere(sphere) => sphere.validate(materials),
+            Plane(plane) => plane.validate(materials),
+            Quad(quad) => quad.validate(materials),
+            Disk(disk) => disk.validate(materials),
+            Cylinder(cylinder) => cylinder.validate(materials),
+            Cone(cone) => cone.validate(materials),
+            Paraboloid(paraboloid) => paraboloid.validate(materials),
+            Sdf(sdf) => sdf.validate(materials),
+            Csg(csg) => csg.validate(materials),
+            Triangle(triangle) => triangle.validate(materials),
+            Mesh(mesh) => mesh.validate(materials),
+            Instance(instance) => instance.validate(materials),
         }
     }
 }
@@ -47,7 +124,7 @@ impl Hittable {
 pub struct Sphere {
     pub position: Vector3,
     pub radius: Float,
-    pub material: &'static Material,
+    pub material: MaterialId,
 }
 
 impl Sphere {
@@ -82,10 +159,32 @@ impl Sphere {
         interaction.p = ray.at(interaction.t);
         interaction.normal = (interaction.p - self.position) / self.radius;
         interaction.material = Some(self.material);
+        // Longitude around y starting at -x, and latitude from the bottom
+        let Vector3 { x, y, z } = interaction.normal;
+        interaction.uv = [
+            (Float::atan2(-z, x) + PI) / (2.0 * PI),
+            Float::acos(Float::clamp(-y, -1.0, 1.0)) / PI,
+        ];
 
         true
     }
 
+    pub fn validate(&self, materials: &MaterialList) -> Result<()> {
+        if !(self.radius > 0.0 && self.radius.is_finite()) {
+            return Err(Error::invalid_parameter(format!(
+                "sphere radius must be positive, got {}",
+                self.radius
+            )));
+        }
+        if !self.position.is_finite() {
+            return Err(Error::invalid_parameter(format!(
+                "sphere position must be finite, got {:?}",
+                self.position
+            )));
+        }
+        validate_material(self.material, materials)
+    }
+
     pub fn bound(&self) -> Bounds3 {
         let radius = Vector3::new(
             Float::abs(self.radius),
@@ -98,3 +197,125 @@ impl Sphere {
         }
     }
 }
+
+/// Another object moved into place by `transform`, which goes from the object's space to the world's.
+/// Changing the transform between frames animates the object
+#[derive(Clone)]
+pub struct Instance {
+    pub object: Box<Hittable>,
+    pub transform: Transform,
+}
+
+impl Instance {
+    pub fn hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        interaction: &mut Interaction,
+    ) -> bool {
+        // The direction isn't normalized so that `t` is the same in both spaces
+        let to_object = self.transform.inverse();
+        let object_ray = Ray {
+            origin: to_object.apply(&ray.origin, 1.0),
+            direction: to_object.apply(&ray.direction, 0.0),
+        };
+
+        if !self.object.hit(&object_ray, t_min, t_max, interaction) {
+            return false;
+        }
+
+        interaction.p = ray.at(interaction.t);
+        interaction.normal = Vector3::unit_vector(self.transform.apply_normal(&interaction.normal));
+
+        true
+    }
+
+    pub fn validate(&self, materials: &MaterialList) -> Result<()> {
+        if !self.transform.is_finite() {
+            return Err(Error::invalid_parameter(
+                "instance transform must be finite and invertible",
+            ));
+        }
+        self.object.validate(materials)
+    }
+
+    /// Bounds all the corners of the object's bound
+    pub fn bound(&self) -> Bounds3 {
+        let bound = self.object.bound();
+
+        let mut corners = Bounds3::default();
+        for i in 0..8 {
+            let corner = Point3::new(
+                bound[i & 1 != 0].x,
+                bound[i & 2 != 0].y,
+                bound[i & 4 != 0].z,
+            );
+            corners = Bounds3::union_point(&corners, &self.transform.apply(&corner, 1.0));
+        }
+        corners
+    }
+}
+
+fn validate_material(material: MaterialId, materials: &MaterialList) -> Result<()> {
+    match materials.get(material) {
+        Some(_) => Ok(()),
+        None => Err(Error::invalid_parameter(format!(
+            "{:?} isn't in the scene's material list",
+            material
+        ))),
+    }
+}
+
+/// Both roots of `a t² + b t + c`, smallest first. Solved in double precision
+/// as the quadrics are prone to cancellation far from the origin
+fn quadratic(a: Float, b: Float, c: Float) -> Option<(Float, Float)> {
+    let (a, b, c) = (a as f64, b as f64, c as f64);
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let t = (-c / b) as Float;
+        return Some((t, t));
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let q = -0.5 * (b + f64::copysign(f64::sqrt(discriminant), b));
+    let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+
+    Some((f64::min(t0, t1) as Float, f64::max(t0, t1) as Float))
+}
+
+/// Angle of `p` around the z axis from +x towards +y, from 0 to 2π
+fn azimuth(p: &Point3) -> Float {
+    let phi = Float::atan2(p.y, p.x);
+    if phi < 0.0 {
+        phi + 2.0 * PI
+    } else {
+        phi
+    }
+}
+
+/// The quadrics can be cut off at an angle around their axis, `phi_max` is in degrees
+fn validate_phi_max(shape: &str, phi_max: Float) -> Result<()> {
+    if !(phi_max > 0.0 && phi_max <= 360.0) {
+        return Err(Error::invalid_parameter(format!(
+            "{} phi max must be more than 0 and at most 360 degrees, got {}",
+            shape, phi_max
+        )));
+    }
+    Ok(())
+}
+
+fn validate_positive(name: &str, value: Float) -> Result<()> {
+    if !(value > 0.0 && value.is_finite()) {
+        return Err(Error::invalid_parameter(format!(
+            "{} must be positive, got {}",
+            name, value
+        )));
+    }
+    Ok(())
+}
@@ -1,15 +1,43 @@
 use super::*;
+use crate::stats;
 use std::rc::Rc;
 
 #[derive(Default)]
 pub struct HittableList {
     objects: Vec<Hittable>,
+    /// The id of each object, which is one more than the order it was added in
+    object_ids: Vec<u32>,
     nodes: Vec<LinearBVHNode>,
+    /// Objects from here on are unbounded like planes, they aren't in the BVH and are tested against every ray
+    num_bounded: usize,
 }
 
 impl HittableList {
-    pub fn add(&mut self, object: Hittable) {
+    /// Returns the object's id, which stays the same when `init` reorders the objects
+    pub fn add(&mut self, object: Hittable) -> u32 {
         self.objects.push(object);
+        self.object_ids.push(self.objects.len() as u32);
+        self.objects.len() as u32
+    }
+
+    /// Changes to the object's bound only take effect after calling `init` again
+    pub fn get_mut(&mut self, object_id: u32) -> Option<&mut Hittable> {
+        let index = self.object_ids.iter().position(|&id| id == object_id)?;
+        Some(&mut self.objects[index])
+    }
+
+    pub fn validate(&self, materials: &MaterialList) -> Result<()> {
+        self.objects
+            .iter()
+            .try_for_each(|object| object.validate(materials))
+    }
+
+    /// Only valid after `init`, and leaves out unbounded objects like planes
+    pub fn bound(&self) -> Bounds3 {
+        match self.nodes.first() {
+            Some(node) => node.bounds,
+            None => Bounds3::default(),
+        }
     }
 
     pub fn hit(
@@ -24,11 +52,8 @@ impl HittableList {
             1.0 / ray.direction.y,
             1.0 / ray.direction.z,
         );
-        let dir_is_neg = [
-            ray.direction.x < 0.0,
-            ray.direction.y < 0.0,
-            ray.direction.z < 0.0,
-        ];
+        // From the inverse so that -0 counts as negative, as its inverse is -infinity
+        let dir_is_neg = [inv_dir.x < 0.0, inv_dir.y < 0.0, inv_dir.z < 0.0];
         let mut temp_interaction = Interaction::default();
         let mut hit_anything = false;
         let mut closest_so_far = t_max;
@@ -38,23 +63,33 @@ impl HittableList {
 
         let mut nodes_to_visit = [usize::MAX; 64];
 
-        loop {
+        let mut nodes_visited = 0;
+        let mut primitive_tests = 0;
+
+        while !self.nodes.is_empty() {
             let node = self.nodes[current_node_index];
+            nodes_visited += 1;
             if node
                 .bounds
                 .intersect(ray, &inv_dir, &dir_is_neg, closest_so_far, t_min)
             {
                 if node.num_hittable > 0 {
+                    primitive_tests += node.num_hittable as u64;
                     for i in 0..node.num_hittable {
                         if let HittableOffset(offset) = node.offset {
+                            // Fresh for every object, so that nothing one shape sets, like a vertex
+                            // colour, is left over for another that doesn't
+                            let mut object_interaction = Interaction::default();
                             if self.objects[offset + i].hit(
                                 ray,
                                 t_min,
                                 closest_so_far,
-                                &mut temp_interaction,
+                                &mut object_interaction,
                             ) {
                                 hit_anything = true;
-                                closest_so_far = temp_interaction.t;
+                                closest_so_far = object_interaction.t;
+                                object_interaction.object_id = self.object_ids[offset + i];
+                                temp_interaction = object_interaction;
                             }
                         }
                     }
@@ -86,38 +121,77 @@ impl HittableList {
             }
         }
 
+        for (object, &object_id) in self.objects[self.num_bounded..]
+            .iter()
+            .zip(&self.object_ids[self.num_bounded..])
+        {
+            primitive_tests += 1;
+            let mut object_interaction = Interaction::default();
+            if object.hit(ray, t_min, closest_so_far, &mut object_interaction) {
+                hit_anything = true;
+                closest_so_far = object_interaction.t;
+                object_interaction.object_id = object_id;
+                temp_interaction = object_interaction;
+            }
+        }
+
+        stats::record(|stats| {
+            stats.bvh_traversals += 1;
+            stats.bvh_nodes_visited += nodes_visited;
+            stats.primitive_tests += primitive_tests;
+        });
+
         *interaction = temp_interaction;
 
         hit_anything
     }
 
     pub fn init(&mut self) {
+        // Unbounded objects would make every node they're in infinite, so they're left out of the BVH
         let mut hittable_info = Vec::with_capacity(self.objects.len());
+        let mut unbounded = vec![];
         for (i, hittable) in self.objects.iter().enumerate() {
             let bound = hittable.bound();
-            hittable_info.push(BVHHittableInfo {
-                hittable_number: i,
-                centroid: bound.center(),
-                bounds: bound,
-            })
+            if bound.p_min.is_finite() && bound.p_max.is_finite() {
+                hittable_info.push(BVHHittableInfo {
+                    hittable_number: i,
+                    centroid: bound.center(),
+                    bounds: bound,
+                })
+            } else {
+                unbounded.push(i);
+            }
         }
 
         let mut total_nodes = 0;
         let mut ordered_hittables = vec![];
         let len = hittable_info.len();
-        let root = self.recursive_build(
-            &mut hittable_info,
-            0,
-            len,
-            &mut total_nodes,
-            &mut ordered_hittables,
-        );
-
-        self.objects = ordered_hittables;
+        let root = (len > 0).then(|| {
+            self.recursive_build(
+                &mut hittable_info,
+                0,
+                len,
+                &mut total_nodes,
+                &mut ordered_hittables,
+            )
+        });
+        self.num_bounded = ordered_hittables.len();
+        ordered_hittables.extend(unbounded);
+
+        self.object_ids = ordered_hittables
+            .iter()
+            .map(|&i| self.object_ids[i])
+            .collect();
+        self.objects = ordered_hittables
+            .iter()
+            .map(|&i| self.objects[i].clone())
+            .collect();
 
         let mut offset = 0;
         self.nodes = vec![LinearBVHNode::default(); total_nodes];
-        self.flatten_bvh(Rc::new(root), &mut offset);
+        if let Some(root) = root {
+            self.flatten_bvh(Rc::new(root), &mut offset);
+        }
     }
 
     fn flatten_bvh(&mut self, node: Rc<BVHBuildNode>, offset: &mut usize) -> usize {
@@ -149,7 +223,7 @@ impl HittableList {
         start: usize,
         end: usize,
         total_nodes: &mut usize,
-        ordered_hittables: &mut Vec<Hittable>,
+        ordered_hittables: &mut Vec<usize>,
     ) -> BVHBuildNode {
         *total_nodes += 1;
 
@@ -163,8 +237,7 @@ impl HittableList {
 
         if num_hittables == 1 {
             let first_hittable = ordered_hittables.len();
-            let hittable_num = hittable_info[start].hittable_number;
-            ordered_hittables.push(self.objects[hittable_num].clone());
+            ordered_hittables.push(hittable_info[start].hittable_number);
             BVHBuildNode::init_leaf(first_hittable, num_hittables, &bounds)
         } else {
             let mut centroid_bounds = Bounds3::default();
@@ -182,8 +255,7 @@ impl HittableList {
             {
                 let first_hittable = ordered_hittables.len();
                 for i in start..end {
-                    let hittable_num = hittable_info[i].hittable_number;
-                    ordered_hittables.push(self.objects[hittable_num].clone());
+                    ordered_hittables.push(hittable_info[i].hittable_number);
                 }
                 BVHBuildNode::init_leaf(first_hittable, num_hittables, &bounds)
             } else {
@@ -271,8 +343,7 @@ impl HittableList {
                     } else {
                         let first_hittable = ordered_hittables.len();
                         for i in start..end {
-                            let hittable_num = hittable_info[i].hittable_number;
-                            ordered_hittables.push(self.objects[hittable_num].clone());
+                            ordered_hittables.push(hittable_info[i].hittable_number);
                         }
                         return BVHBuildNode::init_leaf(first_hittable, num_hittables, &bounds);
                     }
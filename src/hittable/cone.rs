@@ -0,0 +1,70 @@
+use super::*;
+
+/// A cone around the z axis with its base of `radius` at z = 0 and its tip at `height`, without a base.
+/// Only the part from +x to `phi_max` degrees around z is there. Place it elsewhere with an `Instance`
+#[derive(Clone)]
+pub struct Cone {
+    pub height: Float,
+    pub radius: Float,
+    pub phi_max: Float,
+    pub material: MaterialId,
+}
+
+impl Cone {
+    pub fn hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        interaction: &mut Interaction,
+    ) -> bool {
+        // x² + y² = (radius / height)² (height - z)²
+        let (o, d) = (ray.origin, ray.direction);
+        let k = (self.radius / self.height) * (self.radius / self.height);
+        let a = d.x * d.x + d.y * d.y - k * d.z * d.z;
+        let b = 2.0 * (d.x * o.x + d.y * o.y - k * d.z * (o.z - self.height));
+        let c = o.x * o.x + o.y * o.y - k * (o.z - self.height) * (o.z - self.height);
+        let (t0, t1) = match quadratic(a, b, c) {
+            Some(roots) => roots,
+            None => return false,
+        };
+
+        // The equation also has a mirrored cone above the tip, which the range of z cuts off
+        for t in [t0, t1] {
+            if t < t_min || t_max < t {
+                continue;
+            }
+            let p = ray.at(t);
+            let phi = azimuth(&p);
+            if p.z < 0.0 || p.z > self.height || phi > self.phi_max.to_radians() {
+                continue;
+            }
+
+            interaction.t = t;
+            interaction.p = p;
+            // k (height - z) written with the distance from the axis, which stays accurate near the tip
+            let distance = Float::sqrt(p.x * p.x + p.y * p.y);
+            interaction.normal =
+                Vector3::unit_vector(Vector3::new(p.x, p.y, self.radius / self.height * distance));
+            interaction.material = Some(self.material);
+            interaction.uv = [phi / self.phi_max.to_radians(), p.z / self.height];
+            return true;
+        }
+
+        false
+    }
+
+    pub fn validate(&self, materials: &MaterialList) -> Result<()> {
+        validate_positive("cone height", self.height)?;
+        validate_positive("cone radius", self.radius)?;
+        validate_phi_max("cone", self.phi_max)?;
+        validate_material(self.material, materials)
+    }
+
+    pub fn bound(&self) -> Bounds3 {
+        Bounds3 {
+            p_min: Point3::new(-self.radius, -self.radius, 0.0),
+            p_max: Point3::new(self.radius, self.radius, self.height),
+        }
+    }
+}
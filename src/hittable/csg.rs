@@ -0,0 +1,185 @@
+use super::*;
+
+/// Crossings of an operand followed along a ray before giving up, so that no shape can trap it
+const MAX_CROSSINGS: usize = 64;
+/// Least distance to move on past a crossing before looking for the next one. Sphere tracing stops
+/// up to `HIT_DISTANCE` short of a surface, and would stop again straight away if it started within that
+/// on the other side, so this clears both
+const MIN_CROSSING_GAP: Float = 4.0 * sdf::HIT_DISTANCE;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    /// What is in `a` but not in `b`
+    Difference,
+}
+
+impl CsgOperation {
+    fn inside(&self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_a || in_b,
+            CsgOperation::Intersection => in_a && in_b,
+            CsgOperation::Difference => in_a && !in_b,
+        }
+    }
+}
+
+/// A boolean combination of two closed shapes. Every surface keeps the material of the operand it comes from,
+/// and the surfaces of `b` that bound a difference face into the hole it leaves, as if it were a solid
+/// of `b`'s material cut out of `a`
+#[derive(Clone)]
+pub struct Csg {
+    pub operation: CsgOperation,
+    pub a: Box<Hittable>,
+    pub b: Box<Hittable>,
+}
+
+/// Where a ray goes into or out of an operand
+struct Crossing {
+    interaction: Interaction,
+    from_b: bool,
+    entering: bool,
+}
+
+impl Csg {
+    pub fn hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        interaction: &mut Interaction,
+    ) -> bool {
+        // Whether the ray starts inside an operand depends on the crossings before t_min, so they're followed
+        // from the start. Both operands are closed, so the ray starts out of them.
+        // The crossings of both are walked in step, taking whichever is closer next
+        let mut a = Crossings::new(&self.a, false, ray, t_max).peekable();
+        let mut b = Crossings::new(&self.b, true, ray, t_max).peekable();
+
+        let (mut in_a, mut in_b) = (false, false);
+        loop {
+            let crossing = match (a.peek(), b.peek()) {
+                (Some(next_a), Some(next_b)) if next_b.interaction.t < next_a.interaction.t => {
+                    b.next()
+                }
+                (Some(_), _) => a.next(),
+                (None, _) => b.next(),
+            };
+            let crossing = match crossing {
+                Some(crossing) => crossing,
+                None => return false,
+            };
+
+            let inside = self.operation.inside(in_a, in_b);
+            // Setting rather than toggling recovers from a missed crossing, such as one of a grazing double root
+            if crossing.from_b {
+                in_b = crossing.entering;
+            } else {
+                in_a = crossing.entering;
+            }
+            if self.operation.inside(in_a, in_b) == inside || crossing.interaction.t < t_min {
+                continue;
+            }
+
+            *interaction = crossing.interaction;
+            if crossing.from_b && self.operation == CsgOperation::Difference {
+                interaction.normal = -interaction.normal;
+            }
+            return true;
+        }
+    }
+
+    pub fn validate(&self, materials: &MaterialList) -> Result<()> {
+        for operand in [&self.a, &self.b] {
+            if !is_closed(operand) {
+                return Err(Error::invalid_parameter(
+                    "csg operands must be closed: spheres, signed distance fields, other csg or instances of them",
+                ));
+            }
+            operand.validate(materials)?;
+        }
+        Ok(())
+    }
+
+    pub fn bound(&self) -> Bounds3 {
+        let (a, b) = (self.a.bound(), self.b.bound());
+        match self.operation {
+            CsgOperation::Union => Bounds3::union(&a, &b),
+            CsgOperation::Intersection => {
+                let mut bound = a;
+                for axis in 0..3 {
+                    bound.p_min[axis] = Float::max(a.p_min[axis], b.p_min[axis]);
+                    bound.p_max[axis] = Float::min(a.p_max[axis], b.p_max[axis]);
+                }
+                bound
+            }
+            CsgOperation::Difference => a,
+        }
+    }
+}
+
+/// Every surface of `operand` along the whole ray up to `t_max` in order, told apart as entering or leaving
+/// by the outward normal. Each is only looked for once the one before it is used
+struct Crossings<'a> {
+    operand: &'a Hittable,
+    from_b: bool,
+    ray: &'a Ray,
+    t_max: Float,
+    /// Where the next crossing is looked for from
+    t: Float,
+    found: usize,
+}
+
+impl<'a> Crossings<'a> {
+    fn new(operand: &'a Hittable, from_b: bool, ray: &'a Ray, t_max: Float) -> Self {
+        Crossings {
+            operand,
+            from_b,
+            ray,
+            t_max,
+            t: -Float::INFINITY,
+            found: 0,
+        }
+    }
+}
+
+impl Iterator for Crossings<'_> {
+    type Item = Crossing;
+
+    fn next(&mut self) -> Option<Crossing> {
+        if self.found == MAX_CROSSINGS {
+            return None;
+        }
+
+        let mut interaction = Interaction::default();
+        if !self
+            .operand
+            .hit(self.ray, self.t, self.t_max, &mut interaction)
+        {
+            self.found = MAX_CROSSINGS;
+            return None;
+        }
+        self.found += 1;
+
+        // In the ray's own units, as the direction isn't normalized inside instances
+        let speed = self.ray.direction.length();
+        let gap = Float::max(1e-4 * interaction.t.abs() * speed, MIN_CROSSING_GAP);
+        self.t = interaction.t + gap / speed;
+
+        let entering = Vector3::dot(&interaction.normal, &self.ray.direction) < 0.0;
+        Some(Crossing {
+            interaction,
+            from_b: self.from_b,
+            entering,
+        })
+    }
+}
+
+/// Whether the shape has an inside, so that its normals point out and rays alternate between going in and out
+fn is_closed(hittable: &Hittable) -> bool {
+    match hittable {
+        Sphere(_) | Sdf(_) | Csg(_) => true,
+        Instance(instance) => is_closed(&instance.object),
+        _ => false,
+    }
+}
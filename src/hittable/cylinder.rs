@@ -0,0 +1,74 @@
+use super::*;
+
+/// An open tube around the z axis from `z_min` to `z_max`, without caps.
+/// Only the part from +x to `phi_max` degrees around z is there. Place it elsewhere with an `Instance`
+#[derive(Clone)]
+pub struct Cylinder {
+    pub radius: Float,
+    pub z_min: Float,
+    pub z_max: Float,
+    pub phi_max: Float,
+    pub material: MaterialId,
+}
+
+impl Cylinder {
+    pub fn hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        interaction: &mut Interaction,
+    ) -> bool {
+        let (o, d) = (ray.origin, ray.direction);
+        let a = d.x * d.x + d.y * d.y;
+        let b = 2.0 * (d.x * o.x + d.y * o.y);
+        let c = o.x * o.x + o.y * o.y - self.radius * self.radius;
+        let (t0, t1) = match quadratic(a, b, c) {
+            Some(roots) => roots,
+            None => return false,
+        };
+
+        // The nearer root can be cut off, but then the inside is seen through the gap
+        for t in [t0, t1] {
+            if t < t_min || t_max < t {
+                continue;
+            }
+            let p = ray.at(t);
+            let phi = azimuth(&p);
+            if p.z < self.z_min || p.z > self.z_max || phi > self.phi_max.to_radians() {
+                continue;
+            }
+
+            interaction.t = t;
+            interaction.p = p;
+            interaction.normal = Vector3::new(p.x, p.y, 0.0) / self.radius;
+            interaction.material = Some(self.material);
+            interaction.uv = [
+                phi / self.phi_max.to_radians(),
+                (p.z - self.z_min) / (self.z_max - self.z_min),
+            ];
+            return true;
+        }
+
+        false
+    }
+
+    pub fn validate(&self, materials: &MaterialList) -> Result<()> {
+        validate_positive("cylinder radius", self.radius)?;
+        if !(self.z_min < self.z_max && self.z_min.is_finite() && self.z_max.is_finite()) {
+            return Err(Error::invalid_parameter(format!(
+                "cylinder z min must be less than z max, got {} and {}",
+                self.z_min, self.z_max
+            )));
+        }
+        validate_phi_max("cylinder", self.phi_max)?;
+        validate_material(self.material, materials)
+    }
+
+    pub fn bound(&self) -> Bounds3 {
+        Bounds3 {
+            p_min: Point3::new(-self.radius, -self.radius, self.z_min),
+            p_max: Point3::new(self.radius, self.radius, self.z_max),
+        }
+    }
+}
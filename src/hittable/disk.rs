@@ -0,0 +1,80 @@
+use super::*;
+
+/// A disk facing +z at `height`, with a hole of `inner_radius` in the middle.
+/// Only the part from +x to `phi_max` degrees around z is there. Place it elsewhere with an `Instance`
+#[derive(Clone)]
+pub struct Disk {
+    pub height: Float,
+    pub radius: Float,
+    pub inner_radius: Float,
+    pub phi_max: Float,
+    pub material: MaterialId,
+}
+
+impl Disk {
+    pub fn hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        interaction: &mut Interaction,
+    ) -> bool {
+        if ray.direction.z == 0.0 {
+            return false;
+        }
+        let t = (self.height - ray.origin.z) / ray.direction.z;
+        if t < t_min || t_max < t {
+            return false;
+        }
+
+        let p = ray.at(t);
+        let distance_squared = p.x * p.x + p.y * p.y;
+        if distance_squared > self.radius * self.radius
+            || distance_squared < self.inner_radius * self.inner_radius
+        {
+            return false;
+        }
+        let phi = azimuth(&p);
+        if phi > self.phi_max.to_radians() {
+            return false;
+        }
+
+        interaction.t = t;
+        interaction.p = p;
+        interaction.normal = Vector3::new(0.0, 0.0, 1.0);
+        interaction.material = Some(self.material);
+        // v goes from the outer edge to the inner one
+        interaction.uv = [
+            phi / self.phi_max.to_radians(),
+            (self.radius - Float::sqrt(distance_squared)) / (self.radius - self.inner_radius),
+        ];
+
+        true
+    }
+
+    pub fn validate(&self, materials: &MaterialList) -> Result<()> {
+        validate_positive("disk radius", self.radius)?;
+        if !(self.inner_radius >= 0.0 && self.inner_radius < self.radius) {
+            return Err(Error::invalid_parameter(format!(
+                "disk inner radius must be at least 0 and less than the radius {}, got {}",
+                self.radius, self.inner_radius
+            )));
+        }
+        if !self.height.is_finite() {
+            return Err(Error::invalid_parameter(format!(
+                "disk height must be finite, got {}",
+                self.height
+            )));
+        }
+        validate_phi_max("disk", self.phi_max)?;
+        validate_material(self.material, materials)
+    }
+
+    pub fn bound(&self) -> Bounds3 {
+        Bounds3 {
+            p_min: Point3::new(-self.radius, -self.radius, self.height),
+            p_max: Point3::new(self.radius, self.radius, self.height),
+        }
+        .with_min_extent(1e-4)
+    }
+}
@@ -0,0 +1,78 @@
+use super::*;
+
+/// A paraboloid opening towards +z from its tip at the origin, `radius` wide at `z_max` and cut off below `z_min`.
+/// Only the part from +x to `phi_max` degrees around z is there. Place it elsewhere with an `Instance`
+#[derive(Clone)]
+pub struct Paraboloid {
+    pub radius: Float,
+    pub z_min: Float,
+    pub z_max: Float,
+    pub phi_max: Float,
+    pub material: MaterialId,
+}
+
+impl Paraboloid {
+    pub fn hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        interaction: &mut Interaction,
+    ) -> bool {
+        // k (x² + y²) = z
+        let (o, d) = (ray.origin, ray.direction);
+        let k = self.z_max / (self.radius * self.radius);
+        let a = k * (d.x * d.x + d.y * d.y);
+        let b = 2.0 * k * (d.x * o.x + d.y * o.y) - d.z;
+        let c = k * (o.x * o.x + o.y * o.y) - o.z;
+        let (t0, t1) = match quadratic(a, b, c) {
+            Some(roots) => roots,
+            None => return false,
+        };
+
+        for t in [t0, t1] {
+            if t < t_min || t_max < t {
+                continue;
+            }
+            let p = ray.at(t);
+            let phi = azimuth(&p);
+            if p.z < self.z_min || p.z > self.z_max || phi > self.phi_max.to_radians() {
+                continue;
+            }
+
+            interaction.t = t;
+            interaction.p = p;
+            // Points away from the axis, so out of the bowl
+            interaction.normal =
+                Vector3::unit_vector(Vector3::new(2.0 * k * p.x, 2.0 * k * p.y, -1.0));
+            interaction.material = Some(self.material);
+            interaction.uv = [
+                phi / self.phi_max.to_radians(),
+                (p.z - self.z_min) / (self.z_max - self.z_min),
+            ];
+            return true;
+        }
+
+        false
+    }
+
+    pub fn validate(&self, materials: &MaterialList) -> Result<()> {
+        validate_positive("paraboloid radius", self.radius)?;
+        validate_positive("paraboloid z max", self.z_max)?;
+        if !(self.z_min >= 0.0 && self.z_min < self.z_max) {
+            return Err(Error::invalid_parameter(format!(
+                "paraboloid z min must be at least 0 and less than z max {}, got {}",
+                self.z_max, self.z_min
+            )));
+        }
+        validate_phi_max("paraboloid", self.phi_max)?;
+        validate_material(self.material, materials)
+    }
+
+    pub fn bound(&self) -> Bounds3 {
+        Bounds3 {
+            p_min: Point3::new(-self.radius, -self.radius, self.z_min),
+            p_max: Point3::new(self.radius, self.radius, self.z_max),
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use super::*;
+
+/// An infinite plane through `point`. Its bound is infinite, so `HittableList` tests it against every ray
+/// instead of putting it in the BVH
+#[derive(Clone)]
+pub struct Plane {
+    pub point: Point3,
+    pub normal: Vector3,
+    pub material: MaterialId,
+}
+
+impl Plane {
+    pub fn hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        interaction: &mut Interaction,
+    ) -> bool {
+        let denominator = Vector3::dot(&self.normal, &ray.direction);
+        if denominator == 0.0 {
+            return false;
+        }
+
+        let t = Vector3::dot(&self.normal, &(self.point - ray.origin)) / denominator;
+        if t < t_min || t_max < t {
+            return false;
+        }
+
+        interaction.t = t;
+        interaction.p = ray.at(t);
+        interaction.normal = Vector3::unit_vector(self.normal);
+        interaction.material = Some(self.material);
+
+        // Distances along two directions in the plane, so textures tile every unit
+        let (tangent, bitangent) = self.tangents();
+        let offset = interaction.p - self.point;
+        interaction.uv = [
+            Vector3::dot(&offset, &tangent),
+            Vector3::dot(&offset, &bitangent),
+        ];
+
+        true
+    }
+
+    pub fn validate(&self, materials: &MaterialList) -> Result<()> {
+        if !self.point.is_finite() || !self.normal.is_finite() || self.normal.near_zero() {
+            return Err(Error::invalid_parameter(format!(
+                "plane point and normal must be finite and the normal non-zero, got {:?} and {:?}",
+                self.point, self.normal
+            )));
+        }
+        validate_material(self.material, materials)
+    }
+
+    pub fn bound(&self) -> Bounds3 {
+        Bounds3 {
+            p_min: Point3::new(-Float::INFINITY, -Float::INFINITY, -Float::INFINITY),
+            p_max: Point3::new(Float::INFINITY, Float::INFINITY, Float::INFINITY),
+        }
+    }
+
+    fn tangents(&self) -> (Vector3, Vector3) {
+        let normal = Vector3::unit_vector(self.normal);
+        let a = if Float::abs(normal.x) > 0.9 {
+            Vector3::new(0.0, 1.0, 0.0)
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+
+        let bitangent = Vector3::unit_vector(Vector3::cross(&normal, &a));
+        (Vector3::cross(&bitangent, &normal), bitangent)
+    }
+}
@@ -0,0 +1,102 @@
+use super::*;
+
+/// A parallelogram with a corner at `origin` and sides `u` and `v`, which are also the directions of its uv.
+/// The normal is `u × v`
+#[derive(Clone)]
+pub struct Quad {
+    pub origin: Point3,
+    pub u: Vector3,
+    pub v: Vector3,
+    pub material: MaterialId,
+}
+
+impl Quad {
+    /// A rectangle between two opposite corners that differ along exactly two axes.
+    /// The normal points towards the remaining axis
+    pub fn axis_aligned(a: Point3, b: Point3, material: MaterialId) -> Result<Quad> {
+        let flat: Vec<usize> = (0..3).filter(|&axis| a[axis] == b[axis]).collect();
+        if flat.len() != 1 {
+            return Err(Error::invalid_parameter(format!(
+                "an axis aligned quad's corners must differ along exactly two axes, got {:?} and {:?}",
+                a, b
+            )));
+        }
+
+        // The next two axes after the flat one, so that x, y makes a quad facing z and so on
+        let axis_vector = |axis: usize| {
+            let mut vector = Vector3::default();
+            vector[axis] = b[axis] - a[axis];
+            vector
+        };
+        Ok(Quad {
+            origin: a,
+            u: axis_vector((flat[0] + 1) % 3),
+            v: axis_vector((flat[0] + 2) % 3),
+            material,
+        })
+    }
+
+    pub fn hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        interaction: &mut Interaction,
+    ) -> bool {
+        let normal = Vector3::cross(&self.u, &self.v);
+        let denominator = Vector3::dot(&normal, &ray.direction);
+        if denominator == 0.0 {
+            return false;
+        }
+
+        let t = Vector3::dot(&normal, &(self.origin - ray.origin)) / denominator;
+        if t < t_min || t_max < t {
+            return false;
+        }
+
+        // Coordinates of the hit along the sides
+        let p = ray.at(t);
+        let offset = p - self.origin;
+        let w = normal / normal.length_squared();
+        let alpha = Vector3::dot(&w, &Vector3::cross(&offset, &self.v));
+        let beta = Vector3::dot(&w, &Vector3::cross(&self.u, &offset));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return false;
+        }
+
+        interaction.t = t;
+        interaction.p = p;
+        interaction.normal = Vector3::unit_vector(normal);
+        interaction.material = Some(self.material);
+        interaction.uv = [alpha, beta];
+
+        true
+    }
+
+    pub fn validate(&self, materials: &MaterialList) -> Result<()> {
+        if !self.origin.is_finite()
+            || !self.u.is_finite()
+            || !self.v.is_finite()
+            || Vector3::cross(&self.u, &self.v).near_zero()
+        {
+            return Err(Error::invalid_parameter(format!(
+                "quad must be finite with sides that aren't parallel, got {:?}, {:?} and {:?}",
+                self.origin, self.u, self.v
+            )));
+        }
+        validate_material(self.material, materials)
+    }
+
+    pub fn bound(&self) -> Bounds3 {
+        let mut bound = Bounds3::default();
+        for corner in [
+            self.origin,
+            self.origin + self.u,
+            self.origin + self.v,
+            self.origin + self.u + self.v,
+        ] {
+            bound = Bounds3::union_point(&bound, &corner);
+        }
+        bound.with_min_extent(1e-4)
+    }
+}
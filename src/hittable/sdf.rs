@@ -0,0 +1,429 @@
+use super::*;
+
+/// Steps a ray may take before it counts as a miss, fractals near grazing angles need many
+const MAX_STEPS: u32 = 512;
+/// How close to the surface a step has to land to be a hit
+pub(super) const HIT_DISTANCE: Float = 1e-4;
+/// Offset of the samples for the gradient, in both directions along each of the 4 tetrahedron axes
+const NORMAL_OFFSET: Float = 1e-4;
+/// Points further than this from the origin escape the Mandelbulb iteration
+const MANDELBULB_BAILOUT: Float = 2.0;
+
+/// An expression tree of signed distance functions, negative inside the shape.
+/// Besides the primitives, which are centred at the origin, the nodes combine and deform their children
+#[derive(Clone, Debug)]
+pub enum SdfNode {
+    Sphere {
+        radius: Float,
+    },
+    /// A box out to `half_extents` along each axis, with its edges rounded off by `rounding`
+    RoundedBox {
+        half_extents: Vector3,
+        rounding: Float,
+    },
+    /// A ring in the xz plane around the y axis
+    Torus {
+        major_radius: Float,
+        minor_radius: Float,
+    },
+    /// The power `power` Mandelbulb, with the distance estimated after `iterations`
+    Mandelbulb {
+        power: Float,
+        iterations: u32,
+    },
+    Translate {
+        offset: Vector3,
+        node: Box<SdfNode>,
+    },
+    Union(Box<SdfNode>, Box<SdfNode>),
+    Intersection(Box<SdfNode>, Box<SdfNode>),
+    /// Union blended over about `smoothing` with the polynomial smooth minimum
+    SmoothUnion {
+        a: Box<SdfNode>,
+        b: Box<SdfNode>,
+        smoothing: Float,
+    },
+    /// Rotates the child around the y axis by `rate` radians per unit of height
+    Twist {
+        rate: Float,
+        node: Box<SdfNode>,
+    },
+    /// Copies of the child `spacing` apart, with `copies` more on either side of the original along each axis
+    Repeat {
+        spacing: Vector3,
+        copies: [u32; 3],
+        node: Box<SdfNode>,
+    },
+}
+
+impl SdfNode {
+    /// Signed distance from `p` to the surface, or a lower bound on it where the exact distance
+    /// isn't known, as inside a smooth union. Scaled by `lipschitz` it never overshoots
+    pub fn distance(&self, p: Point3) -> Float {
+        match self {
+            SdfNode::Sphere { radius } => p.length() - radius,
+            SdfNode::RoundedBox {
+                half_extents,
+                rounding,
+            } => {
+                let inner = *half_extents - Vector3::new(*rounding, *rounding, *rounding);
+                let q = Vector3::new(p.x.abs(), p.y.abs(), p.z.abs()) - inner;
+                let outside = Vector3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).length();
+                let inside = Float::min(q.x.max(q.y).max(q.z), 0.0);
+                outside + inside - rounding
+            }
+            SdfNode::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let ring = Float::sqrt(p.x * p.x + p.z * p.z) - major_radius;
+                Float::sqrt(ring * ring + p.y * p.y) - minor_radius
+            }
+            SdfNode::Mandelbulb { power, iterations } => mandelbulb(p, *power, *iterations),
+            SdfNode::Translate { offset, node } => node.distance(p - *offset),
+            SdfNode::Union(a, b) => Float::min(a.distance(p), b.distance(p)),
+            SdfNode::Intersection(a, b) => Float::max(a.distance(p), b.distance(p)),
+            SdfNode::SmoothUnion { a, b, smoothing } => {
+                let (a, b) = (a.distance(p), b.distance(p));
+                let h = Float::max(smoothing - (a - b).abs(), 0.0) / smoothing;
+                Float::min(a, b) - h * h * smoothing / 4.0
+            }
+            SdfNode::Twist { rate, node } => {
+                let (sin, cos) = Float::sin_cos(rate * p.y);
+                node.distance(Point3::new(
+                    cos * p.x - sin * p.z,
+                    p.y,
+                    sin * p.x + cos * p.z,
+                ))
+            }
+            SdfNode::Repeat {
+                spacing,
+                copies,
+                node,
+            } => {
+                // Folds p into the nearest copy, clamped to the outermost ones
+                let mut q = p;
+                for axis in 0..3 {
+                    let copies = copies[axis] as Float;
+                    let nearest = Float::clamp((p[axis] / spacing[axis]).round(), -copies, copies);
+                    q[axis] -= spacing[axis] * nearest;
+                }
+                node.distance(q)
+            }
+        }
+    }
+
+    /// How much faster than the real distance `distance` can change. Twisting stretches space,
+    /// so the steps are divided by this to stay short of the surface
+    pub fn lipschitz(&self) -> Float {
+        match self {
+            SdfNode::Sphere { .. }
+            | SdfNode::RoundedBox { .. }
+            | SdfNode::Torus { .. }
+            | SdfNode::Mandelbulb { .. } => 1.0,
+            SdfNode::Translate { node, .. } | SdfNode::Repeat { node, .. } => node.lipschitz(),
+            SdfNode::Union(a, b)
+            | SdfNode::Intersection(a, b)
+            | SdfNode::SmoothUnion { a, b, .. } => Float::max(a.lipschitz(), b.lipschitz()),
+            SdfNode::Twist { rate, node } => {
+                let radius = node.radius_around_y();
+                node.lipschitz() * Float::sqrt(1.0 + rate * rate * radius * radius)
+            }
+        }
+    }
+
+    /// Contains the whole surface, but possibly more
+    pub fn bound(&self) -> Bounds3 {
+        match self {
+            SdfNode::Sphere { radius } => cube(*radius),
+            SdfNode::RoundedBox { half_extents, .. } => Bounds3 {
+                p_min: -*half_extents,
+                p_max: *half_extents,
+            },
+            SdfNode::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let outer = major_radius + minor_radius;
+                Bounds3 {
+                    p_min: Point3::new(-outer, -minor_radius, -outer),
+                    p_max: Point3::new(outer, *minor_radius, outer),
+                }
+            }
+            SdfNode::Mandelbulb { .. } => cube(MANDELBULB_BAILOUT),
+            SdfNode::Translate { offset, node } => {
+                let bound = node.bound();
+                Bounds3 {
+                    p_min: bound.p_min + *offset,
+                    p_max: bound.p_max + *offset,
+                }
+            }
+            SdfNode::Union(a, b) => Bounds3::union(&a.bound(), &b.bound()),
+            SdfNode::Intersection(a, b) => {
+                let (a, b) = (a.bound(), b.bound());
+                let mut bound = a;
+                for axis in 0..3 {
+                    bound.p_min[axis] = Float::max(a.p_min[axis], b.p_min[axis]);
+                    bound.p_max[axis] = Float::min(a.p_max[axis], b.p_max[axis]);
+                }
+                bound
+            }
+            SdfNode::SmoothUnion { a, b, smoothing } => {
+                // The blend pulls the surface out by at most a quarter of the smoothing
+                let grow = Vector3::new(1.0, 1.0, 1.0) * (smoothing / 4.0);
+                let bound = Bounds3::union(&a.bound(), &b.bound());
+                Bounds3 {
+                    p_min: bound.p_min - grow,
+                    p_max: bound.p_max + grow,
+                }
+            }
+            SdfNode::Twist { node, .. } => {
+                let (radius, bound) = (node.radius_around_y(), node.bound());
+                Bounds3 {
+                    p_min: Point3::new(-radius, bound.p_min.y, -radius),
+                    p_max: Point3::new(radius, bound.p_max.y, radius),
+                }
+            }
+            SdfNode::Repeat {
+                spacing,
+                copies,
+                node,
+            } => {
+                let mut bound = node.bound();
+                for axis in 0..3 {
+                    let reach = spacing[axis] * copies[axis] as Float;
+                    bound.p_min[axis] -= reach;
+                    bound.p_max[axis] += reach;
+                }
+                bound
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            SdfNode::Sphere { radius } => validate_positive("sdf sphere radius", *radius),
+            SdfNode::RoundedBox {
+                half_extents,
+                rounding,
+            } => {
+                for axis in 0..3 {
+                    validate_positive("sdf box half extent", half_extents[axis])?;
+                }
+                let smallest = half_extents.x.min(half_extents.y).min(half_extents.z);
+                if !(*rounding >= 0.0 && *rounding <= smallest) {
+                    return Err(Error::invalid_parameter(format!(
+                        "sdf box rounding must be at least 0 and at most the smallest half extent {}, got {}",
+                        smallest, rounding
+                    )));
+                }
+                Ok(())
+            }
+            SdfNode::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                validate_positive("sdf torus major radius", *major_radius)?;
+                validate_positive("sdf torus minor radius", *minor_radius)
+            }
+            SdfNode::Mandelbulb { power, iterations } => {
+                if !(*power >= 2.0 && power.is_finite() && (1..=64).contains(iterations)) {
+                    return Err(Error::invalid_parameter(format!(
+                        "sdf mandelbulb power must be at least 2 with 1 to 64 iterations, got {} and {}",
+                        power, iterations
+                    )));
+                }
+                Ok(())
+            }
+            SdfNode::Translate { offset, node } => {
+                if !offset.is_finite() {
+                    return Err(Error::invalid_parameter(format!(
+                        "sdf translation must be finite, got {:?}",
+                        offset
+                    )));
+                }
+                node.validate()
+            }
+            SdfNode::Union(a, b) | SdfNode::Intersection(a, b) => {
+                a.validate()?;
+                b.validate()
+            }
+            SdfNode::SmoothUnion { a, b, smoothing } => {
+                validate_positive("sdf smooth union smoothing", *smoothing)?;
+                a.validate()?;
+                b.validate()
+            }
+            SdfNode::Twist { rate, node } => {
+                if !rate.is_finite() {
+                    return Err(Error::invalid_parameter(format!(
+                        "sdf twist rate must be finite, got {}",
+                        rate
+                    )));
+                }
+                node.validate()
+            }
+            SdfNode::Repeat {
+                spacing,
+                copies: _,
+                node,
+            } => {
+                for axis in 0..3 {
+                    validate_positive("sdf repetition spacing", spacing[axis])?;
+                }
+                node.validate()
+            }
+        }
+    }
+
+    /// Furthest the bound reaches from the y axis
+    fn radius_around_y(&self) -> Float {
+        let bound = self.bound();
+        let x = Float::max(bound.p_min.x.abs(), bound.p_max.x.abs());
+        let z = Float::max(bound.p_min.z.abs(), bound.p_max.z.abs());
+        Float::sqrt(x * x + z * z)
+    }
+}
+
+/// A shape given by a signed distance function, found by sphere tracing: stepping along the ray
+/// by the distance to the surface, which can't overshoot it
+#[derive(Clone)]
+pub struct Sdf {
+    node: SdfNode,
+    material: MaterialId,
+    /// Both walk the whole tree, so they're worked out once instead of for every ray
+    bound: Bounds3,
+    lipschitz: Float,
+}
+
+impl Sdf {
+    pub fn new(node: SdfNode, material: MaterialId) -> Result<Sdf> {
+        node.validate()?;
+
+        Ok(Sdf {
+            bound: node.bound(),
+            lipschitz: node.lipschitz(),
+            node,
+            material,
+        })
+    }
+
+    pub fn hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        interaction: &mut Interaction,
+    ) -> bool {
+        // Only march where the ray is inside the bound
+        let bound = self.bound;
+        let (mut t_near, mut t_far) = (t_min, t_max);
+        for axis in 0..3 {
+            let inv_dir = 1.0 / ray.direction[axis];
+            let t0 = (bound.p_min[axis] - ray.origin[axis]) * inv_dir;
+            let t1 = (bound.p_max[axis] - ray.origin[axis]) * inv_dir;
+            t_near = Float::max(t_near, Float::min(t0, t1));
+            t_far = Float::min(t_far, Float::max(t0, t1));
+            // NaN from a zero direction with the origin on the boundary is a miss too
+            if t_near.is_nan() || t_far.is_nan() || t_near > t_far {
+                return false;
+            }
+        }
+
+        // Marches in units of the normalized direction, and from inside towards the outside
+        // when it starts in the shape, as rays through glass do
+        let speed = ray.direction.length();
+        let direction = ray.direction / speed;
+        let step_scale = 1.0 / self.lipschitz;
+        let mut s = t_near * speed;
+        let s_far = t_far * speed;
+        let side = Float::signum(self.node.distance(ray.origin + s * direction));
+
+        for _ in 0..MAX_STEPS {
+            let p = ray.origin + s * direction;
+            let distance = side * self.node.distance(p) * step_scale;
+            if distance < HIT_DISTANCE {
+                interaction.t = s / speed;
+                interaction.p = p;
+                interaction.normal = self.normal(p);
+                interaction.material = Some(self.material);
+                // Spherical around the centre of the bound, like `Sphere`
+                let Vector3 { x, y, z } = Vector3::unit_vector(p - bound.center());
+                interaction.uv = [
+                    (Float::atan2(-z, x) + PI) / (2.0 * PI),
+                    Float::acos(Float::clamp(-y, -1.0, 1.0)) / PI,
+                ];
+                return true;
+            }
+
+            s += distance;
+            if s > s_far {
+                return false;
+            }
+        }
+
+        false
+    }
+
+    pub fn validate(&self, materials: &MaterialList) -> Result<()> {
+        validate_material(self.material, materials)
+    }
+
+    pub fn bound(&self) -> Bounds3 {
+        self.bound
+    }
+
+    /// The gradient of the distance from the differences at the corners of a tetrahedron,
+    /// which takes 4 evaluations instead of the 6 of central differences
+    fn normal(&self, p: Point3) -> Vector3 {
+        let mut gradient = Vector3::default();
+        for corner in [
+            Vector3::new(1.0, -1.0, -1.0),
+            Vector3::new(-1.0, -1.0, 1.0),
+            Vector3::new(-1.0, 1.0, -1.0),
+            Vector3::new(1.0, 1.0, 1.0),
+        ] {
+            gradient += corner * self.node.distance(p + corner * NORMAL_OFFSET);
+        }
+
+        if gradient.near_zero() {
+            // Flat, as at the centre of a Mandelbulb, any direction will do
+            return Vector3::new(0.0, 1.0, 0.0);
+        }
+        Vector3::unit_vector(gradient)
+    }
+}
+
+fn cube(half_extent: Float) -> Bounds3 {
+    Bounds3 {
+        p_min: Point3::new(-half_extent, -half_extent, -half_extent),
+        p_max: Point3::new(half_extent, half_extent, half_extent),
+    }
+}
+
+/// The usual distance estimate of the Mandelbulb, from how fast the iteration escapes.
+/// 0 for points that don't escape, which are inside
+fn mandelbulb(p: Point3, power: Float, iterations: u32) -> Float {
+    let mut z = p;
+    let mut derivative = 1.0;
+    let mut r = z.length();
+
+    for _ in 0..iterations {
+        if r > MANDELBULB_BAILOUT || r == 0.0 {
+            break;
+        }
+
+        // z^power in spherical coordinates, then + p
+        let theta = Float::acos(Float::clamp(z.z / r, -1.0, 1.0)) * power;
+        let phi = Float::atan2(z.y, z.x) * power;
+        derivative = r.powf(power - 1.0) * power * derivative + 1.0;
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        z = r.powf(power) * Vector3::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta) + p;
+        r = z.length();
+    }
+
+    if r <= 1.0 {
+        return 0.0;
+    }
+    0.5 * r.ln() * r / derivative
+}
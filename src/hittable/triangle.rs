@@ -0,0 +1,198 @@
+use super::*;
+use std::sync::Arc;
+
+/// Vertices and the triangles between them, as loaded from a file. The optional attributes,
+/// when present, have one entry per vertex
+#[derive(Clone, Debug, Default)]
+pub struct TriangleMesh {
+    pub positions: Vec<Point3>,
+    pub normals: Option<Vec<Vector3>>,
+    pub uvs: Option<Vec<[Float; 2]>>,
+    pub colors: Option<Vec<Color3>>,
+    /// Indices of the three vertices of each triangle, counter-clockwise seen from the front
+    pub indices: Vec<[u32; 3]>,
+}
+
+impl TriangleMesh {
+    pub fn validate(&self) -> Result<()> {
+        if self.indices.is_empty() {
+            return Err(Error::invalid_parameter("mesh has no triangles"));
+        }
+        let vertices = self.positions.len();
+        if let Some(index) = self
+            .indices
+            .iter()
+            .flatten()
+            .find(|&&i| i as usize >= vertices)
+        {
+            return Err(Error::invalid_parameter(format!(
+                "mesh vertex index {} is out of range for {} vertices",
+                index, vertices
+            )));
+        }
+
+        let lengths = [
+            ("normals", self.normals.as_ref().map(Vec::len)),
+            ("uvs", self.uvs.as_ref().map(Vec::len)),
+            ("colors", self.colors.as_ref().map(Vec::len)),
+        ];
+        for (name, length) in lengths {
+            if let Some(length) = length.filter(|&length| length != vertices) {
+                return Err(Error::invalid_parameter(format!(
+                    "mesh has {} {} for {} vertices",
+                    length, name, vertices
+                )));
+            }
+        }
+
+        if self.positions.iter().any(|p| !p.is_finite()) {
+            return Err(Error::invalid_parameter(
+                "mesh vertex positions must be finite",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// One triangle of a mesh, which it shares with the others
+#[derive(Clone)]
+pub struct Triangle {
+    pub mesh: Arc<TriangleMesh>,
+    pub index: usize,
+    pub material: MaterialId,
+}
+
+impl Triangle {
+    pub fn hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        interaction: &mut Interaction,
+    ) -> bool {
+        let [i0, i1, i2] = self.mesh.indices[self.index].map(|i| i as usize);
+        let (p0, p1, p2) = (
+            self.mesh.positions[i0],
+            self.mesh.positions[i1],
+            self.mesh.positions[i2],
+        );
+
+        // Möller-Trumbore
+        let edge1 = p1 - p0;
+        let edge2 = p2 - p0;
+        let p_vec = Vector3::cross(&ray.direction, &edge2);
+        let determinant = Vector3::dot(&edge1, &p_vec);
+        if determinant == 0.0 {
+            return false;
+        }
+        let inv_determinant = 1.0 / determinant;
+
+        let t_vec = ray.origin - p0;
+        let b1 = Vector3::dot(&t_vec, &p_vec) * inv_determinant;
+        if !(0.0..=1.0).contains(&b1) {
+            return false;
+        }
+        let q_vec = Vector3::cross(&t_vec, &edge1);
+        let b2 = Vector3::dot(&ray.direction, &q_vec) * inv_determinant;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return false;
+        }
+        let t = Vector3::dot(&edge2, &q_vec) * inv_determinant;
+        if t < t_min || t_max < t {
+            return false;
+        }
+
+        let b0 = 1.0 - b1 - b2;
+        interaction.t = t;
+        interaction.p = ray.at(t);
+        interaction.normal = match &self.mesh.normals {
+            Some(normals) => {
+                let normal = b0 * normals[i0] + b1 * normals[i1] + b2 * normals[i2];
+                if normal.near_zero() {
+                    Vector3::unit_vector(Vector3::cross(&edge1, &edge2))
+                } else {
+                    Vector3::unit_vector(normal)
+                }
+            }
+            None => Vector3::unit_vector(Vector3::cross(&edge1, &edge2)),
+        };
+        interaction.material = Some(self.material);
+        interaction.uv = match &self.mesh.uvs {
+            Some(uvs) => [0, 1].map(|k| b0 * uvs[i0][k] + b1 * uvs[i1][k] + b2 * uvs[i2][k]),
+            None => [b1, b2],
+        };
+        interaction.color = self
+            .mesh
+            .colors
+            .as_ref()
+            .map(|colors| b0 * colors[i0] + b1 * colors[i1] + b2 * colors[i2]);
+
+        true
+    }
+
+    pub fn validate(&self, materials: &MaterialList) -> Result<()> {
+        if self.index >= self.mesh.indices.len() {
+            return Err(Error::invalid_parameter(format!(
+                "triangle {} isn't in a mesh of {} triangles",
+                self.index,
+                self.mesh.indices.len()
+            )));
+        }
+        validate_material(self.material, materials)
+    }
+
+    pub fn bound(&self) -> Bounds3 {
+        let mut bound = Bounds3::default();
+        for &i in &self.mesh.indices[self.index] {
+            bound = Bounds3::union_point(&bound, &self.mesh.positions[i as usize]);
+        }
+        bound.with_min_extent(1e-4)
+    }
+}
+
+/// A whole triangle mesh as one object, with its own BVH over the triangles
+#[derive(Clone)]
+pub struct Mesh {
+    triangles: Arc<HittableList>,
+    material: MaterialId,
+}
+
+impl Mesh {
+    pub fn new(mesh: TriangleMesh, material: MaterialId) -> Result<Mesh> {
+        mesh.validate()?;
+
+        let mesh = Arc::new(mesh);
+        let mut triangles = HittableList::default();
+        for index in 0..mesh.indices.len() {
+            triangles.add(Hittable::Triangle(Triangle {
+                mesh: mesh.clone(),
+                index,
+                material,
+            }));
+        }
+        triangles.init();
+
+        Ok(Mesh {
+            triangles: Arc::new(triangles),
+            material,
+        })
+    }
+
+    pub fn hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        interaction: &mut Interaction,
+    ) -> bool {
+        self.triangles.hit(ray, t_min, t_max, interaction)
+    }
+
+    pub fn validate(&self, materials: &MaterialList) -> Result<()> {
+        validate_material(self.material, materials)
+    }
+
+    pub fn bound(&self) -> Bounds3 {
+        self.triangles.bound()
+    }
+}
@@ -0,0 +1,67 @@
+#![warn(clippy::all)]
+#![warn(rust_2018_idioms)]
+
+pub type Float = f32;
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::io::{self, Write};
+use std::sync::mpsc;
+
+pub mod animation;
+pub mod bounds;
+pub mod camera;
+pub mod denoise;
+pub mod diagnostics;
+pub mod environment;
+pub mod error;
+pub mod film;
+pub mod gltf;
+pub mod hittable;
+pub mod light;
+pub mod material;
+pub mod pbrt;
+pub mod ply;
+pub mod ray;
+pub mod ray_color;
+pub mod render;
+pub mod scene;
+pub mod stats;
+pub mod texture;
+mod thread_pool;
+pub mod transforms;
+pub mod vector;
+
+pub use crate::gltf::read_gltf;
+pub use animation::{Animatable, Animation, CameraAnimation, Interpolation, Keyframe, Pose, Track};
+pub use camera::{
+    read_lens_file, Aperture, ApertureMask, Camera, CameraRay, EquirectangularCamera,
+    FisheyeCamera, FisheyeProjection, LensElement, OrthographicCamera, PerspectiveCamera,
+    RealisticCamera, StereoRig, ThinLens,
+};
+pub use diagnostics::*;
+pub use environment::Environment;
+pub use error::{Error, Result};
+pub use film::*;
+pub use hittable::*;
+pub use light::*;
+pub use material::{
+    Dielectric, Diffuse, Material, MaterialId, MaterialList, Metal, ReflectanceModel,
+};
+pub use pbrt::read_pbrt;
+pub use ply::{parse_ply, read_ply};
+pub use ray::Ray;
+pub use ray_color::{ray_color, PathVertex};
+pub use render::*;
+pub use scene::*;
+pub use stats::RenderStats;
+pub use texture::{ImageTexture, Texture, Wrap};
+pub use thread_pool::CancellationToken;
+use thread_pool::{Job, ThreadPool};
+pub use vector::{Color3, Point3, Vector3};
+
+const PI: Float = std::f64::consts::PI as Float;
+const FRAC_PI_4: Float = std::f64::consts::FRAC_PI_4 as Float;
+const FRAC_PI_2: Float = std::f64::consts::FRAC_PI_2 as Float;
+const FRAC_1_PI: Float = std::f64::consts::FRAC_1_PI as Float;
+const ONE_MINUS_EPSILON: Float = 1.0 - Float::EPSILON / 2.0;
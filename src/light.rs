@@ -0,0 +1,366 @@
+use super::bounds::Bounds3;
+use super::*;
+
+mod alias_table;
+mod bvh;
+mod sampler;
+#[cfg(test)]
+mod tests;
+
+pub(crate) use alias_table::AliasTable;
+use bvh::LightBounds;
+pub use sampler::{LightList, LightSampling};
+
+/// Lights are only reached by next event estimation. Triangle lights aren't part of the world, so a ray that
+/// hits where one is sees whatever geometry is there, if any, and specular surfaces don't reflect them
+pub enum Light {
+    Point(PointLight),
+    Spot(SpotLight),
+    Directional(DirectionalLight),
+    Triangle(TriangleLight),
+}
+
+impl Light {
+    /// Samples the incident radiance arriving at `p` from the light, divided by the pdf of the direction.
+    /// `wi` is set to the normalized direction from `p` towards the light
+    /// and `distance` to how far along `wi` the light is (infinite for directional lights).
+    /// Only area lights use `u`, which should be uniformly distributed in [0, 1)², the others are delta
+    /// lights whose pdf is always 1.
+    pub fn sample_li(
+        &self,
+        p: &Point3,
+        u: [Float; 2],
+        wi: &mut Vector3,
+        distance: &mut Float,
+    ) -> Color3 {
+        match self {
+            Light::Point(light) => light.sample_li(p, wi, distance),
+            Light::Spot(light) => light.sample_li(p, wi, distance),
+            Light::Directional(light) => light.sample_li(p, wi, distance),
+            Light::Triangle(light) => light.sample_li(p, u, wi, distance),
+        }
+    }
+
+    /// Total power emitted by the light.
+    /// Directional lights illuminate the whole scene so their power depends on `world_bound`
+    pub fn power(&self, world_bound: &Bounds3) -> Color3 {
+        match self {
+            Light::Point(light) => light.power(),
+            Light::Spot(light) => light.power(),
+            Light::Directional(light) => light.power(world_bound),
+            Light::Triangle(light) => light.power(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Light::Point(light) => light.validate(),
+            Light::Spot(light) => light.validate(),
+            Light::Directional(light) => light.validate(),
+            Light::Triangle(light) => light.validate(),
+        }
+    }
+
+    /// `None` if the light is infinitely far away
+    pub fn bounds(&self) -> Option<LightBounds> {
+        match self {
+            Light::Point(light) => Some(light.bounds()),
+            Light::Spot(light) => Some(light.bounds()),
+            Light::Directional(_) => None,
+            Light::Triangle(light) => Some(light.bounds()),
+        }
+    }
+}
+
+pub struct PointLight {
+    pub position: Point3,
+    pub intensity: Color3,
+}
+
+impl PointLight {
+    pub fn sample_li(&self, p: &Point3, wi: &mut Vector3, distance: &mut Float) -> Color3 {
+        let to_light = self.position - *p;
+        let distance_squared = to_light.length_squared();
+
+        *distance = Float::sqrt(distance_squared);
+        *wi = to_light / *distance;
+
+        self.intensity / distance_squared
+    }
+
+    pub fn power(&self) -> Color3 {
+        4.0 * PI * self.intensity
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        validate_position("point light", &self.position)?;
+        validate_color("point light intensity", &self.intensity)
+    }
+
+    pub fn bounds(&self) -> LightBounds {
+        LightBounds {
+            bounds: Bounds3 {
+                p_min: self.position,
+                p_max: self.position,
+            },
+            w: Vector3::new(0.0, 0.0, 1.0),
+            phi: self.power().luminance(),
+            cos_theta_o: -1.0,
+            cos_theta_e: 0.0,
+        }
+    }
+}
+
+pub struct SpotLight {
+    pub position: Point3,
+    pub direction: Vector3,
+    pub intensity: Color3,
+    /// In degrees, only kept to be validated
+    total_width: Float,
+    falloff_start: Float,
+    cos_total_width: Float,
+    cos_falloff_start: Float,
+}
+
+impl SpotLight {
+    /// `total_width` and `falloff_start` are in degrees and measured from the axis of the cone.
+    /// Inside `falloff_start` the light is at full intensity, and it smoothly falls off to zero at `total_width`.
+    pub fn new(
+        position: Point3,
+        look_at: Point3,
+        intensity: Color3,
+        total_width: Float,
+        falloff_start: Float,
+    ) -> SpotLight {
+        SpotLight {
+            position,
+            direction: Vector3::unit_vector(look_at - position),
+            intensity,
+            total_width,
+            falloff_start,
+            cos_total_width: Float::cos(total_width.to_radians()),
+            cos_falloff_start: Float::cos(falloff_start.to_radians()),
+        }
+    }
+
+    pub fn sample_li(&self, p: &Point3, wi: &mut Vector3, distance: &mut Float) -> Color3 {
+        let to_light = self.position - *p;
+        let distance_squared = to_light.length_squared();
+
+        *distance = Float::sqrt(distance_squared);
+        *wi = to_light / *distance;
+
+        self.intensity * self.falloff(&-*wi) / distance_squared
+    }
+
+    pub fn power(&self) -> Color3 {
+        self.intensity * 2.0 * PI * (1.0 - 0.5 * (self.cos_falloff_start + self.cos_total_width))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        validate_position("spot light", &self.position)?;
+        validate_direction("spot light", &self.direction)?;
+        validate_color("spot light intensity", &self.intensity)?;
+        if !(self.total_width > 0.0 && self.total_width <= 180.0) {
+            return Err(Error::invalid_parameter(format!(
+                "spot light cone angle must be between 0 and 180 degrees, got {}",
+                self.total_width
+            )));
+        }
+        if !(self.falloff_start >= 0.0 && self.falloff_start <= self.total_width) {
+            return Err(Error::invalid_parameter(format!(
+                "spot light falloff must start between 0 degrees and the cone angle of {}, got {}",
+                self.total_width, self.falloff_start
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub fn bounds(&self) -> LightBounds {
+        // theta_e covers the falloff region past the fully lit cone
+        let cos_theta_e =
+            Float::cos(Float::acos(self.cos_total_width) - Float::acos(self.cos_falloff_start));
+
+        LightBounds {
+            bounds: Bounds3 {
+                p_min: self.position,
+                p_max: self.position,
+            },
+            w: self.direction,
+            phi: 4.0 * PI * self.intensity.luminance(),
+            cos_theta_o: self.cos_falloff_start,
+            cos_theta_e,
+        }
+    }
+
+    fn falloff(&self, w: &Vector3) -> Float {
+        let cos_theta = Vector3::dot(w, &self.direction);
+
+        if cos_theta < self.cos_total_width {
+            return 0.0;
+        }
+        if cos_theta >= self.cos_falloff_start {
+            return 1.0;
+        }
+
+        let delta =
+            (cos_theta - self.cos_total_width) / (self.cos_falloff_start - self.cos_total_width);
+
+        (delta * delta) * (delta * delta)
+    }
+}
+
+pub struct DirectionalLight {
+    /// The direction the light is travelling in
+    pub direction: Vector3,
+    pub radiance: Color3,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vector3, radiance: Color3) -> DirectionalLight {
+        DirectionalLight {
+            direction: Vector3::unit_vector(direction),
+            radiance,
+        }
+    }
+
+    pub fn sample_li(&self, _: &Point3, wi: &mut Vector3, distance: &mut Float) -> Color3 {
+        *wi = -self.direction;
+        *distance = Float::INFINITY;
+
+        self.radiance
+    }
+
+    pub fn power(&self, world_bound: &Bounds3) -> Color3 {
+        let world_radius = world_bound.diagonal().length() / 2.0;
+        PI * world_radius * world_radius * self.radiance
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        validate_direction("directional light", &self.direction)?;
+        validate_color("directional light radiance", &self.radiance)
+    }
+}
+
+/// A triangle that emits `radiance` from its front, where its vertices go counter-clockwise
+pub struct TriangleLight {
+    pub vertices: [Point3; 3],
+    pub radiance: Color3,
+}
+
+impl TriangleLight {
+    /// One light for every triangle of `mesh`, so that the light BVH can pick out the ones that matter
+    pub fn from_mesh(mesh: &TriangleMesh, radiance: Color3) -> Vec<TriangleLight> {
+        mesh.indices
+            .iter()
+            .map(|triangle| TriangleLight {
+                vertices: triangle.map(|i| mesh.positions[i as usize]),
+                radiance,
+            })
+            .collect()
+    }
+
+    /// Picks a point uniformly over the area
+    pub fn sample_li(
+        &self,
+        p: &Point3,
+        u: [Float; 2],
+        wi: &mut Vector3,
+        distance: &mut Float,
+    ) -> Color3 {
+        let [v0, v1, v2] = self.vertices;
+        let sqrt_u0 = Float::sqrt(u[0]);
+        let (b0, b1) = (1.0 - sqrt_u0, u[1] * sqrt_u0);
+        let point = v0 * b0 + v1 * b1 + v2 * (1.0 - b0 - b1);
+
+        let to_light = point - *p;
+        let distance_squared = to_light.length_squared();
+        *distance = Float::sqrt(distance_squared);
+        *wi = to_light / *distance;
+
+        let (normal, area) = self.normal_and_area();
+        let cos_theta_light = -Vector3::dot(&normal, wi);
+        if distance_squared == 0.0 || cos_theta_light <= 0.0 {
+            return Color3::default();
+        }
+
+        // The pdf of the direction is the distance squared over the area as seen from p
+        self.radiance * cos_theta_light * area / distance_squared
+    }
+
+    pub fn power(&self) -> Color3 {
+        PI * self.normal_and_area().1 * self.radiance
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for vertex in &self.vertices {
+            validate_position("triangle light", vertex)?;
+        }
+        if self.normal_and_area().1 <= 0.0 {
+            return Err(Error::invalid_parameter(format!(
+                "triangle light must have an area, got {:?}",
+                self.vertices
+            )));
+        }
+        validate_color("triangle light radiance", &self.radiance)
+    }
+
+    pub fn bounds(&self) -> LightBounds {
+        let [v0, v1, v2] = self.vertices;
+        let bounds = Bounds3::union_point(
+            &Bounds3 {
+                p_min: v0,
+                p_max: v0,
+            },
+            &v1,
+        );
+
+        LightBounds {
+            bounds: Bounds3::union_point(&bounds, &v2),
+            w: self.normal_and_area().0,
+            phi: self.power().luminance(),
+            // Every point faces the same way and emits over the hemisphere around it
+            cos_theta_o: 1.0,
+            cos_theta_e: 0.0,
+        }
+    }
+
+    fn normal_and_area(&self) -> (Vector3, Float) {
+        let [v0, v1, v2] = self.vertices;
+        let cross = Vector3::cross(&(v1 - v0), &(v2 - v0));
+        let length = cross.length();
+        (cross / length, length / 2.0)
+    }
+}
+
+fn validate_position(light: &str, position: &Point3) -> Result<()> {
+    if !position.is_finite() {
+        return Err(Error::invalid_parameter(format!(
+            "{} position must be finite, got {:?}",
+            light, position
+        )));
+    }
+    Ok(())
+}
+
+/// Directions are normalized when the light is made, so a zero direction ends up as NaN
+fn validate_direction(light: &str, direction: &Vector3) -> Result<()> {
+    if !direction.is_finite() {
+        return Err(Error::invalid_parameter(format!(
+            "{} direction must be finite and non-zero, got {:?}",
+            light, direction
+        )));
+    }
+    Ok(())
+}
+
+fn validate_color(name: &str, color: &Color3) -> Result<()> {
+    if !(color.is_finite() && color.x >= 0.0 && color.y >= 0.0 && color.z >= 0.0) {
+        return Err(Error::invalid_parameter(format!(
+            "{} must be finite and non-negative, got {:?}",
+            name, color
+        )));
+    }
+    Ok(())
+}
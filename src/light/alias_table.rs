@@ -0,0 +1,96 @@
+use super::*;
+
+/// Samples from a discrete distribution in constant time using Vose's alias method.
+#[derive(Clone, Default)]
+pub struct AliasTable {
+    bins: Vec<Bin>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Bin {
+    q: Float,
+    p: Float,
+    alias: usize,
+}
+
+impl AliasTable {
+    pub fn new(weights: &[Float]) -> AliasTable {
+        // In double precision, as the rounding error of summing many lights in single precision
+        // is enough to leave whole bins over at the end
+        let sum = weights.iter().map(|&w| w as f64).sum::<f64>();
+        let n = weights.len();
+        let p = |w: Float| {
+            if sum > 0.0 {
+                w as f64 / sum
+            } else {
+                1.0 / n as f64
+            }
+        };
+
+        let mut bins: Vec<Bin> = weights
+            .iter()
+            .map(|&w| Bin {
+                p: p(w) as Float,
+                ..Default::default()
+            })
+            .collect();
+
+        // p_hat is the probability scaled so that the average bin has 1.0
+        let mut under = vec![];
+        let mut over = vec![];
+        for (i, &w) in weights.iter().enumerate() {
+            let p_hat = p(w) * n as f64;
+            if p_hat < 1.0 {
+                under.push((i, p_hat));
+            } else {
+                over.push((i, p_hat));
+            }
+        }
+
+        while !under.is_empty() && !over.is_empty() {
+            let (un, un_p_hat) = under.pop().unwrap();
+            let (ov, ov_p_hat) = over.pop().unwrap();
+
+            bins[un].q = un_p_hat as Float;
+            bins[un].alias = ov;
+
+            let p_excess = un_p_hat + ov_p_hat - 1.0;
+            if p_excess < 1.0 {
+                under.push((ov, p_excess));
+            } else {
+                over.push((ov, p_excess));
+            }
+        }
+
+        // Whatever is left over should be 1.0 apart from rounding errors,
+        // but a bin that can't be chosen always goes to one that can
+        let fallback = (0..n).find(|&i| bins[i].p > 0.0).unwrap_or(0);
+        for (i, _) in under.into_iter().chain(over) {
+            if bins[i].p > 0.0 {
+                bins[i].q = 1.0;
+                bins[i].alias = i;
+            } else {
+                bins[i].q = 0.0;
+                bins[i].alias = fallback;
+            }
+        }
+
+        AliasTable { bins }
+    }
+
+    /// `u` should be uniformly distributed in [0, 1)
+    pub fn sample(&self, u: Float, pmf: &mut Float) -> usize {
+        let n = self.bins.len();
+        let offset = usize::min((u * n as Float) as usize, n - 1);
+        let up = Float::min(u * n as Float - offset as Float, ONE_MINUS_EPSILON);
+
+        let index = if up < self.bins[offset].q {
+            offset
+        } else {
+            self.bins[offset].alias
+        };
+
+        *pmf = self.bins[index].p;
+        index
+    }
+}
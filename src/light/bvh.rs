@@ -0,0 +1,453 @@
+use super::*;
+
+/// A cone of directions around `w`, the cone is all directions within `acos(cos_theta)` of `w`.
+#[derive(Clone, Copy, Debug)]
+pub struct DirectionCone {
+    pub w: Vector3,
+    pub cos_theta: Float,
+}
+
+impl Default for DirectionCone {
+    fn default() -> DirectionCone {
+        DirectionCone::entire_sphere()
+    }
+}
+
+impl DirectionCone {
+    pub fn entire_sphere() -> DirectionCone {
+        DirectionCone {
+            w: Vector3::new(0.0, 0.0, 1.0),
+            cos_theta: -1.0,
+        }
+    }
+
+    pub fn union(a: &DirectionCone, b: &DirectionCone) -> DirectionCone {
+        let theta_a = safe_acos(a.cos_theta);
+        let theta_b = safe_acos(b.cos_theta);
+        let theta_d = angle_between(&a.w, &b.w);
+
+        // One cone already contains the other
+        if Float::min(theta_d + theta_b, PI) <= theta_a {
+            return *a;
+        }
+        if Float::min(theta_d + theta_a, PI) <= theta_b {
+            return *b;
+        }
+
+        let theta_o = (theta_a + theta_d + theta_b) / 2.0;
+        if theta_o >= PI {
+            return DirectionCone::entire_sphere();
+        }
+
+        // Rotate a.w towards b.w until it is in the middle of the merged cone
+        let theta_r = theta_o - theta_a;
+        let axis = Vector3::cross(&a.w, &b.w);
+        if axis.length_squared() == 0.0 {
+            return DirectionCone::entire_sphere();
+        }
+        let axis = Vector3::unit_vector(axis);
+        let w = a.w * Float::cos(theta_r)
+            + Vector3::cross(&axis, &a.w) * Float::sin(theta_r)
+            + axis * Vector3::dot(&axis, &a.w) * (1.0 - Float::cos(theta_r));
+
+        DirectionCone {
+            w,
+            cos_theta: Float::cos(theta_o),
+        }
+    }
+}
+
+/// Bounds on the position, emitted power and emission directions of a set of lights.
+/// `cos_theta_o` bounds the spread of the surface normals / emission axes around `w`
+/// and `cos_theta_e` bounds how far past that the light is emitted.
+#[derive(Clone, Copy, Debug)]
+pub struct LightBounds {
+    pub bounds: Bounds3,
+    pub w: Vector3,
+    pub phi: Float,
+    pub cos_theta_o: Float,
+    pub cos_theta_e: Float,
+}
+
+impl Default for LightBounds {
+    fn default() -> LightBounds {
+        LightBounds {
+            bounds: Bounds3::default(),
+            w: Vector3::new(0.0, 0.0, 1.0),
+            phi: 0.0,
+            cos_theta_o: 1.0,
+            cos_theta_e: 1.0,
+        }
+    }
+}
+
+impl LightBounds {
+    pub fn centroid(&self) -> Point3 {
+        self.bounds.center()
+    }
+
+    pub fn union(a: &LightBounds, b: &LightBounds) -> LightBounds {
+        if a.phi == 0.0 {
+            return *b;
+        }
+        if b.phi == 0.0 {
+            return *a;
+        }
+
+        let cone = DirectionCone::union(
+            &DirectionCone {
+                w: a.w,
+                cos_theta: a.cos_theta_o,
+            },
+            &DirectionCone {
+                w: b.w,
+                cos_theta: b.cos_theta_o,
+            },
+        );
+
+        LightBounds {
+            bounds: Bounds3::union(&a.bounds, &b.bounds),
+            w: cone.w,
+            phi: a.phi + b.phi,
+            cos_theta_o: cone.cos_theta,
+            cos_theta_e: Float::min(a.cos_theta_e, b.cos_theta_e),
+        }
+    }
+
+    /// Conservative estimate of how much the lights inside the bounds contribute at `p`.
+    /// A zero `normal` means the receiving point isn't on a surface.
+    pub fn importance(&self, p: &Point3, normal: &Vector3) -> Float {
+        let pc = self.bounds.center();
+        let d2 = Float::max(
+            (*p - pc).length_squared(),
+            self.bounds.diagonal().length() / 2.0,
+        );
+
+        // Angle between the cone axis and the vector from the centre of the bounds to the point.
+        // Any direction will do at the centre itself, the bounds subtend the whole sphere from there
+        let wi = if (*p - pc).length_squared() == 0.0 {
+            self.w
+        } else {
+            Vector3::unit_vector(*p - pc)
+        };
+        let cos_theta_w = Vector3::dot(&self.w, &wi);
+        let sin_theta_w = safe_sqrt(1.0 - cos_theta_w * cos_theta_w);
+
+        // Angle subtended by the bounds as seen from the point
+        let cos_theta_b = self.subtended_cos_theta(p);
+        let sin_theta_b = safe_sqrt(1.0 - cos_theta_b * cos_theta_b);
+
+        // cos(max(0, theta_w - theta_o - theta_b))
+        let sin_theta_o = safe_sqrt(1.0 - self.cos_theta_o * self.cos_theta_o);
+        let cos_theta_x = cos_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let sin_theta_x = sin_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let cos_theta_p = cos_sub_clamped(sin_theta_x, cos_theta_x, sin_theta_b, cos_theta_b);
+
+        if cos_theta_p <= self.cos_theta_e {
+            return 0.0;
+        }
+
+        let mut importance = self.phi * cos_theta_p / d2;
+
+        if !normal.near_zero() {
+            let cos_theta_i = Float::abs(Vector3::dot(&wi, normal));
+            let sin_theta_i = safe_sqrt(1.0 - cos_theta_i * cos_theta_i);
+            importance *= cos_sub_clamped(sin_theta_i, cos_theta_i, sin_theta_b, cos_theta_b);
+        }
+
+        Float::max(importance, 0.0)
+    }
+
+    fn subtended_cos_theta(&self, p: &Point3) -> Float {
+        let center = self.bounds.center();
+        let radius_squared = (self.bounds.p_max - center).length_squared();
+        let distance_squared = (*p - center).length_squared();
+
+        if distance_squared <= radius_squared {
+            return -1.0;
+        }
+
+        let sin2_theta_max = radius_squared / distance_squared;
+        safe_sqrt(1.0 - sin2_theta_max)
+    }
+}
+
+#[inline]
+fn safe_sqrt(x: Float) -> Float {
+    Float::sqrt(Float::max(x, 0.0))
+}
+
+#[inline]
+fn safe_acos(x: Float) -> Float {
+    Float::acos(Float::clamp(x, -1.0, 1.0))
+}
+
+#[inline]
+fn angle_between(a: &Vector3, b: &Vector3) -> Float {
+    safe_acos(Vector3::dot(a, b))
+}
+
+/// cos(max(0, a - b))
+#[inline]
+fn cos_sub_clamped(sin_a: Float, cos_a: Float, sin_b: Float, cos_b: Float) -> Float {
+    if cos_a > cos_b {
+        1.0
+    } else {
+        cos_a * cos_b + sin_a * sin_b
+    }
+}
+
+/// sin(max(0, a - b))
+#[inline]
+fn sin_sub_clamped(sin_a: Float, cos_a: Float, sin_b: Float, cos_b: Float) -> Float {
+    if cos_a > cos_b {
+        0.0
+    } else {
+        sin_a * cos_b - cos_a * sin_b
+    }
+}
+
+/// Bounding volume hierarchy over the lights with finite extent.
+/// Each leaf holds exactly one light, and traversal picks children proportionally to their importance.
+#[derive(Default)]
+pub struct LightBVH {
+    nodes: Vec<LinearLightBVHNode>,
+}
+
+impl LightBVH {
+    pub fn new(lights: &[(usize, LightBounds)]) -> LightBVH {
+        let mut bvh = LightBVH::default();
+
+        if lights.is_empty() {
+            return bvh;
+        }
+
+        let mut lights = lights.to_vec();
+        let len = lights.len();
+        bvh.build(&mut lights, 0, len);
+
+        bvh
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Picks a light index according to the importance of each node at `p`.
+    /// `u` should be uniformly distributed in [0, 1)
+    pub fn sample(&self, p: &Point3, normal: &Vector3, u: Float, pmf: &mut Float) -> Option<usize> {
+        let mut u = u;
+        let mut node_index = 0;
+        *pmf = 1.0;
+
+        loop {
+            let node = &self.nodes[node_index];
+            match node.offset {
+                LightOffset(light) => {
+                    if node_index > 0 || node.light_bounds.importance(p, normal) > 0.0 {
+                        return Some(light);
+                    }
+                    return None;
+                }
+                SecondChildOffset(second) => {
+                    let importance = [
+                        self.nodes[node_index + 1]
+                            .light_bounds
+                            .importance(p, normal),
+                        self.nodes[second].light_bounds.importance(p, normal),
+                    ];
+
+                    if importance[0] == 0.0 && importance[1] == 0.0 {
+                        return None;
+                    }
+
+                    let p0 = importance[0] / (importance[0] + importance[1]);
+                    if u < p0 {
+                        node_index += 1;
+                        u = Float::min(u / p0, ONE_MINUS_EPSILON);
+                        *pmf *= p0;
+                    } else {
+                        node_index = second;
+                        u = Float::min((u - p0) / (1.0 - p0), ONE_MINUS_EPSILON);
+                        *pmf *= 1.0 - p0;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Builds the nodes in depth first order, so the first child of an interior node is right after it.
+    fn build(&mut self, lights: &mut [(usize, LightBounds)], start: usize, end: usize) -> usize {
+        let node_index = self.nodes.len();
+
+        if end - start == 1 {
+            let (light, light_bounds) = lights[start];
+            self.nodes.push(LinearLightBVHNode {
+                light_bounds,
+                offset: LightOffset(light),
+            });
+            return node_index;
+        }
+
+        let mut bounds = Bounds3::default();
+        let mut centroid_bounds = Bounds3::default();
+        for (_, light_bounds) in &lights[start..end] {
+            bounds = Bounds3::union(&bounds, &light_bounds.bounds);
+            centroid_bounds = Bounds3::union_point(&centroid_bounds, &light_bounds.centroid());
+        }
+
+        let middle = match Self::split(lights, start, end, &bounds, &centroid_bounds) {
+            Some(middle) => middle,
+            // Couldn't find a good split, so just split in half
+            None => (start + end) / 2,
+        };
+
+        self.nodes.push(LinearLightBVHNode::default());
+        self.build(lights, start, middle);
+        let second = self.build(lights, middle, end);
+
+        let light_bounds = LightBounds::union(
+            &self.nodes[node_index + 1].light_bounds,
+            &self.nodes[second].light_bounds,
+        );
+        self.nodes[node_index] = LinearLightBVHNode {
+            light_bounds,
+            offset: SecondChildOffset(second),
+        };
+
+        node_index
+    }
+
+    /// Splits using the surface area orientation heuristic with buckets.
+    /// Returns the index where the second half starts.
+    fn split(
+        lights: &mut [(usize, LightBounds)],
+        start: usize,
+        end: usize,
+        bounds: &Bounds3,
+        centroid_bounds: &Bounds3,
+    ) -> Option<usize> {
+        const NUM_BUCKETS: usize = 12;
+
+        let mut min_cost = Float::INFINITY;
+        let mut min_cost_split_bucket = 0;
+        let mut min_cost_split_dimension = None;
+
+        for dimension in 0..3 {
+            if centroid_bounds.p_max[dimension] == centroid_bounds.p_min[dimension] {
+                continue;
+            }
+
+            let bucket = |light_bounds: &LightBounds| {
+                usize::min(
+                    (NUM_BUCKETS as Float
+                        * centroid_bounds.offset(&light_bounds.centroid())[dimension])
+                        as usize,
+                    NUM_BUCKETS - 1,
+                )
+            };
+
+            let mut buckets = [LightBounds::default(); NUM_BUCKETS];
+            for (_, light_bounds) in &lights[start..end] {
+                let b = bucket(light_bounds);
+                buckets[b] = LightBounds::union(&buckets[b], light_bounds);
+            }
+
+            for i in 0..NUM_BUCKETS - 1 {
+                let mut b0 = LightBounds::default();
+                let mut b1 = LightBounds::default();
+                for bucket in &buckets[..=i] {
+                    b0 = LightBounds::union(&b0, bucket);
+                }
+                for bucket in &buckets[i + 1..] {
+                    b1 = LightBounds::union(&b1, bucket);
+                }
+
+                let cost =
+                    evaluate_cost(&b0, bounds, dimension) + evaluate_cost(&b1, bounds, dimension);
+                if cost > 0.0 && cost < min_cost {
+                    min_cost = cost;
+                    min_cost_split_bucket = i;
+                    min_cost_split_dimension = Some(dimension);
+                }
+            }
+        }
+
+        let dimension = min_cost_split_dimension?;
+
+        let (left, right): (Vec<_>, Vec<_>) =
+            lights[start..end].iter().partition(|(_, light_bounds)| {
+                usize::min(
+                    (NUM_BUCKETS as Float
+                        * centroid_bounds.offset(&light_bounds.centroid())[dimension])
+                        as usize,
+                    NUM_BUCKETS - 1,
+                ) <= min_cost_split_bucket
+            });
+
+        if left.is_empty() || right.is_empty() {
+            return None;
+        }
+
+        let middle = start + left.len();
+        lights[start..middle].copy_from_slice(&left);
+        lights[middle..end].copy_from_slice(&right);
+
+        Some(middle)
+    }
+}
+
+fn evaluate_cost(b: &LightBounds, bounds: &Bounds3, dimension: usize) -> Float {
+    if b.phi == 0.0 {
+        return 0.0;
+    }
+
+    // Solid angle measure of the bounding cone, taking the emission spread into account
+    let theta_o = safe_acos(b.cos_theta_o);
+    let theta_e = safe_acos(b.cos_theta_e);
+    let theta_w = Float::min(theta_o + theta_e, PI);
+    let sin_theta_o = safe_sqrt(1.0 - b.cos_theta_o * b.cos_theta_o);
+    let m_omega = 2.0 * PI * (1.0 - b.cos_theta_o)
+        + PI / 2.0
+            * (2.0 * theta_w * sin_theta_o
+                - Float::cos(theta_o - 2.0 * theta_w)
+                - 2.0 * theta_o * sin_theta_o
+                + b.cos_theta_o);
+
+    // Penalize thin bounds
+    let diagonal = bounds.diagonal();
+    let max_extent = Float::max(diagonal.x, Float::max(diagonal.y, diagonal.z));
+    let k_r = if diagonal[dimension] > 0.0 {
+        max_extent / diagonal[dimension]
+    } else {
+        1.0
+    };
+
+    let surface_area = if b.bounds.surface_area() > 0.0 {
+        b.bounds.surface_area()
+    } else {
+        1.0
+    };
+
+    b.phi * m_omega * k_r * surface_area
+}
+
+#[derive(Clone, Copy, Debug)]
+enum LightBVHOffset {
+    LightOffset(usize),
+    SecondChildOffset(usize),
+}
+
+use LightBVHOffset::*;
+
+impl Default for LightBVHOffset {
+    fn default() -> LightBVHOffset {
+        LightOffset(0)
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+struct LinearLightBVHNode {
+    light_bounds: LightBounds,
+    offset: LightBVHOffset,
+}
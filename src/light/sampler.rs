@@ -0,0 +1,150 @@
+use super::alias_table::AliasTable;
+use super::bvh::LightBVH;
+use super::*;
+
+/// How `LightList` chooses which light to sample for next event estimation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LightSampling {
+    /// Every light is equally likely
+    Uniform,
+    /// Lights are chosen proportionally to their emitted power
+    Power,
+    /// Lights are chosen by their estimated contribution at the shading point
+    #[default]
+    Bvh,
+}
+
+#[derive(Default)]
+pub struct LightList {
+    lights: Vec<Light>,
+    sampler: Sampler,
+}
+
+#[derive(Default)]
+enum Sampler {
+    #[default]
+    Uniform,
+    Power(AliasTable),
+    Bvh {
+        bvh: LightBVH,
+        /// Lights without bounds like directional lights can't go in the BVH
+        infinite_lights: Vec<usize>,
+    },
+}
+
+impl LightList {
+    pub fn add(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Light> {
+        self.lights.iter()
+    }
+
+    /// Should be called after all the lights are added.
+    /// `world_bound` is needed to work out the power of directional lights.
+    pub fn init(&mut self, sampling: LightSampling, world_bound: &Bounds3) {
+        self.sampler = match sampling {
+            LightSampling::Uniform => Sampler::Uniform,
+            LightSampling::Power => {
+                let weights: Vec<Float> = self
+                    .lights
+                    .iter()
+                    .map(|light| light.power(world_bound).luminance())
+                    .collect();
+
+                if weights.iter().all(|&w| w == 0.0) {
+                    Sampler::Uniform
+                } else {
+                    Sampler::Power(AliasTable::new(&weights))
+                }
+            }
+            LightSampling::Bvh => {
+                let mut bounded_lights = vec![];
+                let mut infinite_lights = vec![];
+
+                for (i, light) in self.lights.iter().enumerate() {
+                    match light.bounds() {
+                        Some(light_bounds) => {
+                            if light_bounds.phi > 0.0 {
+                                bounded_lights.push((i, light_bounds));
+                            }
+                        }
+                        None => infinite_lights.push(i),
+                    }
+                }
+
+                Sampler::Bvh {
+                    bvh: LightBVH::new(&bounded_lights),
+                    infinite_lights,
+                }
+            }
+        };
+    }
+
+    /// Chooses a light to sample at the point `p` with surface normal `normal`.
+    /// `pmf` is set to the probability of choosing the returned light, and is never 0 when one is returned.
+    /// `u` should be uniformly distributed in [0, 1)
+    pub fn sample(
+        &self,
+        p: &Point3,
+        normal: &Vector3,
+        u: Float,
+        pmf: &mut Float,
+    ) -> Option<&Light> {
+        self.choose(p, normal, u, pmf).filter(|_| *pmf > 0.0)
+    }
+
+    fn choose(&self, p: &Point3, normal: &Vector3, u: Float, pmf: &mut Float) -> Option<&Light> {
+        if self.lights.is_empty() {
+            return None;
+        }
+
+        match &self.sampler {
+            Sampler::Uniform => {
+                let index = usize::min(
+                    (u * self.lights.len() as Float) as usize,
+                    self.lights.len() - 1,
+                );
+                *pmf = 1.0 / self.lights.len() as Float;
+                Some(&self.lights[index])
+            }
+            Sampler::Power(alias_table) => Some(&self.lights[alias_table.sample(u, pmf)]),
+            Sampler::Bvh {
+                bvh,
+                infinite_lights,
+            } => {
+                if bvh.is_empty() && infinite_lights.is_empty() {
+                    return None;
+                }
+
+                // Infinite lights get the same chance as the whole BVH
+                let bvh_weight = if bvh.is_empty() { 0.0 } else { 1.0 };
+                let p_infinite =
+                    infinite_lights.len() as Float / (infinite_lights.len() as Float + bvh_weight);
+
+                if u < p_infinite {
+                    let index = usize::min(
+                        (u / p_infinite * infinite_lights.len() as Float) as usize,
+                        infinite_lights.len() - 1,
+                    );
+                    *pmf = p_infinite / infinite_lights.len() as Float;
+                    Some(&self.lights[infinite_lights[index]])
+                } else {
+                    let u = Float::min((u - p_infinite) / (1.0 - p_infinite), ONE_MINUS_EPSILON);
+                    let index = bvh.sample(p, normal, u, pmf)?;
+                    *pmf *= 1.0 - p_infinite;
+                    Some(&self.lights[index])
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! Checks that every way of choosing a light samples each one as often as the pmf it reports
+
+use super::alias_table::AliasTable;
+use super::*;
+use std::collections::HashMap;
+
+/// Stratified, so frequencies only differ from the pmf by the discretization
+const SAMPLES: usize = 200_000;
+
+fn world_bound() -> Bounds3 {
+    Bounds3 {
+        p_min: Point3::new(-10.0, -10.0, -10.0),
+        p_max: Point3::new(10.0, 10.0, 10.0),
+    }
+}
+
+/// A square from -1 to 1 in x and z at height `y`, made of `n` by `n` cells of two triangles each that face down
+fn square(y: Float, n: usize, radiance: Color3) -> Vec<TriangleLight> {
+    let vertex = |i: usize, j: usize| {
+        Point3::new(
+            2.0 * i as Float / n as Float - 1.0,
+            y,
+            2.0 * j as Float / n as Float - 1.0,
+        )
+    };
+    let mut triangles = vec![];
+    for i in 0..n {
+        for j in 0..n {
+            let [a, b, c, d] = [
+                vertex(i, j),
+                vertex(i + 1, j),
+                vertex(i + 1, j + 1),
+                vertex(i, j + 1),
+            ];
+            for vertices in [[a, b, c], [a, c, d]] {
+                triangles.push(TriangleLight { vertices, radiance });
+            }
+        }
+    }
+    triangles
+}
+
+/// Point, spot and triangle lights spread around the origin, plus a directional light that can't go
+/// in the BVH
+fn lights() -> LightList {
+    let mut lights = LightList::default();
+    for light in square(5.0, 4, Color3::new(0.5, 0.5, 0.5)) {
+        lights.add(Light::Triangle(light));
+    }
+    for i in 0..6 {
+        let x = i as Float * 1.5 - 4.0;
+        lights.add(Light::Point(PointLight {
+            position: Point3::new(x, 3.0, 0.5 * x),
+            intensity: Color3::new(1.0 + i as Float, 1.0, 1.0),
+        }));
+        lights.add(Light::Spot(SpotLight::new(
+            Point3::new(-x, 4.0, 1.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Color3::new(5.0, 5.0, 5.0),
+            30.0 + 5.0 * i as Float,
+            20.0,
+        )));
+    }
+    lights.add(Light::Directional(DirectionalLight::new(
+        Vector3::new(0.0, -1.0, 0.2),
+        Color3::new(0.5, 0.5, 0.5),
+    )));
+    lights
+}
+
+/// How often each light was chosen and the pmf it was chosen with, by index,
+/// and how often nothing was chosen
+fn sample_all(
+    lights: &LightList,
+    p: &Point3,
+    normal: &Vector3,
+) -> (HashMap<usize, (usize, Float)>, usize) {
+    let mut chosen = HashMap::new();
+    let mut nothing = 0;
+    for i in 0..SAMPLES {
+        let u = (i as Float + 0.5) / SAMPLES as Float;
+        let mut pmf = 0.0;
+        let light = match lights.sample(p, normal, u, &mut pmf) {
+            Some(light) => light,
+            None => {
+                nothing += 1;
+                continue;
+            }
+        };
+        let index = lights
+            .iter()
+            .position(|other| std::ptr::eq(light, other))
+            .unwrap();
+
+        let entry = chosen.entry(index).or_insert((0, pmf));
+        assert!(
+            (entry.1 - pmf).abs() < 1e-5,
+            "light {} was chosen with pmf {} and {}",
+            index,
+            entry.1,
+            pmf
+        );
+        entry.0 += 1;
+    }
+    (chosen, nothing)
+}
+
+#[test]
+fn alias_table_frequencies_match_weights() {
+    let weights = [1.0, 0.0, 3.0, 6.0, 0.5, 0.0, 2.5];
+    let sum: Float = weights.iter().sum();
+    let table = AliasTable::new(&weights);
+
+    let mut counts = [0; 7];
+    for i in 0..SAMPLES {
+        let mut pmf = 0.0;
+        let index = table.sample((i as Float + 0.5) / SAMPLES as Float, &mut pmf);
+        assert_eq!(pmf, weights[index] / sum);
+        counts[index] += 1;
+    }
+
+    for (count, weight) in counts.iter().zip(&weights) {
+        let frequency = *count as Float / SAMPLES as Float;
+        assert!(
+            (frequency - weight / sum).abs() < 1e-3,
+            "weight {} was chosen {} of the time",
+            weight,
+            frequency
+        );
+    }
+}
+
+#[test]
+fn every_strategy_has_a_normalized_pmf() {
+    let points = [
+        (Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0)),
+        (Point3::new(-3.0, 1.0, 2.0), Vector3::new(1.0, 0.0, 0.0)),
+        // Not on a surface
+        (Point3::new(2.0, 3.0, -1.0), Vector3::new(0.0, 0.0, 0.0)),
+    ];
+
+    for sampling in [
+        LightSampling::Uniform,
+        LightSampling::Power,
+        LightSampling::Bvh,
+    ] {
+        let mut lights = lights();
+        lights.init(sampling, &world_bound());
+
+        for (p, normal) in &points {
+            // The light BVH gives up in subtrees whose lights can't reach `p`,
+            // which is only unbiased if every light that does reach it can be chosen
+            let (chosen, nothing) = sample_all(&lights, p, normal);
+            let total: Float = chosen.values().map(|&(_, pmf)| pmf).sum();
+            let nothing = nothing as Float / SAMPLES as Float;
+            assert!(
+                (total + nothing - 1.0).abs() < 1e-3,
+                "{:?} pmfs sum to {} with nothing chosen {} of the time at {:?}",
+                sampling,
+                total,
+                nothing,
+                p
+            );
+            for (index, light) in lights.iter().enumerate() {
+                let mut wi = Vector3::default();
+                let mut distance = 0.0;
+                let radiance = light.sample_li(p, [0.5, 0.5], &mut wi, &mut distance);
+                let reaches = radiance.luminance() > 0.0
+                    && (normal.near_zero() || Vector3::dot(&wi, normal) != 0.0);
+                assert!(
+                    !reaches || chosen.contains_key(&index),
+                    "{:?} never chose light {} at {:?}",
+                    sampling,
+                    index,
+                    p
+                );
+            }
+
+            for (index, (count, pmf)) in chosen {
+                let frequency = count as Float / SAMPLES as Float;
+                assert!(
+                    (frequency - pmf).abs() < 1e-3,
+                    "{:?} chose light {} {} of the time with pmf {}",
+                    sampling,
+                    index,
+                    frequency,
+                    pmf
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn importance_at_the_centre_of_the_bounds_is_finite() {
+    let light = |x| PointLight {
+        position: Point3::new(x, 0.0, 0.0),
+        intensity: Color3::new(1.0, 1.0, 1.0),
+    };
+    let bounds = LightBounds::union(&light(-1.0).bounds(), &light(1.0).bounds());
+
+    let center = Point3::new(0.0, 0.0, 0.0);
+    for normal in [Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 0.0)] {
+        let importance = bounds.importance(&center, &normal);
+        assert!(importance.is_finite() && importance > 0.0, "{}", importance);
+    }
+}
+
+#[test]
+fn triangle_lights_give_the_irradiance_of_the_square_they_make_up() {
+    // Under the middle of a square with sides twice its height the irradiance is
+    // 4 L x atan(x) with x = 1 / sqrt(2)
+    let x = Float::sqrt(0.5);
+    let expected = 4.0 * x * Float::atan(x);
+
+    let p = Point3::new(0.0, 0.0, 0.0);
+    let normal = Vector3::new(0.0, 1.0, 0.0);
+    for sampling in [
+        LightSampling::Uniform,
+        LightSampling::Power,
+        LightSampling::Bvh,
+    ] {
+        let mut lights = LightList::default();
+        for light in square(1.0, 16, Color3::new(1.0, 1.0, 1.0)) {
+            light.validate().unwrap();
+            lights.add(Light::Triangle(light));
+        }
+        lights.init(sampling, &world_bound());
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut irradiance = 0.0;
+        for _ in 0..SAMPLES {
+            let mut pmf = 0.0;
+            let light = match lights.sample(&p, &normal, rng.gen(), &mut pmf) {
+                Some(light) => light,
+                None => continue,
+            };
+            let mut wi = Vector3::default();
+            let mut distance = 0.0;
+            let li = light.sample_li(&p, rng.gen(), &mut wi, &mut distance);
+            irradiance += li.x * Float::max(Vector3::dot(&wi, &normal), 0.0) / pmf;
+        }
+        irradiance /= SAMPLES as Float;
+
+        assert!(
+            (irradiance - expected).abs() < 0.01 * expected,
+            "{:?} gives an irradiance of {} instead of {}",
+            sampling,
+            irradiance,
+            expected
+        );
+    }
+
+    // Seen from above, the square faces away
+    let mut wi = Vector3::default();
+    let mut distance = 0.0;
+    let above = Point3::new(0.0, 2.0, 0.0);
+    for light in square(1.0, 1, Color3::new(1.0, 1.0, 1.0)) {
+        assert!(light
+            .sample_li(&above, [0.5, 0.5], &mut wi, &mut distance)
+            .near_zero());
+    }
+
+    let degenerate = TriangleLight {
+        vertices: [p, Point3::new(1.0, 0.0, 0.0), Point3::new(2.0, 0.0, 0.0)],
+        radiance: Color3::new(1.0, 1.0, 1.0),
+    };
+    assert!(degenerate.validate().is_err());
+}
+
+/// Summed in single precision, this many weights come out large enough to leave hundreds of bins over
+/// when the table is built
+#[test]
+fn alias_table_never_picks_a_zero_weight() {
+    let weights: Vec<Float> = (0..300_000)
+        .map(|i| if i % 3 == 0 { 0.0 } else { 0.3 })
+        .collect();
+    let table = AliasTable::new(&weights);
+
+    let samples = 4 * weights.len();
+    for i in 0..samples {
+        let mut pmf = 0.0;
+        let index = table.sample((i as Float + 0.5) / samples as Float, &mut pmf);
+        assert!(
+            weights[index] > 0.0 && pmf > 0.0,
+            "chose {} of weight {} with pmf {}",
+            index,
+            weights[index],
+            pmf
+        );
+    }
+}
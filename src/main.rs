@@ -1,202 +1,571 @@
 #![warn(clippy::all)]
 #![warn(rust_2018_idioms)]
 
-#[macro_use]
-extern crate lazy_static;
-
-type Float = f32;
-
-use rand::rngs::SmallRng;
-use rand::{Rng, SeedableRng};
+use rustrt::*;
+use std::convert::TryInto;
 use std::io::{self, Write};
-use std::sync::mpsc;
-
-mod bounds;
-mod camera;
-mod hittable;
-mod material;
-mod ray;
-mod ray_color;
-mod scene;
-mod thread_pool;
-mod transforms;
-mod vector;
-
-use camera::Camera;
-use hittable::*;
-use ray::Ray;
-use ray_color::ray_color;
-use scene::*;
+use std::path::PathBuf;
 use std::time::Instant;
-use thread_pool::ThreadPool;
-use vector::{Color3, Point3, Vector3};
 
 const ASPECT_RATIO: Float = 16.0 / 9.0;
 const WIDTH: usize = 1920;
 const HEIGHT: usize = (WIDTH as Float / ASPECT_RATIO) as usize;
 const SAMPLES_PER_PIXEL: usize = 1024;
 const NUM_CPU: usize = 2;
-const TILE_WIDTH: usize = 16;
-const TILE_HEIGHT: usize = 16;
 const MAX_DEPTH: usize = 16;
-
-const FRAC_PI_4: Float = std::f64::consts::FRAC_PI_4 as Float;
-const FRAC_PI_2: Float = std::f64::consts::FRAC_PI_2 as Float;
-const FRAC_1_PI: Float = std::f64::consts::FRAC_1_PI as Float;
+/// Also write the linear image with all the AOVs as an EXR next to the PNG
+const WRITE_AOVS: bool = true;
+/// Distance between the eyes with `--stereo` unless `--interocular-distance` is given, in scene units
+const INTEROCULAR_DISTANCE: Float = 0.25;
+/// Distance of the screen with `--stereo` unless `--convergence-distance` is given, `None` for parallel eyes
+const CONVERGENCE_DISTANCE: Option<Float> = Some(10.0);
+
+const USAGE: &str = "rustrt [--crop x0,y0,x1,y1 [--full-size]] [--pixel x,y]
+    [--stereo side-by-side|separate [--interocular-distance d] [--convergence-distance d|infinity]]
+    [--frames a..b] [--scene cornell-box|glass-sphere|metal-spheres|file.gltf|file.glb|file.pbrt]
+    [--tile-order row-major|spiral|hilbert] [--light-sampling uniform|power|bvh]
+    [--denoise] [--clamp max] [--debug-invalid] [--seed n] [--stats] [--stats-json]
+    [--blades n [--blade-rotation degrees] | --aperture-mask file.png] [--cats-eye amount]
+    [--chromatic-aberration amount] [--tilt degrees] [--swing degrees] [--shift x,y]
+or: rustrt denoise <input.exr> <output.(png|exr)>";
 
 fn main() {
-    let earlier = Instant::now();
+    let args: Vec<String> = std::env::args().collect();
+    let result = if args.get(1).map(String::as_str) == Some("denoise") {
+        denoise_command(&args[2..])
+    } else {
+        parse_render_options(&args[1..]).and_then(render_command)
+    };
 
-    let (tx, rx) = mpsc::channel();
+    if let Err(error) = result {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
+}
 
-    let mut thread_pool = ThreadPool::new(NUM_CPU, || {
-        let tx = tx.clone();
-        Box::new(move |mut tile: Tile| {
-            let mut small_rng = SmallRng::from_entropy();
+/// Options of the render command. Pixel coordinates are as in the output image, so (0, 0) is the top left
+#[derive(Default)]
+struct RenderOptions {
+    /// `--crop x0,y0,x1,y1` with `x1` and `y1` exclusive
+    crop: Option<[usize; 4]>,
+    /// `--full-size` writes the whole film with only the crop filled in instead of just the crop
+    full_size: bool,
+    /// `--pixel x,y` prints every bounce of every sample of that pixel instead of rendering
+    pixel: Option<[usize; 2]>,
+    /// `--stereo side-by-side|separate` renders the left and right eye
+    stereo: Option<StereoOutput>,
+    /// `--interocular-distance d` is how far apart the eyes are, in scene units
+    interocular_distance: Option<Float>,
+    /// `--convergence-distance d` is how far away the screen is, `infinity` keeps the eyes parallel
+    convergence_distance: Option<Float>,
+    /// `--frames a..b` renders the animation from frame `a` to `b`, both included, to `image_0001.png` and so on
+    frames: Option<[usize; 2]>,
+    /// `--scene file.gltf` renders a glTF, binary glTF or pbrt-v3 file instead of the random spheres.
+    /// A pbrt file's resolution, samples per pixel and depth replace the ones here.
+    /// `cornell-box`, `glass-sphere` and `metal-spheres` render those built in scenes, which are lit
+    /// by point and directional lights
+    scene: Option<PathBuf>,
+    /// `--tile-order row-major|spiral|hilbert` chooses the order tiles are rendered in
+    tile_order: TileOrder,
+    /// `--light-sampling uniform|power|bvh` chooses how lights are picked for next event estimation
+    light_sampling: LightSampling,
+    /// `--denoise` denoises the PNG, the EXR is always left noisy so that it can be denoised again with `rustrt denoise`
+    denoise: bool,
+    /// `--clamp max` clamps the radiance of every sample to `max`, trading bias for fewer fireflies
+    max_sample_value: Option<Float>,
+    /// `--debug-invalid` traces the NaN and infinite samples again and writes out their paths
+    /// to `renders/invalid_samples.txt`
+    debug_invalid_samples: bool,
+    /// `--stats` prints ray counts, BVH traversal and timing statistics after rendering
+    print_stats: bool,
+    /// `--stats-json` writes the statistics to `renders/stats.json`
+    write_stats_json: bool,
+    /// `--seed n` changes the random numbers every sample is derived from, renders with the same seed are identical
+    seed: u64,
+    /// `--blades n` gives the camera's aperture `n` straight blades instead of a circle
+    blades: Option<usize>,
+    /// `--blade-rotation degrees` turns those blades
+    blade_rotation: Float,
+    /// `--aperture-mask file.png` shapes the aperture like the brightness of the image
+    aperture_mask: Option<PathBuf>,
+    /// `--cats-eye`, `--chromatic-aberration`, `--tilt`, `--swing` and `--shift x,y` set the same fields
+    /// of the camera's `ThinLens`
+    lens: ThinLens,
+    /// Whether any of the flags above was given, as only perspective cameras have a thin lens
+    thin_lens: bool,
+}
 
-            let width = usize::min(TILE_WIDTH, WIDTH - tile.x);
-            let height = usize::min(TILE_HEIGHT, HEIGHT - tile.y);
+#[derive(Clone, Copy)]
+enum StereoOutput {
+    /// One image twice as wide with the left eye on the left
+    SideBySide,
+    /// `image_left.png` and `image_right.png`
+    Separate,
+}
 
-            let scale = 1.0 / SAMPLES_PER_PIXEL as Float;
+fn parse_render_options(args: &[String]) -> Result<RenderOptions> {
+    let mut options = RenderOptions::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| Error::invalid_parameter(format!("{} needs a value", arg)))
+        };
+
+        match arg.as_str() {
+            "--crop" => options.crop = Some(parse_list(arg, value()?)?),
+            "--full-size" => options.full_size = true,
+            "--denoise" => options.denoise = true,
+            "--clamp" => options.max_sample_value = Some(parse_number(arg, value()?)?),
+            "--debug-invalid" => options.debug_invalid_samples = true,
+            "--stats" => options.print_stats = true,
+            "--stats-json" => options.write_stats_json = true,
+            "--seed" => options.seed = parse_number(arg, value()?)?,
+            "--pixel" => options.pixel = Some(parse_list(arg, value()?)?),
+            "--frames" => options.frames = Some(parse_frames(arg, value()?)?),
+            "--scene" => options.scene = Some(PathBuf::from(value()?)),
+            "--blades" => {
+                options.blades = Some(parse_number(arg, value()?)?);
+                options.thin_lens = true;
+            }
+            "--blade-rotation" => {
+                options.blade_rotation = parse_number(arg, value()?)?;
+                options.thin_lens = true;
+            }
+            "--aperture-mask" => {
+                options.aperture_mask = Some(PathBuf::from(value()?));
+                options.thin_lens = true;
+            }
+            "--cats-eye" => {
+                options.lens.cats_eye = parse_number(arg, value()?)?;
+                options.thin_lens = true;
+            }
+            "--chromatic-aberration" => {
+                options.lens.chromatic_aberration = parse_number(arg, value()?)?;
+                options.thin_lens = true;
+            }
+            "--tilt" => {
+                options.lens.tilt = parse_number(arg, value()?)?;
+                options.thin_lens = true;
+            }
+            "--swing" => {
+                options.lens.swing = parse_number(arg, value()?)?;
+                options.thin_lens = true;
+            }
+            "--shift" => {
+                let value = value()?;
+                let (x, y) = value.split_once(',').ok_or_else(|| {
+                    Error::invalid_parameter(format!("{} expects x,y, got {}", arg, value))
+                })?;
+                options.lens.shift = [parse_number(arg, x)?, parse_number(arg, y)?];
+                options.thin_lens = true;
+            }
+            "--stereo" => {
+                options.stereo = match value()?.as_str() {
+                    "side-by-side" => Some(StereoOutput::SideBySide),
+                    "separate" => Some(StereoOutput::Separate),
+                    value => {
+                        return Err(Error::invalid_parameter(format!(
+                            "--stereo is either side-by-side or separate, got {}",
+                            value
+                        )))
+                    }
+                }
+            }
+            "--interocular-distance" => {
+                options.interocular_distance = Some(parse_number(arg, value()?)?)
+            }
+            "--convergence-distance" => {
+                options.convergence_distance = Some(parse_number(arg, value()?)?)
+            }
+            "--tile-order" => {
+                options.tile_order = match value()?.as_str() {
+                    "row-major" => TileOrder::RowMajor,
+                    "spiral" => TileOrder::Spiral,
+                    "hilbert" => TileOrder::Hilbert,
+                    value => {
+                        return Err(Error::invalid_parameter(format!(
+                            "--tile-order is row-major, spiral or hilbert, got {}",
+                            value
+                        )))
+                    }
+                }
+            }
+            "--light-sampling" => {
+                options.light_sampling = match value()?.as_str() {
+                    "uniform" => LightSampling::Uniform,
+                    "power" => LightSampling::Power,
+                    "bvh" => LightSampling::Bvh,
+                    value => {
+                        return Err(Error::invalid_parameter(format!(
+                            "--light-sampling is uniform, power or bvh, got {}",
+                            value
+                        )))
+                    }
+                }
+            }
+            _ => {
+                return Err(Error::invalid_parameter(format!(
+                    "unknown argument {}, usage: {}",
+                    arg, USAGE
+                )))
+            }
+        }
+    }
 
-            for i in 0..width {
-                for j in 0..height {
-                    let mut pixel_color = Color3::default();
-                    for _ in 0..SAMPLES_PER_PIXEL {
-                        let x = tile.x + i;
-                        let y = tile.y + j;
+    if options.full_size && options.crop.is_none() {
+        return Err(Error::invalid_parameter(format!(
+            "--full-size only applies to --crop, usage: {}",
+            USAGE
+        )));
+    }
 
-                        let u = (x as Float + small_rng.gen::<Float>()) / (WIDTH - 1) as Float;
-                        let v = (y as Float + small_rng.gen::<Float>()) / (HEIGHT - 1) as Float;
-                        let ray = CAMERA.get_ray(u, v, &mut small_rng);
+    Ok(options)
+}
 
-                        let pixel = ray_color(&ray, &WORLD, &mut small_rng, MAX_DEPTH);
+/// Parses comma separated pixel coordinates like `10,20`
+fn parse_list<const N: usize>(arg: &str, value: &str) -> Result<[usize; N]> {
+    let invalid = || {
+        Error::invalid_parameter(format!(
+            "{} expects {} comma separated integers, got {}",
+            arg, N, value
+        ))
+    };
 
-                        if pixel.is_normal() {
-                            pixel_color += pixel;
-                        }
-                    }
+    let values = value
+        .split(',')
+        .map(|value| value.trim().parse::<usize>().map_err(|_| invalid()))
+        .collect::<Result<Vec<usize>>>()?;
 
-                    pixel_color *= scale;
-                    tile.set(i, j, pixel_color);
-                }
-            }
+    values.try_into().map_err(|_| invalid())
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, value: &str) -> Result<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| Error::invalid_parameter(format!("{} expects a number, got {}", arg, value)))
+}
+
+/// Parses an inclusive range of frames like `1..24`
+fn parse_frames(arg: &str, value: &str) -> Result<[usize; 2]> {
+    let invalid = || {
+        Error::invalid_parameter(format!(
+            "{} expects a range of frames like 1..24, got {}",
+            arg, value
+        ))
+    };
+
+    let (first, last) = value.split_once("..").ok_or_else(invalid)?;
+    let first = first.trim().parse::<usize>().map_err(|_| invalid())?;
+    let last = last.trim().parse::<usize>().map_err(|_| invalid())?;
+    if first > last {
+        return Err(invalid());
+    }
+
+    Ok([first, last])
+}
 
-            tx.send(tile).unwrap();
-        })
-    });
+fn render_command(options: RenderOptions) -> Result<()> {
+    let earlier = Instant::now();
 
-    let div_up = |a, b| {
-        if a % b == 0 {
-            a / b
-        } else {
-            a / b + 1
+    let mut settings = RenderSettings {
+        width: WIDTH,
+        height: HEIGHT,
+        samples_per_pixel: SAMPLES_PER_PIXEL,
+        max_depth: MAX_DEPTH,
+        num_threads: NUM_CPU,
+        tile_order: options.tile_order,
+        max_sample_value: options.max_sample_value,
+        seed: options.seed,
+        collect_stats: options.print_stats || options.write_stats_json,
+        ..RenderSettings::default()
+    };
+
+    let mut scene = match &options.scene {
+        Some(path)
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "pbrt") =>
+        {
+            let (scene, file_settings) = read_pbrt(path)?;
+            settings.width = file_settings.width;
+            settings.height = file_settings.height;
+            settings.samples_per_pixel = file_settings.samples_per_pixel;
+            settings.max_depth = file_settings.max_depth;
+            scene
         }
+        Some(path) => match built_in_scene(path) {
+            Some(scene) => scene(settings.aspect_ratio())?,
+            None => read_gltf(path, settings.aspect_ratio())?,
+        },
+        None => random_spheres(settings.aspect_ratio())?,
     };
+    scene.set_light_sampling(options.light_sampling);
+    if options.thin_lens {
+        scene.set_thin_lens(thin_lens(&options)?)?;
+    }
 
-    for i in 0..div_up(WIDTH, TILE_WIDTH) {
-        for j in 0..div_up(HEIGHT, TILE_HEIGHT) {
-            thread_pool.push_que(Tile {
-                x: i * TILE_WIDTH,
-                y: j * TILE_HEIGHT,
-                buffer: vec![0.0; TILE_WIDTH * TILE_HEIGHT * 4],
-            });
+    // The film's y axis points up, unlike the image's
+    if let Some([x, y]) = options.pixel {
+        let y = settings.height.checked_sub(y + 1).ok_or_else(|| {
+            Error::invalid_parameter(format!("pixel ({}, {}) is outside of the image", x, y))
+        })?;
+        let stdout = io::stdout();
+        let mut out = io::BufWriter::new(stdout.lock());
+        trace_pixel(&mut out, &scene, &settings, x, y)?;
+        out.flush()?;
+        return Ok(());
+    }
+
+    if let Some([x0, y0, x1, y1]) = options.crop {
+        // Checked as given on the command line, before flipping it into film coordinates can underflow
+        settings.crop = Some(Crop { x0, y0, x1, y1 });
+        settings.validate()?;
+
+        settings.crop = Some(Crop {
+            x0,
+            y0: settings.height - y1,
+            x1,
+            y1: settings.height - y0,
+        });
+    }
+
+    // Ctrl-C stops the render and still writes out whatever was finished, a second one quits straight away
+    let cancel = CancellationToken::default();
+    {
+        let cancel = cancel.clone();
+        let handler = move || {
+            if cancel.is_cancelled() {
+                std::process::exit(130);
+            }
+            cancel.cancel();
+        };
+        if let Err(error) = ctrlc::set_handler(handler) {
+            eprintln!("warning: Ctrl-C won't save a partial image: {}", error);
         }
     }
 
-    std::thread::spawn(move || {
-        // use std::time::Instant;
-        // let earlier = Instant::now();
-        thread_pool.execute_que();
-        // println!(
-        //     "{} milliseconds",
-        //     Instant::now().duration_since(earlier).as_nanos() as f64 / 1_000_000.0
-        // );
-    });
+    let prefix = std::path::Path::new("renders");
+    std::fs::create_dir_all(prefix)?;
 
-    let mut image = vec![0u8; WIDTH * HEIGHT * 4];
+    // Every frame reuses the scene, only moving what's animated
+    let frames: Vec<Option<usize>> = match options.frames {
+        Some([first, last]) => (first..=last).map(Some).collect(),
+        None => vec![None],
+    };
+    for frame in frames {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let suffix = match frame {
+            Some(frame) => {
+                scene.set_time(frame as Float)?;
+                format!("_{:04}", frame)
+            }
+            None => String::new(),
+        };
+        render_frame(&mut scene, &settings, &options, &cancel, prefix, &suffix)?;
+    }
 
-    let total_tiles = div_up(WIDTH, TILE_WIDTH) * div_up(HEIGHT, TILE_HEIGHT);
-    let mut remaining = total_tiles;
+    println!(
+        "Took {} seconds",
+        Instant::now().duration_since(earlier).as_nanos() as f64 / 1_000_000_000.0
+    );
 
-    for tile in rx {
-        remaining -= 1;
-        print!("{esc}[2J{esc}[1;1H", esc = 27 as char);
-        println!("{}/{} tiles remaining...", remaining, total_tiles);
-        println!(
-            "Estimated {} seconds remaining...",
-            ((Instant::now().duration_since(earlier).as_nanos() as f64)
-                / ((total_tiles - remaining) as f64))
-                * (remaining as f64)
-                / 1_000_000_000f64
-        );
+    Ok(())
+}
+
+/// The scene `--scene` names if it isn't a file
+fn built_in_scene(name: &std::path::Path) -> Option<fn(Float) -> Result<Scene>> {
+    match name.to_str()? {
+        "cornell-box" => Some(cornell_box),
+        "glass-sphere" => Some(glass_sphere),
+        "metal-spheres" => Some(metal_spheres),
+        _ => None,
+    }
+}
 
-        io::stdout().flush().unwrap();
+/// The camera's lens from the command line, with a circular aperture unless it's given blades or a mask
+fn thin_lens(options: &RenderOptions) -> Result<ThinLens> {
+    let aperture = match (options.blades, &options.aperture_mask) {
+        (Some(_), Some(_)) => {
+            return Err(Error::invalid_parameter(
+                "the aperture can either have blades or a mask, not both",
+            ))
+        }
+        (Some(blades), None) => Aperture::Polygon {
+            blades,
+            rotation: options.blade_rotation,
+        },
+        (None, Some(path)) => Aperture::Mask(ApertureMask::read(path)?),
+        (None, None) => Aperture::Circle,
+    };
 
-        let width = usize::min(TILE_WIDTH, WIDTH - tile.x);
-        let height = usize::min(TILE_HEIGHT, HEIGHT - tile.y);
+    Ok(ThinLens {
+        aperture,
+        ..options.lens.clone()
+    })
+}
 
-        for i in 0..width {
-            for j in 0..height {
-                for k in 0..4 {
-                    image[index(tile.x + i, tile.y + j) + k] =
-                        (256.0 * tile.buffer[Tile::index(i, j) + k].clamp(0.0, 0.999)) as u8;
+/// Renders every view of the scene as it is and writes the images to `prefix`.
+/// `suffix` goes at the end of the file names to tell frames apart
+fn render_frame(
+    scene: &mut Scene,
+    settings: &RenderSettings,
+    options: &RenderOptions,
+    cancel: &CancellationToken,
+    prefix: &std::path::Path,
+    suffix: &str,
+) -> Result<()> {
+    // The views only differ in their camera, they share the rest of the scene
+    let outputs = match options.stereo {
+        Some(output) => {
+            let rig = StereoRig {
+                interocular_distance: options.interocular_distance.unwrap_or(INTEROCULAR_DISTANCE),
+                convergence_distance: match options.convergence_distance {
+                    Some(distance) if distance == Float::INFINITY => None,
+                    Some(distance) => Some(distance),
+                    None => CONVERGENCE_DISTANCE,
+                },
+            };
+            let mut films = Vec::new();
+            for (eye, camera) in ["_left", "_right"]
+                .iter()
+                .zip(Vec::from(scene.camera.stereo_pair(&rig)?))
+            {
+                if cancel.is_cancelled() {
+                    break;
                 }
+                let suffix = format!("{}{}", suffix, eye);
+                let camera = std::mem::replace(&mut scene.camera, camera);
+                let film = render_view(scene, settings, options, cancel, &suffix);
+                scene.camera = camera;
+                films.push((suffix, film?));
             }
-        }
 
-        if remaining == 0 {
-            break;
+            match output {
+                StereoOutput::SideBySide => {
+                    let films = films.into_iter().map(|(_, film)| film).collect();
+                    vec![(suffix.to_string(), Film::side_by_side(films)?)]
+                }
+                StereoOutput::Separate => films,
+            }
         }
+        None => vec![(
+            suffix.to_string(),
+            render_view(scene, settings, options, cancel, suffix)?,
+        )],
+    };
+
+    if cancel.is_cancelled() {
+        println!("Cancelled, writing the partial image");
+    } else {
+        println!("Done!");
     }
 
-    print!("\r{esc}[K", esc = 27 as char);
-    println!("Done!");
+    for (suffix, mut film) in outputs {
+        let output_start = Instant::now();
+        let path = prefix.join(format!("image{}.png", suffix));
+
+        if WRITE_AOVS {
+            film.write_exr(&path.with_extension("exr"))?;
+        }
 
-    let path = std::path::Path::new("renders/image.png");
-    let prefix = path.parent().unwrap();
-    std::fs::create_dir_all(prefix).unwrap();
+        if options.denoise {
+            denoise::denoise(&mut film);
+        }
 
-    let image_buffer: image::ImageBuffer<image::Rgba<u8>, _> =
-        image::ImageBuffer::from_raw(WIDTH as u32, HEIGHT as u32, image).unwrap();
+        film.write_png(&path)?;
 
-    let dynamic_image = image::DynamicImage::ImageRgba8(image_buffer);
+        film.stats.output_time = output_start.elapsed();
 
-    let dynamic_image = dynamic_image.flipv();
+        if options.print_stats {
+            print!("{}", film.stats.report());
+        }
+        if options.write_stats_json {
+            std::fs::write(
+                prefix.join(format!("stats{}.json", suffix)),
+                film.stats.to_json(),
+            )?;
+        }
 
-    dynamic_image
-        .save_with_format(path, image::ImageFormat::Png)
-        .unwrap();
+        if !film.diagnostics.is_empty() {
+            print!("{}", film.diagnostics.report());
+        }
+    }
 
-    println!(
-        "Took {} seconds",
-        Instant::now().duration_since(earlier).as_nanos() as f64 / 1_000_000_000.0
-    )
+    Ok(())
 }
 
-#[inline]
-fn index(i: usize, j: usize) -> usize {
-    (j * WIDTH + i) * 4
-}
+/// Renders `scene` from its camera with a progress report, cropped as asked.
+/// `suffix` tells the frames and views apart in the file names, like `_0001_left`, and is empty for a single image
+fn render_view(
+    scene: &Scene,
+    settings: &RenderSettings,
+    options: &RenderOptions,
+    cancel: &CancellationToken,
+    suffix: &str,
+) -> Result<Film> {
+    let earlier = Instant::now();
+    let view = match suffix {
+        "" => String::new(),
+        suffix => format!(" in image{}", suffix),
+    };
+
+    let film = render_with_progress(scene, settings, cancel, |done, total_pixels| {
+        let remaining = total_pixels - done;
+        print!("{esc}[2J{esc}[1;1H", esc = 27 as char);
+        println!("{}/{} pixels remaining{}...", remaining, total_pixels, view);
+        println!(
+            "Estimated {} seconds remaining...",
+            ((Instant::now().duration_since(earlier).as_nanos() as f64) / (done as f64))
+                * (remaining as f64)
+                / 1_000_000_000f64
+        );
+
+        // Progress is best effort, a closed stdout shouldn't stop the render
+        let _ = io::stdout().flush();
+    })?;
+    print!("\r{esc}[K", esc = 27 as char);
+
+    // Paths can only be traced again while the scene still has this view's camera
+    if options.debug_invalid_samples && !film.diagnostics.is_empty() {
+        let path = format!("renders/invalid_samples{}.txt", suffix);
+        film.diagnostics
+            .write_paths(std::path::Path::new(&path), scene, settings)?;
+    }
 
-struct Tile {
-    x: usize,
-    y: usize,
-    buffer: Vec<Float>,
+    Ok(match (settings.crop, options.full_size) {
+        (Some(crop), false) => film.cropped(crop),
+        _ => film,
+    })
 }
 
-impl Tile {
-    #[inline]
-    fn index(i: usize, j: usize) -> usize {
-        (j * TILE_WIDTH + i) * 4
+/// `rustrt denoise <input.exr> <output.(png|exr)>`
+/// The input needs the albedo, normal, depth and variance AOVs for the best results
+fn denoise_command(args: &[String]) -> Result<()> {
+    if args.len() != 2 {
+        return Err(Error::invalid_parameter(
+            "usage: rustrt denoise <input.exr> <output.(png|exr)>",
+        ));
     }
 
-    #[inline]
-    pub fn set(&mut self, i: usize, j: usize, rgb: Color3) {
-        // apply gamma correction
-        self.buffer[Tile::index(i, j)] = Float::sqrt(rgb[0]);
-        self.buffer[Tile::index(i, j) + 1] = Float::sqrt(rgb[1]);
-        self.buffer[Tile::index(i, j) + 2] = Float::sqrt(rgb[2]);
-        self.buffer[Tile::index(i, j) + 3] = 1.0;
+    let input = std::path::Path::new(&args[0]);
+    let output = std::path::Path::new(&args[1]);
+
+    let mut film = Film::read_exr(input)?;
+
+    let earlier = Instant::now();
+    denoise::denoise(&mut film);
+    println!(
+        "Denoised in {} seconds",
+        Instant::now().duration_since(earlier).as_nanos() as f64 / 1_000_000_000.0
+    );
+
+    match output.extension().and_then(|extension| extension.to_str()) {
+        Some("exr") => film.write_exr(output),
+        _ => film.write_png(output),
     }
 }
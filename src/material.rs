@@ -1,5 +1,8 @@
 use super::*;
 
+#[cfg(test)]
+mod tests;
+
 pub enum ReflectanceModel {
     Diffuse(Diffuse),
     Metal(Metal),
@@ -43,6 +46,26 @@ impl ReflectanceModelTrait for ReflectanceModel {
             Dielectric(material) => material.pdf(dir_in, dir_out),
         }
     }
+
+    fn is_specular(&self) -> bool {
+        use ReflectanceModel::*;
+
+        match self {
+            Diffuse(material) => material.is_specular(),
+            Metal(material) => material.is_specular(),
+            Dielectric(material) => material.is_specular(),
+        }
+    }
+
+    fn albedo(&self) -> Color3 {
+        use ReflectanceModel::*;
+
+        match self {
+            Diffuse(material) => material.albedo(),
+            Metal(material) => material.albedo(),
+            Dielectric(material) => material.albedo(),
+        }
+    }
 }
 
 struct Basis {
@@ -60,7 +83,7 @@ impl Basis {
         )
     }
 
-    fn from_local(&self, v: &Vector3) -> Vector3 {
+    fn to_world(&self, v: &Vector3) -> Vector3 {
         v.x * self.u + v.y * self.v + v.z * self.w
     }
 
@@ -81,6 +104,8 @@ impl Basis {
 
 pub struct Material {
     pub reflectance_model: ReflectanceModel,
+    /// Tints the reflectance model, which should then usually be white
+    pub texture: Option<Texture>,
 }
 
 impl Material {
@@ -103,40 +128,105 @@ impl Material {
 
         let f = self
             .reflectance_model
-            .scatter(&dir_in, &mut dir_out, pdf, is_specular, rng);
+            .scatter(&dir_in, &mut dir_out, pdf, is_specular, rng)
+            * self.tint(interaction);
 
         *ray_out = Ray {
             origin: interaction.p,
-            direction: basis.from_local(&dir_out),
+            direction: basis.to_world(&dir_out),
         };
 
         f
     }
 
-    #[allow(dead_code)]
     /// `ray_in.direction` should be normalized.
     /// As well as `interaction.normal`
-    pub fn reflectance(
-        &self,
-        ray_in: &Ray,
-        ray_out: &mut Ray,
-        interaction: &Interaction,
-    ) -> Color3 {
+    pub fn reflectance(&self, ray_in: &Ray, ray_out: &Ray, interaction: &Interaction) -> Color3 {
         let basis = Basis::from_normal(&interaction.normal);
 
         let dir_in = -basis.to_local(&ray_in.direction);
         let dir_out = basis.to_local(&ray_out.direction);
 
-        self.reflectance_model.reflectance(&dir_in, &dir_out)
+        self.reflectance_model.reflectance(&dir_in, &dir_out) * self.tint(interaction)
+    }
+
+    /// Specular materials have a delta distribution so `reflectance` is zero for every direction
+    /// but a single one, and there is no point in sampling lights for them.
+    pub fn is_specular(&self) -> bool {
+        self.reflectance_model.is_specular()
+    }
+
+    /// Overall colour of the material at the hit, used for the albedo AOV
+    pub fn albedo(&self, interaction: &Interaction) -> Color3 {
+        self.reflectance_model.albedo() * self.tint(interaction)
+    }
+
+    fn tint(&self, interaction: &Interaction) -> Color3 {
+        match &self.texture {
+            Some(texture) => texture.evaluate(interaction),
+            None => Color3::new(1.0, 1.0, 1.0),
+        }
     }
 }
 
 impl From<ReflectanceModel> for Material {
     fn from(reflectance_model: ReflectanceModel) -> Material {
-        Material { reflectance_model }
+        Material {
+            reflectance_model,
+            texture: None,
+        }
+    }
+}
+
+/// Index of a material in the `MaterialList` it was added to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MaterialId(u32);
+
+impl MaterialId {
+    /// The id written to the material id AOV, 0 is left for pixels that didn't hit anything
+    pub fn aov_id(self) -> u32 {
+        self.0 + 1
+    }
+}
+
+/// Owns every material of a scene, objects refer to them by `MaterialId`
+#[derive(Default)]
+pub struct MaterialList {
+    materials: Vec<Material>,
+}
+
+impl MaterialList {
+    pub fn add(&mut self, material: Material) -> MaterialId {
+        self.materials.push(material);
+        MaterialId((self.materials.len() - 1) as u32)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.materials.len()
+    }
+
+    pub fn get(&self, id: MaterialId) -> Option<&Material> {
+        self.materials.get(id.0 as usize)
+    }
+}
+
+impl std::ops::Index<MaterialId> for MaterialList {
+    type Output = Material;
+
+    fn index(&self, id: MaterialId) -> &Material {
+        &self.materials[id.0 as usize]
     }
 }
 
+/// Specular models sample a delta distribution, so `pdf` is the probability of picking the delta
+/// and `reflectance` is its weight divided by `|dir_out.z|`, both only for the exact direction of the delta
+/// and 0 for every other direction. Their `scatter` returns the same as `pdf` and `reflectance` would,
+/// so that `reflectance * |cos| / pdf` is the weight of the delta.
+/// `material/tests.rs` checks every model against this.
 trait ReflectanceModelTrait {
     /// Scatters the dir_in
     /// Can also be assumed that `dir_in` and `dir_out` is in coordinate where the normal is pointing in the +z
@@ -164,7 +254,7 @@ trait ReflectanceModelTrait {
     /// it can be assumed that `dir_in` and `dir_out` is in coordinate where the normal is pointing in the +z direction
     fn reflectance(&self, dir_in: &Vector3, dir_out: &Vector3) -> Color3;
 
-    /// PDF of the distribution generated by `scatter`
+    /// PDF of the distribution generated by `scatter` with respect to solid angle
     fn pdf(&self, dir_in: &Vector3, dir_out: &Vector3) -> Float {
         if dir_in.z * dir_out.z > 0.0 {
             Float::abs(dir_out.z) * FRAC_1_PI
@@ -172,6 +262,14 @@ trait ReflectanceModelTrait {
             0.0
         }
     }
+
+    /// Whether `scatter` samples a delta distribution
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    /// Hemispherical reflectance, doesn't need to be exact
+    fn albedo(&self) -> Color3;
 }
 
 pub struct Diffuse {
@@ -182,6 +280,10 @@ impl ReflectanceModelTrait for Diffuse {
     fn reflectance(&self, _: &Vector3, _: &Vector3) -> Color3 {
         self.albedo * FRAC_1_PI
     }
+
+    fn albedo(&self) -> Color3 {
+        self.albedo
+    }
 }
 
 pub struct Metal {
@@ -198,21 +300,37 @@ impl ReflectanceModelTrait for Metal {
         is_specular: &mut bool,
         _: &mut SmallRng,
     ) -> Color3 {
-        *dir_out = Vector3::new(-dir_in.x, -dir_in.y, dir_in.z);
-        *pdf = 1.0;
+        *dir_out = mirror(dir_in);
+        *pdf = self.pdf(dir_in, dir_out);
         *is_specular = true;
 
-        // idk maybe I should use actual fresnel
-        // I don't even care rn
-        self.albedo / Float::abs(dir_out.z)
+        self.reflectance(dir_in, dir_out)
     }
 
-    fn reflectance(&self, _: &Vector3, _: &Vector3) -> Color3 {
-        Color3::new(0.0, 0.0, 0.0)
+    fn reflectance(&self, dir_in: &Vector3, dir_out: &Vector3) -> Color3 {
+        if is_delta_direction(dir_out, &mirror(dir_in)) {
+            // idk maybe I should use actual fresnel
+            // I don't even care rn
+            self.albedo / Float::abs(dir_out.z)
+        } else {
+            Color3::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    fn pdf(&self, dir_in: &Vector3, dir_out: &Vector3) -> Float {
+        if is_delta_direction(dir_out, &mirror(dir_in)) {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn is_specular(&self) -> bool {
+        true
     }
 
-    fn pdf(&self, _: &Vector3, _: &Vector3) -> Float {
-        0.0
+    fn albedo(&self) -> Color3 {
+        self.albedo
     }
 }
 
@@ -229,6 +347,41 @@ impl ReflectanceModelTrait for Dielectric {
         is_specular: &mut bool,
         _: &mut SmallRng,
     ) -> Color3 {
+        *dir_out = self.specular_direction(dir_in);
+        *pdf = self.pdf(dir_in, dir_out);
+        *is_specular = true;
+
+        self.reflectance(dir_in, dir_out)
+    }
+
+    fn reflectance(&self, dir_in: &Vector3, dir_out: &Vector3) -> Color3 {
+        if is_delta_direction(dir_out, &self.specular_direction(dir_in)) {
+            Color3::new(1.0, 1.0, 1.0) / Float::abs(dir_out.z)
+        } else {
+            Color3::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    fn pdf(&self, dir_in: &Vector3, dir_out: &Vector3) -> Float {
+        if is_delta_direction(dir_out, &self.specular_direction(dir_in)) {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+
+    fn albedo(&self) -> Color3 {
+        Color3::new(1.0, 1.0, 1.0)
+    }
+}
+
+impl Dielectric {
+    /// Refracted direction, or the mirrored one under total internal reflection
+    fn specular_direction(&self, dir_in: &Vector3) -> Vector3 {
         let refraction_ratio = match dir_in.z > 0.0 {
             true => 1.0 / self.index_of_refraction,
             false => self.index_of_refraction,
@@ -239,28 +392,25 @@ impl ReflectanceModelTrait for Dielectric {
 
         let cannot_refract = (refraction_ratio * sin_theta) > 1.0;
 
-        *dir_out = if cannot_refract {
-            Vector3::new(-dir_in.x, -dir_in.y, dir_in.z)
+        if cannot_refract {
+            mirror(dir_in)
         } else {
             Vector3::refract(
                 &-*dir_in,
                 &Vector3::flip(&Vector3::new(0.0, 0.0, 1.0), dir_in),
                 refraction_ratio,
             )
-        };
-        *pdf = 1.0;
-        *is_specular = true;
-
-        Color3::new(1.0, 1.0, 1.0) / Float::abs(dir_out.z)
+        }
     }
+}
 
-    fn reflectance(&self, _: &Vector3, _: &Vector3) -> Color3 {
-        Color3::new(0.0, 0.0, 0.0)
-    }
+fn mirror(dir_in: &Vector3) -> Vector3 {
+    Vector3::new(-dir_in.x, -dir_in.y, dir_in.z)
+}
 
-    fn pdf(&self, _: &Vector3, _: &Vector3) -> Float {
-        0.0
-    }
+/// Whether `dir_out` is the direction of a delta distribution, up to rounding
+fn is_delta_direction(dir_out: &Vector3, delta: &Vector3) -> bool {
+    Vector3::dot(dir_out, delta) >= (1.0 - 1e-5) * dir_out.length() * delta.length()
 }
 
 fn sample_disk(rng: &mut SmallRng) -> Point3 {
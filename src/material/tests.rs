@@ -0,0 +1,355 @@
+//! Statistical checks that every reflectance model's `scatter`, `reflectance` and `pdf` agree with each other
+
+use super::*;
+
+const THETA_BINS: usize = 20;
+const PHI_BINS: usize = 40;
+/// Subdivisions of every bin along each axis when integrating the pdf over it
+const BIN_SUBDIVISIONS: usize = 8;
+const SAMPLES: usize = 200_000;
+
+fn models() -> Vec<(&'static str, ReflectanceModel)> {
+    vec![
+        (
+            "white diffuse",
+            ReflectanceModel::Diffuse(Diffuse {
+                albedo: Color3::new(1.0, 1.0, 1.0),
+            }),
+        ),
+        (
+            "coloured diffuse",
+            ReflectanceModel::Diffuse(Diffuse {
+                albedo: Color3::new(0.8, 0.4, 0.1),
+            }),
+        ),
+        (
+            "white metal",
+            ReflectanceModel::Metal(Metal {
+                albedo: Color3::new(1.0, 1.0, 1.0),
+                fuzziness: 0.0,
+            }),
+        ),
+        (
+            "coloured metal",
+            ReflectanceModel::Metal(Metal {
+                albedo: Color3::new(0.9, 0.6, 0.2),
+                fuzziness: 0.3,
+            }),
+        ),
+        (
+            "glass",
+            ReflectanceModel::Dielectric(Dielectric {
+                index_of_refraction: 1.5,
+            }),
+        ),
+        (
+            "air in glass",
+            ReflectanceModel::Dielectric(Dielectric {
+                index_of_refraction: 1.0 / 1.5,
+            }),
+        ),
+    ]
+}
+
+/// Directions to scatter from, on both sides of the surface and including grazing angles
+fn incoming_directions() -> Vec<Vector3> {
+    [0.99, 0.7, 0.3, 0.05, -0.5, -0.9]
+        .iter()
+        .map(|&z: &Float| {
+            let r = Float::sqrt(1.0 - z * z);
+            Vector3::new(r * Float::cos(0.4), r * Float::sin(0.4), z)
+        })
+        .collect()
+}
+
+fn rng() -> SmallRng {
+    SmallRng::seed_from_u64(0x5eed)
+}
+
+fn uniform_sphere(rng: &mut SmallRng) -> Vector3 {
+    let z = 1.0 - 2.0 * rng.gen::<Float>();
+    let r = Float::sqrt(Float::max(0.0, 1.0 - z * z));
+    let phi = 2.0 * PI * rng.gen::<Float>();
+    Vector3::new(r * Float::cos(phi), r * Float::sin(phi), z)
+}
+
+fn scatter(
+    model: &ReflectanceModel,
+    dir_in: &Vector3,
+    rng: &mut SmallRng,
+) -> (Vector3, Float, bool, Color3) {
+    let mut dir_out = Vector3::default();
+    let mut pdf = 0.0;
+    let mut is_specular = false;
+    let reflectance = model.scatter(dir_in, &mut dir_out, &mut pdf, &mut is_specular, rng);
+    (dir_out, pdf, is_specular, reflectance)
+}
+
+/// Bins are equal area: uniform in cos theta over the whole sphere and in phi
+fn bin(direction: &Vector3) -> usize {
+    let z = direction.z.clamp(-1.0, 1.0);
+    let theta_bin = usize::min(
+        ((1.0 - z) / 2.0 * THETA_BINS as Float) as usize,
+        THETA_BINS - 1,
+    );
+    let phi = Float::atan2(direction.y, direction.x).rem_euclid(2.0 * PI);
+    let phi_bin = usize::min(
+        (phi / (2.0 * PI) * PHI_BINS as Float) as usize,
+        PHI_BINS - 1,
+    );
+    theta_bin * PHI_BINS + phi_bin
+}
+
+/// Integral of the pdf over every bin, with the midpoint rule on a finer grid
+fn integrate_pdf(model: &ReflectanceModel, dir_in: &Vector3) -> Vec<f64> {
+    let n_z = THETA_BINS * BIN_SUBDIVISIONS;
+    let n_phi = PHI_BINS * BIN_SUBDIVISIONS;
+    let d_z = 2.0 / n_z as f64;
+    let d_phi = 2.0 * std::f64::consts::PI / n_phi as f64;
+
+    let mut integrals = vec![0.0; THETA_BINS * PHI_BINS];
+    for i in 0..n_z {
+        let z = 1.0 - (i as f64 + 0.5) * d_z;
+        let r = f64::sqrt(1.0 - z * z);
+        for j in 0..n_phi {
+            let phi = (j as f64 + 0.5) * d_phi;
+            let dir_out = Vector3::new(
+                (r * phi.cos()) as Float,
+                (r * phi.sin()) as Float,
+                z as Float,
+            );
+            let index = (i / BIN_SUBDIVISIONS) * PHI_BINS + j / BIN_SUBDIVISIONS;
+            integrals[index] += model.pdf(dir_in, &dir_out) as f64 * d_z * d_phi;
+        }
+    }
+
+    integrals
+}
+
+/// Upper tail probability of a chi-square distribution, using the Wilson–Hilferty normal approximation
+fn chi_square_p_value(chi_square: f64, degrees_of_freedom: usize) -> f64 {
+    let k = degrees_of_freedom as f64;
+    let z = ((chi_square / k).cbrt() - (1.0 - 2.0 / (9.0 * k))) / f64::sqrt(2.0 / (9.0 * k));
+    0.5 * erfc(z / std::f64::consts::SQRT_2)
+}
+
+/// Complementary error function, Numerical Recipes' Chebyshev fit which is accurate to 1.2e-7
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t * f64::exp(
+        -z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))),
+    );
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+#[test]
+fn scatter_matches_pdf_chi_square() {
+    // Bonferroni corrected over every model and direction that is tested
+    let significance = 0.01 / (models().len() * incoming_directions().len()) as f64;
+
+    for (name, model) in models() {
+        if model.is_specular() {
+            continue;
+        }
+
+        for dir_in in incoming_directions() {
+            let mut rng = rng();
+            let mut observed = vec![0.0; THETA_BINS * PHI_BINS];
+            for _ in 0..SAMPLES {
+                let (dir_out, pdf, _, _) = scatter(&model, &dir_in, &mut rng);
+                if pdf > 0.0 {
+                    observed[bin(&dir_out)] += 1.0;
+                }
+            }
+            let expected: Vec<f64> = integrate_pdf(&model, &dir_in)
+                .iter()
+                .map(|integral| integral * SAMPLES as f64)
+                .collect();
+
+            // Pool the bins with too few expected samples, as the test is unreliable for them
+            let mut chi_square = 0.0;
+            let mut degrees_of_freedom = 0;
+            let (mut pooled_observed, mut pooled_expected) = (0.0, 0.0);
+            for (&observed, &expected) in observed.iter().zip(&expected) {
+                if expected == 0.0 {
+                    assert!(
+                        observed == 0.0,
+                        "{}: sampled {} directions where the pdf is 0 for {:?}",
+                        name,
+                        observed,
+                        dir_in
+                    );
+                } else if expected < 5.0 {
+                    pooled_observed += observed;
+                    pooled_expected += expected;
+                } else {
+                    chi_square += (observed - expected).powi(2) / expected;
+                    degrees_of_freedom += 1;
+                }
+            }
+            if pooled_expected > 0.0 {
+                chi_square += (pooled_observed - pooled_expected).powi(2) / pooled_expected;
+                degrees_of_freedom += 1;
+            }
+
+            let p_value = chi_square_p_value(chi_square, degrees_of_freedom - 1);
+            assert!(
+                p_value > significance,
+                "{}: sampled directions don't follow the pdf for {:?}, chi-square {} with {} degrees of freedom, p = {}",
+                name,
+                dir_in,
+                chi_square,
+                degrees_of_freedom - 1,
+                p_value
+            );
+        }
+    }
+}
+
+#[test]
+fn pdf_integrates_to_one() {
+    for (name, model) in models() {
+        if model.is_specular() {
+            continue;
+        }
+
+        for dir_in in incoming_directions() {
+            let integral: f64 = integrate_pdf(&model, &dir_in).iter().sum();
+            assert!(
+                (integral - 1.0).abs() < 1e-3,
+                "{}: pdf integrates to {} for {:?}",
+                name,
+                integral,
+                dir_in
+            );
+        }
+    }
+}
+
+#[test]
+fn scatter_agrees_with_reflectance_and_pdf() {
+    for (name, model) in models() {
+        let mut rng = rng();
+
+        for dir_in in incoming_directions() {
+            for _ in 0..1000 {
+                let (dir_out, pdf, is_specular, reflectance) = scatter(&model, &dir_in, &mut rng);
+                assert_eq!(
+                    is_specular,
+                    model.is_specular(),
+                    "{}: scatter and is_specular disagree",
+                    name
+                );
+
+                if is_specular {
+                    assert!(pdf > 0.0, "{}: a delta sample has a pdf of {}", name, pdf);
+                    // Just off the delta direction both have to be 0 again
+                    let off_delta = Vector3::unit_vector(dir_out + Vector3::new(0.01, -0.01, 0.0));
+                    assert_eq!(
+                        model.pdf(&dir_in, &off_delta),
+                        0.0,
+                        "{}: the pdf of a delta isn't 0 next to it",
+                        name
+                    );
+                    assert!(
+                        model.reflectance(&dir_in, &off_delta).near_zero(),
+                        "{}: the reflectance of a delta isn't 0 next to it",
+                        name
+                    );
+                }
+                if pdf > 0.0 {
+                    let expected_pdf = model.pdf(&dir_in, &dir_out);
+                    assert!(
+                        (pdf - expected_pdf).abs() <= 1e-4 * expected_pdf,
+                        "{}: scatter gave a pdf of {} but pdf gives {}",
+                        name,
+                        pdf,
+                        expected_pdf
+                    );
+                    let expected_reflectance = model.reflectance(&dir_in, &dir_out);
+                    assert!(
+                        (reflectance - expected_reflectance).length() <= 1e-4,
+                        "{}: scatter gave a reflectance of {:?} but reflectance gives {:?}",
+                        name,
+                        reflectance,
+                        expected_reflectance
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn reflectance_is_reciprocal() {
+    let mut rng = rng();
+
+    for (name, model) in models() {
+        for _ in 0..10_000 {
+            let a = uniform_sphere(&mut rng);
+            let b = uniform_sphere(&mut rng);
+
+            let ab = model.reflectance(&a, &b);
+            let ba = model.reflectance(&b, &a);
+            assert!(
+                (ab - ba).length() <= 1e-5 * Float::max(ab.length(), 1.0),
+                "{}: reflectance isn't reciprocal for {:?} and {:?}, {:?} != {:?}",
+                name,
+                a,
+                b,
+                ab,
+                ba
+            );
+        }
+    }
+}
+
+/// A white material in a uniformly white environment must reflect exactly as much light as it receives
+#[test]
+fn white_furnace() {
+    for (name, model) in models() {
+        if !(model.albedo() - Color3::new(1.0, 1.0, 1.0)).near_zero() {
+            continue;
+        }
+
+        for dir_in in incoming_directions() {
+            let mut rng = rng();
+            let mut sum = Color3::default();
+            for _ in 0..SAMPLES {
+                let (dir_out, pdf, _, reflectance) = scatter(&model, &dir_in, &mut rng);
+                if pdf > 0.0 {
+                    let weight = reflectance * Float::abs(dir_out.z) / pdf;
+                    assert!(
+                        weight.x <= 1.0 + 1e-4 && weight.y <= 1.0 + 1e-4 && weight.z <= 1.0 + 1e-4,
+                        "{}: a single sample reflects {:?} for {:?}",
+                        name,
+                        weight,
+                        dir_in
+                    );
+                    sum += weight;
+                }
+            }
+
+            let mean = sum / SAMPLES as Float;
+            assert!(
+                (mean - Color3::new(1.0, 1.0, 1.0)).length() < 1e-3,
+                "{}: reflects {:?} of the incoming light for {:?}",
+                name,
+                mean,
+                dir_in
+            );
+        }
+    }
+}
@@ -0,0 +1,900 @@
+//! Reads the subset of pbrt-v3's scene format that this renderer has a match for, so that the same scenes
+//! can be rendered by both. That's the transform directives and named coordinate systems, `AttributeBegin`/`End`,
+//! `TransformBegin`/`End`, `Include`, perspective, orthographic and realistic `Camera`s, the resolution and
+//! diagonal from `Film`,
+//! the samples per pixel from `Sampler` and the depth from `Integrator`, `sphere`, `trianglemesh` and `plymesh`
+//! shapes, `matte`, `plastic`, `metal`, `mirror` and `glass` materials, named or not, and `point`, `spot`,
+//! `distant` and constant `infinite` lights.
+//!
+//! Plastic loses its glossy coat and metal its roughness, which is kept as its fuzziness but not rendered,
+//! and colours have to be rgb.
+//! `PixelFilter`, `Accelerator` and `ReverseOrientation` are ignored as they don't change what the image
+//! converges to, and anything else, like textures and area lights, is an error
+
+use super::camera::*;
+use super::environment::Environment;
+use super::hittable::*;
+use super::light::*;
+use super::material::*;
+use super::ply::read_ply;
+use super::transforms::Transform;
+use super::vector::*;
+use super::{Error, Float, RenderSettings, Result, Scene};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Includes nested deeper than this are taken to be including themselves
+const MAX_INCLUDE_DEPTH: usize = 32;
+
+/// Returns the scene and the render settings with the file's resolution, samples per pixel and depth.
+/// Files that are included or loaded from it are looked for next to it, like pbrt does
+pub fn read_pbrt(path: &Path) -> Result<(Scene, RenderSettings)> {
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let tokens = tokenize(path, directory, 0)?;
+
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        directory,
+        transform: Transform::default(),
+        material: None,
+        stack: vec![],
+        named_materials: HashMap::new(),
+        coordinate_systems: HashMap::new(),
+        camera: None,
+        film_diagonal: 35.0,
+        settings: RenderSettings {
+            width: 640,
+            height: 480,
+            samples_per_pixel: 16,
+            max_depth: 5,
+            ..RenderSettings::default()
+        },
+        world: HittableList::default(),
+        materials: MaterialList::default(),
+        lights: LightList::default(),
+        environment: Color3::new(0.0, 0.0, 0.0),
+    };
+    parser.parse()?;
+
+    let camera = parser.camera()?;
+    let mut scene = Scene::new(parser.world, parser.materials, parser.lights, camera)?;
+    // pbrt's background is black unless there's an infinite light
+    scene.environment = Environment::Constant(parser.environment);
+    Ok((scene, parser.settings))
+}
+
+/// pbrt's world is left handed, so it's mirrored in x to come out the same way round in this right handed renderer
+fn to_right_handed() -> Transform {
+    Transform::scale(&Vector3::new(-1.0, 1.0, 1.0))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    /// Directive names and the unquoted `true` and `false`
+    Word(String),
+    String(String),
+    Number(f64),
+    Open,
+    Close,
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    file: Rc<str>,
+    line: usize,
+}
+
+impl Token {
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error::parse(
+            &*self.file,
+            format!("line {}: {}", self.line, message.into()),
+        )
+    }
+}
+
+/// Splits the file into tokens, with the tokens of the files it includes in place of the `Include` directives
+fn tokenize(path: &Path, directory: &Path, depth: usize) -> Result<Vec<Token>> {
+    let file: Rc<str> = path.display().to_string().into();
+    let text = std::fs::read_to_string(path)?;
+
+    let mut tokens = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let token = |kind| Token {
+            kind,
+            file: file.clone(),
+            line: i + 1,
+        };
+        let mut rest = line;
+        loop {
+            rest = rest.trim_start();
+            let next = match rest.chars().next() {
+                None | Some('#') => break,
+                Some(next) => next,
+            };
+
+            match next {
+                '[' => tokens.push(token(TokenKind::Open)),
+                ']' => tokens.push(token(TokenKind::Close)),
+                '"' => {
+                    let end = rest[1..].find('"').ok_or_else(|| {
+                        Error::parse(
+                            &*file,
+                            format!("line {}: the string doesn't end on this line", i + 1),
+                        )
+                    })?;
+                    tokens.push(token(TokenKind::String(rest[1..end + 1].to_string())));
+                    rest = &rest[end + 2..];
+                    continue;
+                }
+                _ => {
+                    let end = rest
+                        .find(|c: char| c.is_whitespace() || "[]\"#".contains(c))
+                        .unwrap_or(rest.len());
+                    let word = &rest[..end];
+                    tokens.push(token(match word.parse::<f64>() {
+                        Ok(number) => TokenKind::Number(number),
+                        Err(_) => TokenKind::Word(word.to_string()),
+                    }));
+                    rest = &rest[end..];
+                    continue;
+                }
+            }
+            rest = &rest[1..];
+        }
+    }
+
+    // Includes are spliced in here so that the parser sees a single stream of directives
+    let mut spliced = Vec::with_capacity(tokens.len());
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        if token.kind != TokenKind::Word("Include".to_string()) {
+            spliced.push(token);
+            continue;
+        }
+        let included = match tokens.next() {
+            Some(Token {
+                kind: TokenKind::String(included),
+                ..
+            }) => included,
+            _ => return Err(token.error("Include needs the name of a file")),
+        };
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(token.error(format!("{} is included too deeply", included)));
+        }
+        spliced.extend(tokenize(&directory.join(included), directory, depth + 1)?);
+    }
+
+    Ok(spliced)
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    /// Kept in double precision so that large indices are exact
+    Number(f64),
+    String(String),
+}
+
+/// A parameter like `"rgb Kd" [0.5 0.5 0.5]`
+#[derive(Clone, Debug)]
+struct Parameter {
+    ty: String,
+    name: String,
+    values: Vec<Value>,
+}
+
+/// The parameter list of a directive, which errors point back to
+#[derive(Clone, Debug)]
+struct Parameters {
+    directive: Token,
+    list: Vec<Parameter>,
+}
+
+impl Parameters {
+    /// Only the first of `types` is named in errors, the others are its aliases
+    fn find(&self, name: &str, types: &[&str]) -> Result<Option<&Parameter>> {
+        let parameter = match self.list.iter().find(|parameter| parameter.name == name) {
+            Some(parameter) => parameter,
+            None => return Ok(None),
+        };
+        if types.contains(&parameter.ty.as_str()) {
+            return Ok(Some(parameter));
+        }
+
+        Err(self.directive.error(match parameter.ty.as_str() {
+            "texture" => format!("{} is a texture, which isn't supported", name),
+            "spectrum" | "blackbody" | "xyz" if types[0] == "rgb" => {
+                format!(
+                    "{} is a {}, only rgb colours are supported",
+                    name, parameter.ty
+                )
+            }
+            ty => format!("{} should be a {}, not a {}", name, types[0], ty),
+        }))
+    }
+
+    fn numbers(&self, name: &str, types: &[&str]) -> Result<Option<Vec<Float>>> {
+        Ok(self
+            .exact_numbers(name, types)?
+            .map(|numbers| numbers.into_iter().map(|number| number as Float).collect()))
+    }
+
+    fn exact_numbers(&self, name: &str, types: &[&str]) -> Result<Option<Vec<f64>>> {
+        let parameter = match self.find(name, types)? {
+            Some(parameter) => parameter,
+            None => return Ok(None),
+        };
+        parameter
+            .values
+            .iter()
+            .map(|value| match value {
+                Value::Number(number) => Ok(*number),
+                Value::String(string) => Err(self
+                    .directive
+                    .error(format!("{} should be numbers, got \"{}\"", name, string))),
+            })
+            .collect::<Result<Vec<f64>>>()
+            .map(Some)
+    }
+
+    fn integers(&self, name: &str) -> Result<Option<Vec<u32>>> {
+        let integers = match self.exact_numbers(name, &["integer"])? {
+            Some(integers) => integers,
+            None => return Ok(None),
+        };
+        integers
+            .into_iter()
+            .map(|integer| {
+                match integer >= 0.0 && integer <= u32::MAX as f64 && integer.fract() == 0.0 {
+                    true => Ok(integer as u32),
+                    false => Err(self.directive.error(format!(
+                        "{} should be positive integers, got {}",
+                        name, integer
+                    ))),
+                }
+            })
+            .collect::<Result<Vec<u32>>>()
+            .map(Some)
+    }
+
+    /// Exactly `N` numbers or nothing
+    fn fixed<const N: usize>(&self, name: &str, types: &[&str]) -> Result<Option<[Float; N]>> {
+        match self.numbers(name, types)? {
+            Some(numbers) => numbers.try_into().map(Some).map_err(|numbers: Vec<Float>| {
+                self.directive.error(format!(
+                    "{} needs {} values, got {}",
+                    name,
+                    N,
+                    numbers.len()
+                ))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    fn float(&self, name: &str, default: Float) -> Result<Float> {
+        Ok(self
+            .fixed::<1>(name, &["float"])?
+            .map_or(default, |[value]| value))
+    }
+
+    fn integer(&self, name: &str, default: usize) -> Result<usize> {
+        match self.integers(name)?.as_deref() {
+            Some(&[value]) => Ok(value as usize),
+            Some(_) => Err(self
+                .directive
+                .error(format!("{} should be a single integer", name))),
+            None => Ok(default),
+        }
+    }
+
+    fn color(&self, name: &str, default: Color3) -> Result<Color3> {
+        Ok(self
+            .fixed(name, &["rgb", "color"])?
+            .map_or(default, |[r, g, b]| Color3::new(r, g, b)))
+    }
+
+    fn point(&self, name: &str, default: Point3) -> Result<Point3> {
+        Ok(self
+            .fixed(name, &["point", "point3"])?
+            .map_or(default, |[x, y, z]| Point3::new(x, y, z)))
+    }
+
+    fn string(&self, name: &str) -> Result<Option<&str>> {
+        match self.find(name, &["string"])? {
+            Some(Parameter { values, .. }) => match values.as_slice() {
+                [Value::String(value)] => Ok(Some(value)),
+                _ => Err(self
+                    .directive
+                    .error(format!("{} should be a single string", name))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.list.iter().any(|parameter| parameter.name == name)
+    }
+}
+
+/// What `AttributeBegin` or `TransformBegin` saved, `TransformBegin` leaves the material alone
+struct Saved {
+    transform: Transform,
+    material: Option<Option<MaterialId>>,
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    directory: &'a Path,
+    /// From object to world space in the world block, before it from world to camera space like in pbrt
+    transform: Transform,
+    /// `None` until a material is set, then the shapes get pbrt's default grey matte
+    material: Option<MaterialId>,
+    stack: Vec<Saved>,
+    named_materials: HashMap<String, MaterialId>,
+    coordinate_systems: HashMap<String, Transform>,
+    /// The camera's type and parameters, and its camera to world transform
+    camera: Option<(String, Parameters, Transform)>,
+    /// In millimetres, only used by the realistic camera
+    film_diagonal: Float,
+    settings: RenderSettings,
+    world: HittableList,
+    materials: MaterialList,
+    lights: LightList,
+    /// The sum of the infinite lights
+    environment: Color3,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.position).map(|token| &token.kind)
+    }
+
+    fn number(&mut self, directive: &Token) -> Result<Float> {
+        match self.next() {
+            Some(Token {
+                kind: TokenKind::Number(number),
+                ..
+            }) => Ok(number as Float),
+            _ => Err(directive.error(format!("{} needs more numbers", word(directive)))),
+        }
+    }
+
+    fn numbers<const N: usize>(&mut self, directive: &Token) -> Result<[Float; N]> {
+        let mut numbers = [0.0; N];
+        for number in &mut numbers {
+            *number = self.number(directive)?;
+        }
+        Ok(numbers)
+    }
+
+    /// A matrix given column by column, in brackets or not
+    fn matrix(&mut self, directive: &Token) -> Result<Transform> {
+        let bracketed = self.peek() == Some(&TokenKind::Open);
+        if bracketed {
+            self.next();
+        }
+        let columns = self.numbers::<16>(directive)?;
+        if bracketed && self.next().map(|token| token.kind) != Some(TokenKind::Close) {
+            return Err(directive.error("the matrix needs 16 numbers"));
+        }
+
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            *row = [0, 1, 2, 3].map(|j| columns[j * 4 + i]);
+        }
+        Transform::from_matrix(rows).ok_or_else(|| directive.error("the matrix can't be inverted"))
+    }
+
+    fn string(&mut self, directive: &Token) -> Result<String> {
+        match self.next() {
+            Some(Token {
+                kind: TokenKind::String(string),
+                ..
+            }) => Ok(string),
+            _ => Err(directive.error(format!("{} needs a name in quotes", word(directive)))),
+        }
+    }
+
+    fn parameters(&mut self, directive: &Token) -> Result<Parameters> {
+        let mut list = vec![];
+        while let Some(TokenKind::String(declaration)) = self.peek() {
+            let (ty, name) = match declaration.split_whitespace().collect::<Vec<_>>()[..] {
+                [ty, name] => (ty.to_string(), name.to_string()),
+                _ => {
+                    return Err(directive.error(format!(
+                        "parameter \"{}\" should be a type and a name",
+                        declaration
+                    )))
+                }
+            };
+            self.next();
+
+            let value = |token: Option<Token>| match token.map(|token| token.kind) {
+                Some(TokenKind::Number(number)) => Ok(Value::Number(number)),
+                Some(TokenKind::String(string)) | Some(TokenKind::Word(string)) => {
+                    Ok(Value::String(string))
+                }
+                _ => Err(directive.error(format!("{} has no value", name))),
+            };
+            let values = match self.peek() {
+                Some(TokenKind::Open) => {
+                    self.next();
+                    let mut values = vec![];
+                    while self.peek() != Some(&TokenKind::Close) {
+                        values.push(value(self.next())?);
+                    }
+                    self.next();
+                    values
+                }
+                _ => vec![value(self.next())?],
+            };
+
+            list.push(Parameter { ty, name, values });
+        }
+
+        Ok(Parameters {
+            directive: directive.clone(),
+            list,
+        })
+    }
+
+    /// A type or name in quotes followed by parameters
+    fn named_parameters(&mut self, directive: &Token) -> Result<(String, Parameters)> {
+        let name = self.string(directive)?;
+        Ok((name, self.parameters(directive)?))
+    }
+
+    fn parse(&mut self) -> Result<()> {
+        while let Some(token) = self.next() {
+            let directive = match &token.kind {
+                TokenKind::Word(directive) => directive.clone(),
+                _ => return Err(token.error("expected a directive")),
+            };
+
+            match directive.as_str() {
+                "Identity" => self.transform = Transform::default(),
+                "Translate" => {
+                    let [x, y, z] = self.numbers(&token)?;
+                    self.transform = self.transform * Transform::translate(&Vector3::new(x, y, z));
+                }
+                "Scale" => {
+                    let [x, y, z] = self.numbers(&token)?;
+                    self.transform = self.transform * Transform::scale(&Vector3::new(x, y, z));
+                }
+                "Rotate" => {
+                    let [angle, x, y, z] = self.numbers(&token)?;
+                    let axis = Vector3::new(x, y, z);
+                    if axis.near_zero() {
+                        return Err(token.error("rotating about a zero axis"));
+                    }
+                    self.transform = self.transform * Transform::rotate(angle, &axis);
+                }
+                "LookAt" => {
+                    let [ex, ey, ez, lx, ly, lz, ux, uy, uz] = self.numbers(&token)?;
+                    let look_at = Transform::look_at(
+                        &Point3::new(ex, ey, ez),
+                        &Point3::new(lx, ly, lz),
+                        &Vector3::new(ux, uy, uz),
+                    )
+                    .map_err(|error| token.error(error.to_string()))?;
+                    self.transform = self.transform * look_at;
+                }
+                "Transform" => self.transform = self.matrix(&token)?,
+                "ConcatTransform" => self.transform = self.transform * self.matrix(&token)?,
+                "CoordinateSystem" => {
+                    let name = self.string(&token)?;
+                    self.coordinate_systems.insert(name, self.transform);
+                }
+                "CoordSysTransform" => {
+                    let name = self.string(&token)?;
+                    self.transform = *self.coordinate_systems.get(&name).ok_or_else(|| {
+                        token.error(format!("there's no coordinate system {}", name))
+                    })?;
+                }
+
+                "Camera" => {
+                    let (ty, parameters) = self.named_parameters(&token)?;
+                    let camera_to_world = self.transform.inverse();
+                    self.coordinate_systems
+                        .insert("camera".to_string(), camera_to_world);
+                    self.camera = Some((ty, parameters, to_right_handed() * camera_to_world));
+                }
+                "Film" => {
+                    let (_, parameters) = self.named_parameters(&token)?;
+                    self.settings.width = parameters.integer("xresolution", 640)?;
+                    self.settings.height = parameters.integer("yresolution", 480)?;
+                    self.film_diagonal = parameters.float("diagonal", 35.0)?;
+                }
+                "Sampler" => {
+                    let (_, parameters) = self.named_parameters(&token)?;
+                    self.settings.samples_per_pixel = parameters.integer("pixelsamples", 16)?;
+                }
+                "Integrator" => {
+                    let (_, parameters) = self.named_parameters(&token)?;
+                    self.settings.max_depth = parameters.integer("maxdepth", 5)?;
+                }
+                "PixelFilter" | "Accelerator" => {
+                    self.named_parameters(&token)?;
+                }
+                "ReverseOrientation" => {}
+
+                "WorldBegin" => {
+                    self.transform = Transform::default();
+                    self.coordinate_systems
+                        .insert("world".to_string(), self.transform);
+                }
+                "WorldEnd" => break,
+                "AttributeBegin" | "TransformBegin" => self.stack.push(Saved {
+                    transform: self.transform,
+                    material: (directive == "AttributeBegin").then_some(self.material),
+                }),
+                "AttributeEnd" | "TransformEnd" => {
+                    let saved = self
+                        .stack
+                        .pop()
+                        .ok_or_else(|| token.error(format!("{} without a begin", directive)))?;
+                    if (directive == "AttributeEnd") != saved.material.is_some() {
+                        return Err(token.error(format!("{} ends the wrong block", directive)));
+                    }
+                    self.transform = saved.transform;
+                    if let Some(material) = saved.material {
+                        self.material = material;
+                    }
+                }
+
+                "Material" => {
+                    let (ty, parameters) = self.named_parameters(&token)?;
+                    self.material = Some(self.add_material(&ty, &parameters)?);
+                }
+                "MakeNamedMaterial" => {
+                    let (name, parameters) = self.named_parameters(&token)?;
+                    let ty = parameters
+                        .string("type")?
+                        .ok_or_else(|| token.error(format!("material {} has no type", name)))?
+                        .to_string();
+                    let material = self.add_material(&ty, &parameters)?;
+                    self.named_materials.insert(name, material);
+                }
+                "NamedMaterial" => {
+                    let name = self.string(&token)?;
+                    let material = self.named_materials.get(&name).ok_or_else(|| {
+                        token.error(format!("there's no material named {}", name))
+                    })?;
+                    self.material = Some(*material);
+                }
+                "Shape" => {
+                    let (ty, parameters) = self.named_parameters(&token)?;
+                    self.add_shape(&ty, &parameters)?;
+                }
+                "LightSource" => {
+                    let (ty, parameters) = self.named_parameters(&token)?;
+                    self.add_light(&ty, &parameters)?;
+                }
+
+                _ => {
+                    return Err(token.error(format!(
+                        "{} isn't supported or isn't a directive",
+                        directive
+                    )))
+                }
+            }
+        }
+
+        if !self.stack.is_empty() {
+            return Err(Error::parse(
+                &*self.tokens[0].file,
+                "a block is never ended",
+            ));
+        }
+        Ok(())
+    }
+
+    fn add_material(&mut self, ty: &str, parameters: &Parameters) -> Result<MaterialId> {
+        let reflectance_model = match ty {
+            "matte" => ReflectanceModel::Diffuse(Diffuse {
+                albedo: parameters.color("Kd", Color3::new(0.5, 0.5, 0.5))?,
+            }),
+            "plastic" => ReflectanceModel::Diffuse(Diffuse {
+                albedo: parameters.color("Kd", Color3::new(0.25, 0.25, 0.25))?,
+            }),
+            // Copper by default, with the reflectance at normal incidence as the colour
+            "metal" => {
+                let eta = parameters.color("eta", Color3::new(0.2004, 0.924, 1.1022))?;
+                let k = parameters.color("k", Color3::new(3.9129, 2.4528, 2.1421))?;
+                let reflectance = |i: usize| {
+                    ((eta[i] - 1.0).powi(2) + k[i] * k[i]) / ((eta[i] + 1.0).powi(2) + k[i] * k[i])
+                };
+                ReflectanceModel::Metal(Metal {
+                    albedo: Color3::new(reflectance(0), reflectance(1), reflectance(2)),
+                    fuzziness: parameters.float("roughness", 0.01)?,
+                })
+            }
+            "mirror" => ReflectanceModel::Metal(Metal {
+                albedo: parameters.color("Kr", Color3::new(0.9, 0.9, 0.9))?,
+                fuzziness: 0.0,
+            }),
+            "glass" => ReflectanceModel::Dielectric(Dielectric {
+                index_of_refraction: match parameters.has("eta") {
+                    true => parameters.float("eta", 1.5)?,
+                    false => parameters.float("index", 1.5)?,
+                },
+            }),
+            _ => {
+                return Err(parameters
+                    .directive
+                    .error(format!("material \"{}\" isn't supported", ty)))
+            }
+        };
+        Ok(self.materials.add(Material::from(reflectance_model)))
+    }
+
+    fn add_shape(&mut self, ty: &str, parameters: &Parameters) -> Result<()> {
+        let material = match self.material {
+            Some(material) => material,
+            None => {
+                let material = self.add_material(
+                    "matte",
+                    &Parameters {
+                        directive: parameters.directive.clone(),
+                        list: vec![],
+                    },
+                )?;
+                self.material = Some(material);
+                material
+            }
+        };
+
+        let object = match ty {
+            "sphere" => {
+                if ["zmin", "zmax", "phimax"]
+                    .iter()
+                    .any(|&name| parameters.has(name))
+                {
+                    return Err(parameters
+                        .directive
+                        .error("only whole spheres are supported"));
+                }
+                Hittable::Sphere(Sphere {
+                    position: Point3::new(0.0, 0.0, 0.0),
+                    radius: parameters.float("radius", 1.0)?,
+                    material,
+                })
+            }
+            "trianglemesh" | "plymesh" => {
+                let mesh = match ty {
+                    "trianglemesh" => triangle_mesh(parameters)?,
+                    _ => {
+                        let file = parameters.string("filename")?.ok_or_else(|| {
+                            parameters.directive.error("plymesh needs a filename")
+                        })?;
+                        read_ply(&self.directory.join(file))?
+                    }
+                };
+                Hittable::Mesh(
+                    Mesh::new(mesh, material)
+                        .map_err(|error| parameters.directive.error(error.to_string()))?,
+                )
+            }
+            _ => {
+                return Err(parameters
+                    .directive
+                    .error(format!("shape \"{}\" isn't supported", ty)))
+            }
+        };
+
+        let instance = Instance {
+            object: Box::new(object),
+            transform: to_right_handed() * self.transform,
+        };
+        instance
+            .validate(&self.materials)
+            .map_err(|error| parameters.directive.error(error.to_string()))?;
+        self.world.add(Hittable::Instance(instance));
+        Ok(())
+    }
+
+    fn add_light(&mut self, ty: &str, parameters: &Parameters) -> Result<()> {
+        let to_world = to_right_handed() * self.transform;
+        let scale = parameters.color("scale", Color3::new(1.0, 1.0, 1.0))?;
+        let white = Color3::new(1.0, 1.0, 1.0);
+        let from = parameters.point("from", Point3::new(0.0, 0.0, 0.0))?;
+        let to = parameters.point("to", Point3::new(0.0, 0.0, 1.0))?;
+
+        let light = match ty {
+            "point" => Light::Point(PointLight {
+                position: to_world.apply(&from, 1.0),
+                intensity: parameters.color("I", white)? * scale,
+            }),
+            "spot" => {
+                let cone_angle = parameters.float("coneangle", 30.0)?;
+                let cone_delta = parameters.float("conedelta", 5.0)?;
+                Light::Spot(SpotLight::new(
+                    to_world.apply(&from, 1.0),
+                    to_world.apply(&to, 1.0),
+                    parameters.color("I", white)? * scale,
+                    cone_angle,
+                    Float::max(cone_angle - cone_delta, 0.0),
+                ))
+            }
+            "distant" => Light::Directional(DirectionalLight::new(
+                to_world.apply(&(to - from), 0.0),
+                parameters.color("L", white)? * scale,
+            )),
+            "infinite" => {
+                if parameters.has("mapname") {
+                    return Err(parameters
+                        .directive
+                        .error("infinite lights with a map aren't supported"));
+                }
+                self.environment += parameters.color("L", white)? * scale;
+                return Ok(());
+            }
+            _ => {
+                return Err(parameters
+                    .directive
+                    .error(format!("light \"{}\" isn't supported", ty)))
+            }
+        };
+        self.lights.add(light);
+        Ok(())
+    }
+
+    /// pbrt's default camera is a perspective one looking down +z from the origin
+    fn camera(&self) -> Result<Box<dyn Camera>> {
+        let (ty, parameters, camera_to_world) = match &self.camera {
+            Some((ty, parameters, camera_to_world)) => (ty.as_str(), parameters, *camera_to_world),
+            None => {
+                return Ok(Box::new(PerspectiveCamera::new(
+                    Point3::new(0.0, 0.0, 0.0),
+                    Point3::new(0.0, 0.0, 1.0),
+                    Vector3::new(0.0, 1.0, 0.0),
+                    90.0,
+                    self.settings.aspect_ratio(),
+                    0.0,
+                    1e6,
+                )?))
+            }
+        };
+        // Camera space looks down +z with +y up
+        let look_from = camera_to_world.apply(&Point3::new(0.0, 0.0, 0.0), 1.0);
+        let look_at = camera_to_world.apply(&Point3::new(0.0, 0.0, 1.0), 1.0);
+        let view_up = camera_to_world.apply(&Vector3::new(0.0, 1.0, 0.0), 0.0);
+        let aspect_ratio = self.settings.aspect_ratio();
+
+        let camera: Box<dyn Camera> = match ty {
+            // pbrt's field of view is across the shorter side of the image
+            "perspective" => {
+                let fov = parameters.float("fov", 90.0)?;
+                let fov = match aspect_ratio < 1.0 {
+                    true => {
+                        2.0 * Float::atan((fov / 2.0).to_radians().tan() / aspect_ratio)
+                            .to_degrees()
+                    }
+                    false => fov,
+                };
+                Box::new(PerspectiveCamera::new(
+                    look_from,
+                    look_at,
+                    view_up,
+                    fov,
+                    aspect_ratio,
+                    2.0 * parameters.float("lensradius", 0.0)?,
+                    parameters.float("focaldistance", 1e6)?,
+                )?)
+            }
+            // The screen window spans 2 across the shorter side of the image unless it's given
+            "orthographic" => {
+                let height = match parameters.fixed::<4>("screenwindow", &["float"])? {
+                    Some([_, _, y0, y1]) => y1 - y0,
+                    None if aspect_ratio < 1.0 => 2.0 / aspect_ratio,
+                    None => 2.0,
+                };
+                Box::new(OrthographicCamera::new(
+                    look_from,
+                    look_at,
+                    view_up,
+                    height * view_up.length(),
+                    aspect_ratio,
+                )?)
+            }
+            // The lens file is looked for next to the scene
+            "realistic" => {
+                let file = parameters.string("lensfile")?.ok_or_else(|| {
+                    parameters
+                        .directive
+                        .error("realistic camera needs a lensfile")
+                })?;
+                Box::new(RealisticCamera::new(
+                    look_from,
+                    look_at,
+                    view_up,
+                    read_lens_file(&self.directory.join(file))?,
+                    parameters.float("aperturediameter", 1.0)?,
+                    parameters.float("focusdistance", 10.0)?,
+                    self.film_diagonal,
+                    aspect_ratio,
+                )?)
+            }
+            _ => {
+                return Err(parameters
+                    .directive
+                    .error(format!("camera \"{}\" isn't supported", ty)))
+            }
+        };
+        Ok(camera)
+    }
+}
+
+fn word(token: &Token) -> String {
+    match &token.kind {
+        TokenKind::Word(word) => word.clone(),
+        kind => format!("{:?}", kind),
+    }
+}
+
+/// Needs the positions `P` and, unless there's a single triangle, the `indices`.
+/// Normals `N` and uvs `uv` or `st` are optional
+fn triangle_mesh(parameters: &Parameters) -> Result<TriangleMesh> {
+    let error = |message: &str| parameters.directive.error(message);
+    let triples = |numbers: Vec<Float>, name: &str| {
+        if !numbers.len().is_multiple_of(3) {
+            return Err(error(&format!("{} needs a multiple of 3 numbers", name)));
+        }
+        Ok(numbers
+            .chunks_exact(3)
+            .map(|v| Vector3::new(v[0], v[1], v[2]))
+            .collect::<Vec<_>>())
+    };
+
+    let positions = match parameters.numbers("P", &["point3", "point"])? {
+        Some(positions) => triples(positions, "P")?,
+        None => return Err(error("trianglemesh needs positions P")),
+    };
+    let indices = match parameters.integers("indices")? {
+        Some(indices) => indices,
+        None if positions.len() == 3 => vec![0, 1, 2],
+        None => return Err(error("trianglemesh needs indices")),
+    };
+    if !indices.len().is_multiple_of(3) {
+        return Err(error("trianglemesh needs 3 indices per triangle"));
+    }
+
+    let normals = match parameters.numbers("N", &["normal", "normal3"])? {
+        Some(normals) => Some(triples(normals, "N")?),
+        None => None,
+    };
+    let uvs = match parameters.numbers("uv", &["float", "point2"])? {
+        Some(uvs) => Some(uvs),
+        None => parameters.numbers("st", &["float", "point2"])?,
+    };
+    let uvs = match uvs {
+        Some(uvs) if !uvs.len().is_multiple_of(2) => {
+            return Err(error("uv needs 2 numbers per vertex"))
+        }
+        Some(uvs) => Some(uvs.chunks_exact(2).map(|uv| [uv[0], uv[1]]).collect()),
+        None => None,
+    };
+
+    Ok(TriangleMesh {
+        positions,
+        normals,
+        uvs,
+        colors: None,
+        indices: indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect(),
+    })
+}
@@ -0,0 +1,458 @@
+//! Reads triangle meshes from PLY files, in ASCII or either binary byte order.
+//! Positions come from the `vertex` element's `x`, `y` and `z`, with normals, uvs and colours when it has them,
+//! and polygons from the `face` element's `vertex_indices`, split into fans of triangles.
+//! Everything else is skipped
+
+use super::hittable::TriangleMesh;
+use super::vector::*;
+use super::{Error, Float, Result};
+use std::convert::TryFrom;
+use std::path::Path;
+
+pub fn read_ply(path: &Path) -> Result<TriangleMesh> {
+    let bytes = std::fs::read(path)?;
+    parse_ply(&path.display().to_string(), &bytes)
+}
+
+/// `file` only names the file in errors
+pub fn parse_ply(file: &str, bytes: &[u8]) -> Result<TriangleMesh> {
+    let (format, elements, body_start) = parse_header(file, bytes)?;
+    let mut body = Body {
+        file,
+        format,
+        bytes,
+        position: body_start,
+    };
+
+    let mut mesh = TriangleMesh::default();
+    let mut vertices = vec![];
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => vertices = read_vertices(&mut body, element)?,
+            "face" => mesh.indices = read_faces(&mut body, element)?,
+            _ => {
+                for _ in 0..element.count {
+                    for property in &element.properties {
+                        body.read_property(property)?;
+                    }
+                }
+            }
+        }
+    }
+
+    // Attributes are only kept when every component of them is there
+    let has = |attributes: &[Attribute]| {
+        attributes.iter().all(|attribute| {
+            elements
+                .iter()
+                .filter(|element| element.name == "vertex")
+                .flat_map(|element| &element.properties)
+                .any(|property| Attribute::from_name(&property.name) == Some(*attribute))
+        })
+    };
+    if !has(&[Attribute::X, Attribute::Y, Attribute::Z]) {
+        return Err(Error::parse(file, "vertices need x, y and z"));
+    }
+    let vector = |values: &[f64; ATTRIBUTES], first: Attribute| {
+        let i = first as usize;
+        Vector3::new(
+            values[i] as Float,
+            values[i + 1] as Float,
+            values[i + 2] as Float,
+        )
+    };
+
+    mesh.positions = vertices
+        .iter()
+        .map(|values| vector(values, Attribute::X))
+        .collect();
+    if has(&[Attribute::Nx, Attribute::Ny, Attribute::Nz]) {
+        mesh.normals = Some(
+            vertices
+                .iter()
+                .map(|values| vector(values, Attribute::Nx))
+                .collect(),
+        );
+    }
+    if has(&[Attribute::U, Attribute::V]) {
+        mesh.uvs = Some(
+            vertices
+                .iter()
+                .map(|values| {
+                    [
+                        values[Attribute::U as usize] as Float,
+                        values[Attribute::V as usize] as Float,
+                    ]
+                })
+                .collect(),
+        );
+    }
+    if has(&[Attribute::Red, Attribute::Green, Attribute::Blue]) {
+        mesh.colors = Some(
+            vertices
+                .iter()
+                .map(|values| vector(values, Attribute::Red))
+                .collect(),
+        );
+    }
+
+    mesh.validate()
+        .map_err(|error| Error::parse(file, error.to_string()))?;
+    Ok(mesh)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ScalarType {
+    Int8,
+    Uint8,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    fn from_name(name: &str) -> Option<ScalarType> {
+        Some(match name {
+            "char" | "int8" => ScalarType::Int8,
+            "uchar" | "uint8" => ScalarType::Uint8,
+            "short" | "int16" => ScalarType::Int16,
+            "ushort" | "uint16" => ScalarType::Uint16,
+            "int" | "int32" => ScalarType::Int32,
+            "uint" | "uint32" => ScalarType::Uint32,
+            "float" | "float32" => ScalarType::Float32,
+            "double" | "float64" => ScalarType::Float64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::Int8 | ScalarType::Uint8 => 1,
+            ScalarType::Int16 | ScalarType::Uint16 => 2,
+            ScalarType::Int32 | ScalarType::Uint32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+
+    /// Integer colours go up to the largest value of their type, floating point ones up to 1
+    fn color_scale(self) -> f64 {
+        match self {
+            ScalarType::Int8 => i8::MAX as f64,
+            ScalarType::Uint8 => u8::MAX as f64,
+            ScalarType::Int16 => i16::MAX as f64,
+            ScalarType::Uint16 => u16::MAX as f64,
+            ScalarType::Int32 => i32::MAX as f64,
+            ScalarType::Uint32 => u32::MAX as f64,
+            ScalarType::Float32 | ScalarType::Float64 => 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum PropertyType {
+    Scalar(ScalarType),
+    List { count: ScalarType, item: ScalarType },
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    kind: PropertyType,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// The vertex properties that are kept, in the order they're stored in while reading
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Attribute {
+    X,
+    Y,
+    Z,
+    Nx,
+    Ny,
+    Nz,
+    U,
+    V,
+    Red,
+    Green,
+    Blue,
+}
+
+const ATTRIBUTES: usize = Attribute::Blue as usize + 1;
+
+impl Attribute {
+    fn from_name(name: &str) -> Option<Attribute> {
+        Some(match name {
+            "x" => Attribute::X,
+            "y" => Attribute::Y,
+            "z" => Attribute::Z,
+            "nx" => Attribute::Nx,
+            "ny" => Attribute::Ny,
+            "nz" => Attribute::Nz,
+            "u" | "s" | "texture_u" | "texture_s" => Attribute::U,
+            "v" | "t" | "texture_v" | "texture_t" => Attribute::V,
+            "red" => Attribute::Red,
+            "green" => Attribute::Green,
+            "blue" => Attribute::Blue,
+            _ => return None,
+        })
+    }
+}
+
+/// The format, the elements and where the data after the header starts
+fn parse_header(file: &str, bytes: &[u8]) -> Result<(Format, Vec<Element>, usize)> {
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    let mut position = 0;
+
+    for line_number in 1.. {
+        let error =
+            |message: String| Error::parse(file, format!("line {}: {}", line_number, message));
+
+        let length = bytes[position..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .ok_or_else(|| error("the header has no end_header".to_string()))?;
+        let line = std::str::from_utf8(&bytes[position..position + length])
+            .map_err(|_| error("the header isn't text".to_string()))?;
+        position += length + 1;
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if line_number == 1 {
+            if words != ["ply"] {
+                return Err(error("isn't a PLY file".to_string()));
+            }
+            continue;
+        }
+
+        match words.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => {}
+            ["format", name, "1.0"] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(error(format!("unknown format {}", name))),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| error(format!("{} isn't a count", count)))?,
+                properties: vec![],
+            }),
+            ["property", ..] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| error("property before any element".to_string()))?;
+                let scalar = |name: &str| {
+                    ScalarType::from_name(name)
+                        .ok_or_else(|| error(format!("unknown type {}", name)))
+                };
+                let (kind, name) = match words.as_slice() {
+                    ["property", "list", count, item, name] => (
+                        PropertyType::List {
+                            count: scalar(count)?,
+                            item: scalar(item)?,
+                        },
+                        name,
+                    ),
+                    ["property", kind, name] => (PropertyType::Scalar(scalar(kind)?), name),
+                    _ => return Err(error(format!("malformed property {}", line.trim()))),
+                };
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind,
+                });
+            }
+            ["end_header"] => break,
+            _ => return Err(error(format!("unexpected {}", line.trim()))),
+        }
+    }
+
+    let format = format.ok_or_else(|| Error::parse(file, "the header has no format"))?;
+    Ok((format, elements, position))
+}
+
+/// The data after the header, read one value at a time
+struct Body<'a> {
+    file: &'a str,
+    format: Format,
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, kind: ScalarType) -> Result<f64> {
+        let bytes = self.bytes;
+        let file = self.file;
+        let end_early = || Error::parse(file, "the file ends before all the elements");
+
+        if self.format == Format::Ascii {
+            while bytes
+                .get(self.position)
+                .is_some_and(u8::is_ascii_whitespace)
+            {
+                self.position += 1;
+            }
+            let start = self.position;
+            while bytes
+                .get(self.position)
+                .is_some_and(|byte| !byte.is_ascii_whitespace())
+            {
+                self.position += 1;
+            }
+            if start == self.position {
+                return Err(end_early());
+            }
+
+            let word = String::from_utf8_lossy(&bytes[start..self.position]);
+            return word
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| Error::parse(self.file, format!("{} isn't a number", word)));
+        }
+
+        let size = kind.size();
+        let value = bytes
+            .get(self.position..self.position + size)
+            .ok_or_else(end_early)?;
+        self.position += size;
+
+        // Little endian from here on
+        let mut raw = [0; 8];
+        raw[..size].copy_from_slice(value);
+        if self.format == Format::BinaryBigEndian {
+            raw[..size].reverse();
+        }
+        let [b0, b1, b2, b3, ..] = raw;
+        Ok(match kind {
+            ScalarType::Int8 => b0 as i8 as f64,
+            ScalarType::Uint8 => b0 as f64,
+            ScalarType::Int16 => i16::from_le_bytes([b0, b1]) as f64,
+            ScalarType::Uint16 => u16::from_le_bytes([b0, b1]) as f64,
+            ScalarType::Int32 => i32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            ScalarType::Uint32 => u32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            ScalarType::Float32 => f32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            ScalarType::Float64 => f64::from_le_bytes(raw),
+        })
+    }
+
+    /// How many elements of `element` there's room for in what's left of the file, as every one takes at
+    /// least a byte. The header's count alone can't be trusted to allocate for
+    fn capacity_for(&self, element: &Element) -> usize {
+        element
+            .count
+            .min(self.bytes.len().saturating_sub(self.position))
+    }
+
+    fn read_count(&mut self, kind: ScalarType) -> Result<usize> {
+        let count = self.read(kind)?;
+        if count < 0.0 || count.fract() != 0.0 {
+            return Err(Error::parse(
+                self.file,
+                format!("{} isn't a count or an index", count),
+            ));
+        }
+        Ok(count as usize)
+    }
+
+    /// Reads a property that isn't needed, only to get past it
+    fn read_property(&mut self, property: &Property) -> Result<()> {
+        match property.kind {
+            PropertyType::Scalar(kind) => {
+                self.read(kind)?;
+            }
+            PropertyType::List { count, item } => {
+                for _ in 0..self.read_count(count)? {
+                    self.read(item)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_vertices(body: &mut Body<'_>, element: &Element) -> Result<Vec<[f64; ATTRIBUTES]>> {
+    let mut vertices = Vec::with_capacity(body.capacity_for(element));
+    for _ in 0..element.count {
+        let mut values = [0.0; ATTRIBUTES];
+        for property in &element.properties {
+            match (Attribute::from_name(&property.name), property.kind) {
+                (Some(attribute), PropertyType::Scalar(kind)) => {
+                    let mut value = body.read(kind)?;
+                    if matches!(
+                        attribute,
+                        Attribute::Red | Attribute::Green | Attribute::Blue
+                    ) {
+                        value /= kind.color_scale();
+                    }
+                    values[attribute as usize] = value;
+                }
+                _ => body.read_property(property)?,
+            }
+        }
+        vertices.push(values);
+    }
+    Ok(vertices)
+}
+
+fn read_faces(body: &mut Body<'_>, element: &Element) -> Result<Vec<[u32; 3]>> {
+    if !element.properties.iter().any(is_vertex_indices) {
+        return Err(Error::parse(body.file, "faces need vertex_indices"));
+    }
+
+    let mut triangles = Vec::with_capacity(body.capacity_for(element));
+    for face in 0..element.count {
+        for property in &element.properties {
+            match property.kind {
+                PropertyType::List { count, item } if is_vertex_indices(property) => {
+                    let count = body.read_count(count)?;
+                    let polygon = (0..count)
+                        .map(|_| {
+                            let index = body.read_count(item)?;
+                            u32::try_from(index).map_err(|_| {
+                                Error::parse(body.file, format!("index {} is too large", index))
+                            })
+                        })
+                        .collect::<Result<Vec<u32>>>()?;
+                    if polygon.len() < 3 {
+                        return Err(Error::parse(
+                            body.file,
+                            format!("face {} has only {} vertices", face, polygon.len()),
+                        ));
+                    }
+
+                    // A fan around the first vertex, which is only right for convex polygons
+                    for i in 1..polygon.len() - 1 {
+                        triangles.push([polygon[0], polygon[i], polygon[i + 1]]);
+                    }
+                }
+                _ => body.read_property(property)?,
+            }
+        }
+    }
+    Ok(triangles)
+}
+
+fn is_vertex_indices(property: &Property) -> bool {
+    matches!(property.kind, PropertyType::List { .. })
+        && (property.name == "vertex_indices" || property.name == "vertex_index")
+}
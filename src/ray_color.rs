@@ -1,18 +1,91 @@
 use super::*;
 
-pub fn ray_color(ray: &Ray, world: &HittableList, rng: &mut SmallRng, depth: usize) -> Color3 {
+/// One bounce of a path, only recorded when debugging
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PathVertex {
+    pub p: Point3,
+    pub normal: Vector3,
+    pub material_id: u32,
+    pub object_id: u32,
+    pub direction_in: Vector3,
+    pub direction_out: Vector3,
+    pub reflectance: Color3,
+    pub pdf: Float,
+    pub is_specular: bool,
+    /// Light added by next event estimation at this vertex, already multiplied by the throughput
+    pub light: Color3,
+    /// Throughput after scattering
+    pub beta: Color3,
+}
+
+/// Also records the AOVs of the path into `aov`.
+/// Light that reaches the camera after at most one bounce is counted as direct and everything else as indirect.
+/// If `path` is given every bounce is pushed onto it.
+pub fn ray_color(
+    ray: &Ray,
+    scene: &Scene,
+    rng: &mut SmallRng,
+    depth: usize,
+    aov: &mut AovSample,
+    mut path: Option<&mut Vec<PathVertex>>,
+) -> Color3 {
     let mut beta = Color3::new(1.0, 1.0, 1.0);
     let mut ray = *ray;
+    let mut found_non_specular = false;
+    let mut path_length = 0;
 
     ray.direction = Vector3::unit_vector(ray.direction);
 
+    *aov = AovSample {
+        depth: Float::INFINITY,
+        ..Default::default()
+    };
+
     for bounces in 0..depth {
+        stats::record(|stats| {
+            if bounces == 0 {
+                stats.camera_rays += 1;
+            } else {
+                stats.indirect_rays += 1;
+            }
+        });
+
         let mut interaction = Interaction::default();
-        if world.hit(&ray, 0.001, Float::INFINITY, &mut interaction) {
+        if scene
+            .world
+            .hit(&ray, 0.001, Float::INFINITY, &mut interaction)
+        {
+            let material_id = interaction.material.unwrap();
+            let material = &scene.materials[material_id];
+            path_length += 1;
+
+            if bounces == 0 {
+                aov.depth = interaction.t;
+                aov.position = interaction.p;
+                aov.object_id = interaction.object_id;
+                aov.material_id = material_id.aov_id();
+            }
+
+            if !found_non_specular && !material.is_specular() {
+                found_non_specular = true;
+                aov.albedo = material.albedo(&interaction);
+                aov.normal = interaction.normal;
+            }
+
+            let mut direct = Color3::default();
+            if !material.is_specular() && !scene.lights.is_empty() {
+                direct = beta * sample_one_light(&ray, &interaction, scene, rng);
+                if bounces == 0 {
+                    aov.direct += direct;
+                } else {
+                    aov.indirect += direct;
+                }
+            }
+
             let mut next_ray = Ray::default();
             let mut pdf = 0.0;
             let mut is_specular = false;
-            let reflectance = interaction.material.unwrap().scatter(
+            let reflectance = material.scatter(
                 &ray,
                 &mut next_ray,
                 &mut pdf,
@@ -25,23 +98,107 @@ pub fn ray_color(ray: &Ray, world: &HittableList, rng: &mut SmallRng, depth: usi
                 * Float::abs(Vector3::dot(&interaction.normal, &next_ray.direction))
                 / pdf;
 
+            if aov.invalid_material_id == 0 && !(beta.is_finite() && direct.is_finite()) {
+                aov.invalid_material_id = material_id.aov_id();
+            }
+
+            if let Some(path) = path.as_mut() {
+                path.push(PathVertex {
+                    p: interaction.p,
+                    normal: interaction.normal,
+                    material_id: material_id.aov_id(),
+                    object_id: interaction.object_id,
+                    direction_in: ray.direction,
+                    direction_out: next_ray.direction,
+                    reflectance,
+                    pdf,
+                    is_specular,
+                    light: direct,
+                    beta,
+                });
+            }
+
             ray = next_ray;
         } else {
-            let unit_direction = Vector3::unit_vector(ray.direction);
-            let t = 0.5 * (unit_direction.y + 1.0);
-            let environment =
-                (1.0 - t) * Color3::new(1.0, 1.0, 1.0) + t * Color3::new(0.5, 0.7, 1.0);
+            let environment = scene.environment.radiance(&ray.direction);
 
-            return environment * beta;
+            if !found_non_specular {
+                aov.albedo = environment;
+            }
+
+            if bounces <= 1 {
+                aov.direct += environment * beta;
+            } else {
+                aov.indirect += environment * beta;
+            }
+
+            break;
         }
         if bounces > 3 {
             let q = Float::max(0.05, 1.0 - beta.luminance());
             if rng.gen::<f32>() < q {
+                stats::record(|stats| stats.russian_roulette_terminations += 1);
                 break;
             }
             beta /= 1.0 - q;
         }
     }
 
-    Color3::new(0.0, 0.0, 0.0)
+    stats::record(|stats| stats.record_path_length(path_length));
+
+    aov.direct + aov.indirect
+}
+
+/// Next event estimation with a single light chosen by the `LightList`.
+/// Delta lights can never be hit by `scatter` and area lights aren't part of the world,
+/// so lights are only ever reached this way and there is no need for MIS.
+fn sample_one_light(
+    ray: &Ray,
+    interaction: &Interaction,
+    scene: &Scene,
+    rng: &mut SmallRng,
+) -> Color3 {
+    let mut pmf = 0.0;
+    let light = match scene
+        .lights
+        .sample(&interaction.p, &interaction.normal, rng.gen(), &mut pmf)
+    {
+        Some(light) => light,
+        None => return Color3::new(0.0, 0.0, 0.0),
+    };
+
+    let mut wi = Vector3::default();
+    let mut distance = 0.0;
+    let li = light.sample_li(&interaction.p, rng.gen(), &mut wi, &mut distance);
+
+    if li.near_zero() {
+        return Color3::new(0.0, 0.0, 0.0);
+    }
+
+    // The light has to be on the same side of the surface as the viewer
+    let cos_theta = Vector3::dot(&interaction.normal, &wi);
+    if cos_theta * Vector3::dot(&interaction.normal, &ray.direction) >= 0.0 {
+        return Color3::new(0.0, 0.0, 0.0);
+    }
+
+    let shadow_ray = Ray {
+        origin: interaction.p,
+        direction: wi,
+    };
+
+    stats::record(|stats| stats.shadow_rays += 1);
+    let mut shadow_interaction = Interaction::default();
+    if scene.world.hit(
+        &shadow_ray,
+        0.001,
+        distance * (1.0 - 1e-4),
+        &mut shadow_interaction,
+    ) {
+        return Color3::new(0.0, 0.0, 0.0);
+    }
+
+    let material = &scene.materials[interaction.material.unwrap()];
+    let reflectance = material.reflectance(ray, &shadow_ray, interaction);
+
+    reflectance * li * Float::abs(cos_theta) / pmf
 }
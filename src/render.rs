@@ -0,0 +1,407 @@
+use super::*;
+use std::time::Instant;
+
+mod tile_order;
+pub use tile_order::TileOrder;
+
+#[derive(Clone, Debug)]
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: usize,
+    pub max_depth: usize,
+    pub num_threads: usize,
+    pub tile_width: usize,
+    pub tile_height: usize,
+    pub tile_order: TileOrder,
+    /// Clamp the radiance of every sample to this, trading bias for fewer fireflies
+    pub max_sample_value: Option<Float>,
+    /// Every sample's random numbers are derived from this so renders are reproducible
+    pub seed: u64,
+    /// Count rays, BVH traversals and path lengths into `Film::stats`, the timings are always measured
+    pub collect_stats: bool,
+    /// Only render this window, the rest of the film is left black
+    pub crop: Option<Crop>,
+}
+
+/// Window of the film in film coordinates, so (0, 0) is the bottom left. `x1` and `y1` are exclusive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Crop {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl Crop {
+    pub fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> usize {
+        self.y1 - self.y0
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings {
+            width: 1920,
+            height: 1080,
+            samples_per_pixel: 1024,
+            max_depth: 16,
+            num_threads: 2,
+            tile_width: 16,
+            tile_height: 16,
+            tile_order: TileOrder::default(),
+            max_sample_value: None,
+            seed: 0,
+            collect_stats: false,
+            crop: None,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn aspect_ratio(&self) -> Float {
+        self.width as Float / self.height as Float
+    }
+
+    /// The part of the film that is rendered
+    pub fn window(&self) -> Crop {
+        self.crop.unwrap_or(Crop {
+            x0: 0,
+            y0: 0,
+            x1: self.width,
+            y1: self.height,
+        })
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.width < 2 || self.height < 2 {
+            return Err(Error::invalid_parameter(format!(
+                "film must be at least 2x2 pixels, got {}x{}",
+                self.width, self.height
+            )));
+        }
+        if let Some(crop) = self.crop {
+            if crop.x0 >= crop.x1
+                || crop.y0 >= crop.y1
+                || crop.x1 > self.width
+                || crop.y1 > self.height
+            {
+                return Err(Error::invalid_parameter(format!(
+                    "crop {:?} must be a non-empty window inside the {}x{} film",
+                    crop, self.width, self.height
+                )));
+            }
+        }
+        if self.samples_per_pixel == 0 {
+            return Err(Error::invalid_parameter(
+                "samples per pixel must be at least 1",
+            ));
+        }
+        if self.num_threads == 0 {
+            return Err(Error::invalid_parameter(
+                "number of threads must be at least 1",
+            ));
+        }
+        if self.tile_width == 0 || self.tile_height == 0 {
+            return Err(Error::invalid_parameter(format!(
+                "tiles must be at least 1x1 pixels, got {}x{}",
+                self.tile_width, self.tile_height
+            )));
+        }
+        if let Some(max) = self.max_sample_value {
+            if max.is_nan() || max <= 0.0 {
+                return Err(Error::invalid_parameter(format!(
+                    "max sample value must be positive, got {}",
+                    max
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn render(scene: &Scene, settings: &RenderSettings) -> Result<Film> {
+    render_with_progress(scene, settings, &CancellationToken::default(), |_, _| {})
+}
+
+/// `progress` is called on the calling thread with the number of finished pixels and the total number of pixels
+/// every time a tile finishes.
+/// Once `cancel` is cancelled the threads stop after their current row of pixels
+/// and the film is returned with the pixels that were finished, the rest are left black.
+pub fn render_with_progress<F>(
+    scene: &Scene,
+    settings: &RenderSettings,
+    cancel: &CancellationToken,
+    mut progress: F,
+) -> Result<Film>
+where
+    F: FnMut(usize, usize),
+{
+    settings.validate()?;
+
+    let div_up = |a, b| {
+        if a % b == 0 {
+            a / b
+        } else {
+            a / b + 1
+        }
+    };
+
+    let window = settings.window();
+
+    let tiles = settings
+        .tile_order
+        .tiles(
+            div_up(window.width(), settings.tile_width),
+            div_up(window.height(), settings.tile_height),
+        )
+        .into_iter()
+        .map(|(i, j)| {
+            let x = window.x0 + i * settings.tile_width;
+            let y = window.y0 + j * settings.tile_height;
+            Tile {
+                x,
+                y,
+                width: usize::min(settings.tile_width, window.x1 - x),
+                height: usize::min(settings.tile_height, window.y1 - y),
+            }
+        })
+        .collect();
+
+    let thread_pool = ThreadPool::new(settings.num_threads, tiles, cancel.clone());
+
+    let earlier = Instant::now();
+
+    let mut film = Film::new(settings.width, settings.height);
+    let total_pixels = window.width() * window.height();
+    let mut finished_pixels = 0;
+
+    std::thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+
+        scope.spawn(move || {
+            thread_pool.execute(|tile| {
+                // The receiver only goes away once the pool is done
+                let _ = tx.send(render_tile(scene, settings, cancel, tile));
+            });
+        });
+
+        // Ends once the pool is done and `tx` is dropped
+        for tile in rx {
+            for j in 0..tile.rows {
+                for i in 0..tile.tile.width {
+                    let pixel = tile.pixels[j * tile.tile.width + i];
+                    film.set(tile.tile.x + i, tile.tile.y + j, pixel);
+                }
+            }
+
+            film.diagnostics.merge(tile.diagnostics);
+            film.stats.merge(&tile.stats);
+
+            finished_pixels += tile.rows * tile.tile.width;
+            progress(finished_pixels, total_pixels);
+        }
+    });
+
+    film.stats.render_time = earlier.elapsed();
+    film.stats.bvh_build_time = scene.build_time;
+
+    Ok(film)
+}
+
+fn render_tile(
+    scene: &Scene,
+    settings: &RenderSettings,
+    cancel: &CancellationToken,
+    tile: Tile,
+) -> RenderedTile {
+    let mut rendered = RenderedTile {
+        pixels: Vec::with_capacity(tile.width * tile.height),
+        diagnostics: Diagnostics::default(),
+        stats: RenderStats::default(),
+        rows: 0,
+        tile,
+    };
+    // Once per tile rather than in the hot loop of every sample
+    stats::set_enabled(settings.collect_stats);
+
+    for j in 0..tile.height {
+        if cancel.is_cancelled() {
+            break;
+        }
+
+        for i in 0..tile.width {
+            let pixel = render_pixel(
+                scene,
+                settings,
+                tile.x + i,
+                tile.y + j,
+                &mut rendered.diagnostics,
+            );
+            rendered.pixels.push(pixel);
+        }
+        rendered.rows += 1;
+    }
+
+    rendered.stats = stats::take();
+    rendered
+}
+
+fn render_pixel(
+    scene: &Scene,
+    settings: &RenderSettings,
+    x: usize,
+    y: usize,
+    diagnostics: &mut Diagnostics,
+) -> Pixel {
+    let mut pixel = Pixel::default();
+
+    for sample in 0..settings.samples_per_pixel {
+        let mut aov = AovSample::default();
+        let color = trace_sample(scene, settings, x, y, sample, &mut aov, None);
+
+        if color.is_finite() {
+            pixel.add_sample(clamp_sample(color, settings.max_sample_value), &aov);
+        } else {
+            // Left out of the pixel altogether, along with its AOVs that could be just as broken,
+            // so the pixel is the average of the samples that are left instead of being darkened
+            diagnostics.record(InvalidSample {
+                x,
+                y,
+                sample,
+                value: color,
+                material_id: aov.invalid_material_id,
+            });
+        }
+    }
+
+    pixel.resolve();
+    pixel
+}
+
+/// Traces a single camera sample through pixel (`x`, `y`), black if the camera doesn't project anything there.
+/// The random numbers only depend on the pixel, the sample index and `settings.seed`,
+/// so any sample can be traced again on its own.
+/// Statistics are only counted on threads that turned them on, like the workers of `render`.
+pub fn trace_sample(
+    scene: &Scene,
+    settings: &RenderSettings,
+    x: usize,
+    y: usize,
+    sample: usize,
+    aov: &mut AovSample,
+    path: Option<&mut Vec<PathVertex>>,
+) -> Color3 {
+    let mut rng = sample_rng(settings.seed, x, y, sample);
+
+    let u = (x as Float + rng.gen::<Float>()) / (settings.width - 1) as Float;
+    let v = (y as Float + rng.gen::<Float>()) / (settings.height - 1) as Float;
+    match scene.camera.get_ray(u, v, &mut rng) {
+        Some(CameraRay { ray, weight }) => {
+            let color = ray_color(&ray, scene, &mut rng, settings.max_depth, aov, path);
+            aov.direct *= weight;
+            aov.indirect *= weight;
+            color * weight
+        }
+        None => {
+            *aov = AovSample {
+                depth: Float::INFINITY,
+                ..Default::default()
+            };
+            Color3::default()
+        }
+    }
+}
+
+fn sample_rng(seed: u64, x: usize, y: usize, sample: usize) -> SmallRng {
+    // splitmix64 finalizer so that neighbouring pixels get unrelated seeds
+    let mix = |mut z: u64| {
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    };
+
+    let seed = mix(mix(mix(seed ^ x as u64) ^ y as u64) ^ sample as u64);
+    SmallRng::seed_from_u64(seed)
+}
+
+#[inline]
+fn clamp_sample(color: Color3, max_sample_value: Option<Float>) -> Color3 {
+    match max_sample_value {
+        Some(max) => {
+            // Scale the whole colour so that the hue stays the same
+            let largest = Float::max(color.x, Float::max(color.y, color.z));
+            if largest > max {
+                color * (max / largest)
+            } else {
+                color
+            }
+        }
+        None => color,
+    }
+}
+
+/// Smallest tile side that is still worth splitting into
+const MIN_TILE_SIZE: usize = 4;
+
+#[derive(Clone, Copy, Debug)]
+struct Tile {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Job for Tile {
+    /// Splits into quarters, or halves along the longer side for thin tiles
+    fn split(self) -> std::result::Result<Vec<Tile>, Tile> {
+        let split_x = self.width >= 2 * MIN_TILE_SIZE;
+        let split_y = self.height >= 2 * MIN_TILE_SIZE;
+        if !split_x && !split_y {
+            return Err(self);
+        }
+
+        let xs = if split_x {
+            vec![
+                (self.x, self.width / 2),
+                (self.x + self.width / 2, self.width - self.width / 2),
+            ]
+        } else {
+            vec![(self.x, self.width)]
+        };
+        let ys = if split_y {
+            vec![
+                (self.y, self.height / 2),
+                (self.y + self.height / 2, self.height - self.height / 2),
+            ]
+        } else {
+            vec![(self.y, self.height)]
+        };
+
+        Ok(ys
+            .iter()
+            .flat_map(|&(y, height)| {
+                xs.iter().map(move |&(x, width)| Tile {
+                    x,
+                    y,
+                    width,
+                    height,
+                })
+            })
+            .collect())
+    }
+}
+
+struct RenderedTile {
+    tile: Tile,
+    /// Row major, only the first `rows` rows are there if the render was cancelled
+    pixels: Vec<Pixel>,
+    rows: usize,
+    diagnostics: Diagnostics,
+    stats: RenderStats,
+}
@@ -0,0 +1,89 @@
+/// Order in which tiles are handed out to the render threads
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TileOrder {
+    /// Bottom row first, left to right
+    RowMajor,
+    /// Outwards from the centre of the image, so the interesting part shows up first
+    #[default]
+    Spiral,
+    /// Along a Hilbert curve, which keeps consecutive tiles close together for better cache use
+    Hilbert,
+}
+
+impl TileOrder {
+    /// Every tile of a `columns` x `rows` grid exactly once, in this order
+    pub fn tiles(self, columns: usize, rows: usize) -> Vec<(usize, usize)> {
+        match self {
+            TileOrder::RowMajor => (0..rows)
+                .flat_map(|j| (0..columns).map(move |i| (i, j)))
+                .collect(),
+            TileOrder::Spiral => spiral(columns, rows),
+            TileOrder::Hilbert => {
+                let size = usize::max(columns, rows).next_power_of_two();
+                let mut tiles: Vec<(usize, usize)> = (0..rows)
+                    .flat_map(|j| (0..columns).map(move |i| (i, j)))
+                    .collect();
+                tiles.sort_by_key(|&(i, j)| hilbert_index(size, i, j));
+                tiles
+            }
+        }
+    }
+}
+
+/// Walks right, up, left, down with arm lengths 1, 1, 2, 2, 3, 3, ... skipping tiles outside of the grid
+fn spiral(columns: usize, rows: usize) -> Vec<(usize, usize)> {
+    let total = columns * rows;
+    let mut tiles = Vec::with_capacity(total);
+
+    let (mut i, mut j) = ((columns as isize - 1) / 2, (rows as isize - 1) / 2);
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut arm = 1;
+    let mut direction = 0;
+
+    let visit = |i: isize, j: isize, tiles: &mut Vec<(usize, usize)>| {
+        if i >= 0 && j >= 0 && (i as usize) < columns && (j as usize) < rows {
+            tiles.push((i as usize, j as usize));
+        }
+    };
+
+    visit(i, j, &mut tiles);
+    while tiles.len() < total {
+        for _ in 0..2 {
+            let (di, dj) = directions[direction];
+            for _ in 0..arm {
+                i += di;
+                j += dj;
+                visit(i, j, &mut tiles);
+            }
+            direction = (direction + 1) % 4;
+        }
+        arm += 1;
+    }
+
+    tiles
+}
+
+/// Distance along the Hilbert curve filling a `size` x `size` grid, `size` has to be a power of two
+fn hilbert_index(size: usize, mut x: usize, mut y: usize) -> usize {
+    let mut d = 0;
+    let mut s = size / 2;
+
+    while s > 0 {
+        let rx = (x & s > 0) as usize;
+        let ry = (y & s > 0) as usize;
+        d += s * s * ((3 * rx) ^ ry);
+
+        // Rotate the quadrant so the curve stays continuous
+        if ry == 0 {
+            if rx == 1 {
+                x = size - 1 - x;
+                y = size - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+
+        s /= 2;
+    }
+
+    d
+}
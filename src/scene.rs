@@ -1,130 +1,99 @@
 use super::*;
+use std::time::{Duration, Instant};
+
+mod cornell_box;
+mod material_spheres;
+mod random_sphere;
+pub use cornell_box::cornell_box;
+pub use material_spheres::{glass_sphere, metal_spheres};
+pub use random_sphere::random_spheres;
+
+/// Everything needed to render an image
+pub struct Scene {
+    pub world: HittableList,
+    pub materials: MaterialList,
+    pub lights: LightList,
+    pub camera: Box<dyn Camera>,
+    /// What rays that leave the scene see
+    pub environment: Environment,
+    /// Only takes effect through `set_time`
+    pub animation: Animation,
+    /// How long building the acceleration structures took
+    pub build_time: Duration,
+}
 
-use material::*;
-
-lazy_static! {
-    static ref MATERIAL_GROUND: Material = {
-        let material = Diffuse {
-            albedo: Color3::new(0.8, 0.8, 0.0),
-        };
-
-        Material::from(ReflectanceModel::Diffuse(material))
-    };
-    static ref MATERIAL_CENTER: Material = {
-        let material = Diffuse {
-            albedo: Color3::new(0.1, 0.2, 0.5),
-        };
-
-        Material::from(ReflectanceModel::Diffuse(material))
-    };
-    static ref MATERIAL_LEFT: Material = {
-        let material = Dielectric {
-            index_of_refraction: 1.5,
-        };
-
-        Material::from(ReflectanceModel::Dielectric(material))
-    };
-    static ref MATERIAL_RIGHT: Material = {
-        let material = Metal {
-            albedo: Color3::new(0.8, 0.6, 0.2),
-            fuzziness: 0.0,
-        };
-
-        Material::from(ReflectanceModel::Metal(material))
-    };
-    static ref MATERIAL_LIST: Vec<Material> = {
-        let mut rng = SmallRng::from_seed([126; 32]);
-        // let mut rng = SmallRng::from_entropy();
-        let num_sphere = 22 * 22;
-        let mut vec = Vec::with_capacity(num_sphere);
-        for _ in 0..num_sphere {
-            let choose_mat = rng.gen::<f32>();
-
-            vec.push(if choose_mat < 0.8 {
-                let albedo = Color3::random(&mut rng) * Color3::random(&mut rng);
-                Material::from(ReflectanceModel::Diffuse(Diffuse { albedo }))
-            } else if choose_mat < 0.95 {
-                let albedo = Color3::random(&mut rng) * 0.5 + Color3::new(0.5, 0.5, 0.5);
-                let fuzziness = rng.gen::<Float>() * 0.5;
-                Material::from(ReflectanceModel::Metal(Metal { albedo, fuzziness }))
-            } else {
-                Material::from(ReflectanceModel::Dielectric(Dielectric {
-                    index_of_refraction: 1.5,
-                }))
-            });
+impl Scene {
+    /// Checks every object and light and builds the acceleration structures of `world` and `lights`,
+    /// lights are sampled with `LightSampling::Bvh` and the environment is the default sky
+    pub fn new(
+        mut world: HittableList,
+        materials: MaterialList,
+        mut lights: LightList,
+        camera: impl Camera + 'static,
+    ) -> Result<Scene> {
+        world.validate(&materials)?;
+        for light in lights.iter() {
+            light.validate()?;
         }
-        vec
-    };
-    pub static ref WORLD: HittableList = {
-        let mut world = HittableList::default();
-        let mut rng = SmallRng::from_seed([123; 32]);
-        // let mut rng = SmallRng::from_entropy();
-
-        world.add(Hittable::Sphere(Sphere {
-            position: Point3::new(0.0, -1000.0, 0.0),
-            radius: 1000.0,
-            material: &MATERIAL_GROUND,
-        }));
 
-        for a in -11..11 {
-            for b in -11..11 {
-                let material = ((a + 11) * 22 + (b + 11)) as usize;
-                let a = a as Float;
-                let b = b as Float;
-                let position = Vector3::new(
-                    a + 0.9 * rng.gen::<Float>(),
-                    0.2,
-                    b + 0.9 * rng.gen::<Float>(),
-                );
+        let earlier = Instant::now();
+        world.init();
+        lights.init(LightSampling::Bvh, &world.bound());
+        let build_time = earlier.elapsed();
+
+        Ok(Scene {
+            world,
+            materials,
+            lights,
+            camera: Box::new(camera),
+            environment: Environment::default(),
+            animation: Animation::default(),
+            build_time,
+        })
+    }
+
+    /// Moves the camera and the instances to where the animation has them at `time`, in frames.
+    /// The acceleration structure is rebuilt if anything but the camera moves
+    pub fn set_time(&mut self, time: Float) -> Result<()> {
+        if let Some(camera) = &self.animation.camera {
+            self.camera = Box::new(camera.camera_at(time)?);
+        }
+        if self.animation.instances.is_empty() {
+            return Ok(());
+        }
 
-                if (position - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                    world.add(Hittable::Sphere(Sphere {
-                        position,
-                        radius: 0.2,
-                        material: &MATERIAL_LIST[material],
-                    }));
+        for (object_id, track) in &self.animation.instances {
+            match self.world.get_mut(*object_id) {
+                Some(Hittable::Instance(instance)) => {
+                    instance.transform = track.at(time).transform();
+                    instance.validate(&self.materials)?;
+                }
+                _ => {
+                    return Err(Error::invalid_parameter(format!(
+                        "object {} is animated but isn't an instance",
+                        object_id
+                    )))
                 }
             }
         }
 
-        world.add(Hittable::Sphere(Sphere {
-            position: Point3::new(0.0, 1.0, 0.0),
-            radius: 1.0,
-            material: &MATERIAL_CENTER,
-        }));
+        let earlier = Instant::now();
+        self.world.init();
+        self.build_time = earlier.elapsed();
 
-        world.add(Hittable::Sphere(Sphere {
-            position: Point3::new(-4.0, 1.0, 0.0),
-            radius: 1.0,
-            material: &MATERIAL_LEFT,
-        }));
+        Ok(())
+    }
 
-        world.add(Hittable::Sphere(Sphere {
-            position: Point3::new(4.0, 1.0, 0.0),
-            radius: 1.0,
-            material: &MATERIAL_RIGHT,
-        }));
-
-        world.init();
-
-        world
-    };
-    pub static ref CAMERA: Camera = {
-        let look_from = Point3::new(13.0, 2.0, 3.0);
-        let look_at = Point3::new(0.0, 0.0, 0.0);
-        let view_up = Vector3::new(0.0, 1.0, 0.0);
-        let fov = 20.0;
-        let dist_to_focus = 10.0;
-        let aperture = 0.1;
+    /// Replaces the thin lens of the camera, and of the animated camera so that it keeps it at every frame
+    pub fn set_thin_lens(&mut self, lens: ThinLens) -> Result<()> {
+        self.camera = self.camera.with_thin_lens(&lens)?;
+        if let Some(camera) = &mut self.animation.camera {
+            camera.lens = lens;
+        }
+        Ok(())
+    }
 
-        Camera::new(
-            look_from,
-            look_at,
-            view_up,
-            fov,
-            ASPECT_RATIO,
-            aperture,
-            dist_to_focus,
-        )
-    };
+    pub fn set_light_sampling(&mut self, sampling: LightSampling) {
+        self.lights.init(sampling, &self.world.bound());
+    }
 }
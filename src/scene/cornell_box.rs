@@ -0,0 +1,64 @@
+use super::*;
+
+use material::*;
+
+/// A Cornell box with a diffuse and a mirror sphere, lit by a point light under the ceiling.
+/// The walls are infinite planes, the box spans -1 to 1 in x and z and 0 to 2 in y
+/// and is open towards the camera.
+pub fn cornell_box(aspect_ratio: Float) -> Result<Scene> {
+    let mut materials = MaterialList::default();
+
+    let diffuse = |albedo| Material::from(ReflectanceModel::Diffuse(Diffuse { albedo }));
+    let white = materials.add(diffuse(Color3::new(0.73, 0.73, 0.73)));
+    let red = materials.add(diffuse(Color3::new(0.65, 0.05, 0.05)));
+    let green = materials.add(diffuse(Color3::new(0.12, 0.45, 0.15)));
+    let mirror = materials.add(Material::from(ReflectanceModel::Metal(Metal {
+        albedo: Color3::new(0.9, 0.9, 0.9),
+        fuzziness: 0.0,
+    })));
+
+    let mut world = HittableList::default();
+
+    let mut wall = |normal: Vector3, distance: Float, material| {
+        world.add(Hittable::Plane(Plane {
+            point: -normal * distance,
+            normal,
+            material,
+        }));
+    };
+    // `normal` points into the box and `distance` is how far the wall is from the origin
+    wall(Vector3::new(1.0, 0.0, 0.0), 1.0, red);
+    wall(Vector3::new(-1.0, 0.0, 0.0), 1.0, green);
+    wall(Vector3::new(0.0, 1.0, 0.0), 0.0, white);
+    wall(Vector3::new(0.0, -1.0, 0.0), -2.0, white);
+    wall(Vector3::new(0.0, 0.0, 1.0), 1.0, white);
+
+    world.add(Hittable::Sphere(Sphere {
+        position: Point3::new(-0.45, 0.4, -0.3),
+        radius: 0.4,
+        material: white,
+    }));
+    world.add(Hittable::Sphere(Sphere {
+        position: Point3::new(0.45, 0.4, 0.3),
+        radius: 0.4,
+        material: mirror,
+    }));
+
+    let mut lights = LightList::default();
+    lights.add(Light::Point(PointLight {
+        position: Point3::new(0.0, 1.9, 0.0),
+        intensity: Color3::new(3.0, 3.0, 3.0),
+    }));
+
+    let camera = PerspectiveCamera::new(
+        Point3::new(0.0, 1.0, 3.8),
+        Point3::new(0.0, 1.0, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        40.0,
+        aspect_ratio,
+        0.0,
+        3.8,
+    )?;
+
+    Scene::new(world, materials, lights, camera)
+}
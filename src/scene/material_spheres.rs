@@ -0,0 +1,104 @@
+use super::*;
+
+use material::*;
+
+fn ground(materials: &mut MaterialList, world: &mut HittableList) {
+    let material = materials.add(Material::from(ReflectanceModel::Diffuse(Diffuse {
+        albedo: Color3::new(0.5, 0.5, 0.5),
+    })));
+
+    world.add(Hittable::Plane(Plane {
+        point: Point3::new(0.0, 0.0, 0.0),
+        normal: Vector3::new(0.0, 1.0, 0.0),
+        material,
+    }));
+}
+
+fn sun() -> LightList {
+    let mut lights = LightList::default();
+    lights.add(Light::Directional(DirectionalLight::new(
+        Vector3::new(-1.0, -2.0, -0.5),
+        Color3::new(1.0, 0.9, 0.7),
+    )));
+    lights
+}
+
+fn camera(aspect_ratio: Float) -> Result<PerspectiveCamera> {
+    PerspectiveCamera::new(
+        Point3::new(0.0, 1.5, 5.0),
+        Point3::new(0.0, 0.6, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        35.0,
+        aspect_ratio,
+        0.0,
+        5.0,
+    )
+}
+
+/// A solid glass sphere next to a hollow one on a grey ground, under the sky and a sun
+pub fn glass_sphere(aspect_ratio: Float) -> Result<Scene> {
+    let mut materials = MaterialList::default();
+    let mut world = HittableList::default();
+    ground(&mut materials, &mut world);
+
+    let glass = materials.add(Material::from(ReflectanceModel::Dielectric(Dielectric {
+        index_of_refraction: 1.5,
+    })));
+    // The inside of a hollow sphere is a sphere of air in glass
+    let air = materials.add(Material::from(ReflectanceModel::Dielectric(Dielectric {
+        index_of_refraction: 1.0 / 1.5,
+    })));
+    let behind = materials.add(Material::from(ReflectanceModel::Diffuse(Diffuse {
+        albedo: Color3::new(0.8, 0.2, 0.1),
+    })));
+
+    world.add(Hittable::Sphere(Sphere {
+        position: Point3::new(-0.7, 0.6, 0.0),
+        radius: 0.6,
+        material: glass,
+    }));
+    world.add(Hittable::Sphere(Sphere {
+        position: Point3::new(0.7, 0.6, 0.0),
+        radius: 0.6,
+        material: glass,
+    }));
+    world.add(Hittable::Sphere(Sphere {
+        position: Point3::new(0.7, 0.6, 0.0),
+        radius: 0.5,
+        material: air,
+    }));
+    world.add(Hittable::Sphere(Sphere {
+        position: Point3::new(0.0, 0.4, -2.0),
+        radius: 0.4,
+        material: behind,
+    }));
+
+    Scene::new(world, materials, sun(), camera(aspect_ratio)?)
+}
+
+/// Three metal spheres on a grey ground going from a perfect mirror to very rough
+pub fn metal_spheres(aspect_ratio: Float) -> Result<Scene> {
+    let mut materials = MaterialList::default();
+    let mut world = HittableList::default();
+    ground(&mut materials, &mut world);
+
+    let spheres = [
+        (-1.3, Color3::new(0.9, 0.9, 0.9), 0.0),
+        (0.0, Color3::new(0.8, 0.6, 0.2), 0.2),
+        (1.3, Color3::new(0.7, 0.3, 0.3), 0.6),
+    ];
+
+    for &(x, albedo, fuzziness) in &spheres {
+        let material = materials.add(Material::from(ReflectanceModel::Metal(Metal {
+            albedo,
+            fuzziness,
+        })));
+        world.add(Hittable::Sphere(Sphere {
+            position: Point3::new(x, 0.6, 0.0),
+            radius: 0.6,
+            material,
+        }));
+    }
+
+    Scene::new(world, materials, sun(), camera(aspect_ratio)?)
+}
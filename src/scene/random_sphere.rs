@@ -1,38 +1,28 @@
 use super::*;
 
 use material::*;
-
-lazy_static! {
-    static ref MATERIAL_GROUND: Material = {
-        let material = Diffuse {
-            albedo: Color3::new(0.8, 0.8, 0.0),
-        };
-
-        Material::Diffuse(material)
-    };
-    static ref MATERIAL_CENTER: Material = {
-        let material = Diffuse {
-            albedo: Color3::new(0.1, 0.2, 0.5),
-        };
-
-        Material::Diffuse(material)
-    };
-    static ref MATERIAL_LEFT: Material = {
-        let material = Dielectric {
-            index_of_refraction: 1.5,
-        };
-
-        Material::Dielectric(material)
-    };
-    static ref MATERIAL_RIGHT: Material = {
-        let material = Metal {
-            albedo: Color3::new(0.8, 0.6, 0.2),
-            fuzziness: 0.0,
-        };
-
-        Material::Metal(material)
-    };
-    static ref MATERIAL_LIST: Vec<Material> = {
+use transforms::Transform;
+
+/// The final scene of "Ray Tracing in One Weekend" under the sky.
+/// Its animation dollies the camera in over 48 frames while the blue sphere bounces twice
+pub fn random_spheres(aspect_ratio: Float) -> Result<Scene> {
+    let mut materials = MaterialList::default();
+
+    let material_ground = materials.add(Material::from(ReflectanceModel::Diffuse(Diffuse {
+        albedo: Color3::new(0.8, 0.8, 0.0),
+    })));
+    let material_center = materials.add(Material::from(ReflectanceModel::Diffuse(Diffuse {
+        albedo: Color3::new(0.1, 0.2, 0.5),
+    })));
+    let material_left = materials.add(Material::from(ReflectanceModel::Dielectric(Dielectric {
+        index_of_refraction: 1.5,
+    })));
+    let material_right = materials.add(Material::from(ReflectanceModel::Metal(Metal {
+        albedo: Color3::new(0.8, 0.6, 0.2),
+        fuzziness: 0.0,
+    })));
+
+    let material_list: Vec<MaterialId> = {
         let mut rng = SmallRng::from_seed([126; 32]);
         // let mut rng = SmallRng::from_entropy();
         let num_sphere = 22 * 22;
@@ -40,91 +30,137 @@ lazy_static! {
         for _ in 0..num_sphere {
             let choose_mat = rng.gen::<f32>();
 
-            vec.push(if choose_mat < 0.8 {
+            let material = if choose_mat < 0.8 {
                 let albedo = Color3::random(&mut rng) * Color3::random(&mut rng);
-                Material::Diffuse(Diffuse { albedo })
+                Material::from(ReflectanceModel::Diffuse(Diffuse { albedo }))
             } else if choose_mat < 0.95 {
                 let albedo = Color3::random(&mut rng) * 0.5 + Color3::new(0.5, 0.5, 0.5);
                 let fuzziness = rng.gen::<Float>() * 0.5;
-                Material::Metal(Metal { albedo, fuzziness })
+                Material::from(ReflectanceModel::Metal(Metal { albedo, fuzziness }))
             } else {
-                Material::Dielectric(Dielectric {
+                Material::from(ReflectanceModel::Dielectric(Dielectric {
                     index_of_refraction: 1.5,
-                })
-            });
+                }))
+            };
+            vec.push(materials.add(material));
         }
         vec
     };
-    pub static ref WORLD: HittableList = {
-        let mut world = HittableList::default();
-        let mut rng = SmallRng::from_seed([123; 32]);
-        // let mut rng = SmallRng::from_entropy();
 
-        world.add(Hittable::Sphere(Sphere {
-            position: Point3::new(0.0, -1000.0, 0.0),
-            radius: 1000.0,
-            material: &MATERIAL_GROUND,
-        }));
-
-        for a in -11..11 {
-            for b in -11..11 {
-                let material = ((a + 11) * 22 + (b + 11)) as usize;
-                let a = a as Float;
-                let b = b as Float;
-                let position = Vector3::new(
-                    a + 0.9 * rng.gen::<Float>(),
-                    0.2,
-                    b + 0.9 * rng.gen::<Float>(),
-                );
-
-                if (position - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                    world.add(Hittable::Sphere(Sphere {
-                        position,
-                        radius: 0.2,
-                        material: &MATERIAL_LIST[material],
-                    }));
-                }
+    let mut world = HittableList::default();
+    let mut rng = SmallRng::from_seed([123; 32]);
+    // let mut rng = SmallRng::from_entropy();
+
+    world.add(Hittable::Sphere(Sphere {
+        position: Point3::new(0.0, -1000.0, 0.0),
+        radius: 1000.0,
+        material: material_ground,
+    }));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let material = ((a + 11) * 22 + (b + 11)) as usize;
+            let a = a as Float;
+            let b = b as Float;
+            let position = Vector3::new(
+                a + 0.9 * rng.gen::<Float>(),
+                0.2,
+                b + 0.9 * rng.gen::<Float>(),
+            );
+
+            if (position - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                world.add(Hittable::Sphere(Sphere {
+                    position,
+                    radius: 0.2,
+                    material: material_list[material],
+                }));
             }
         }
+    }
 
-        world.add(Hittable::Sphere(Sphere {
-            position: Point3::new(0.0, 1.0, 0.0),
+    let bouncing_sphere = world.add(Hittable::Instance(Instance {
+        object: Box::new(Hittable::Sphere(Sphere {
+            position: Point3::new(0.0, 0.0, 0.0),
             radius: 1.0,
-            material: &MATERIAL_CENTER,
-        }));
-
-        world.add(Hittable::Sphere(Sphere {
-            position: Point3::new(-4.0, 1.0, 0.0),
-            radius: 1.0,
-            material: &MATERIAL_LEFT,
-        }));
-
-        world.add(Hittable::Sphere(Sphere {
-            position: Point3::new(4.0, 1.0, 0.0),
-            radius: 1.0,
-            material: &MATERIAL_RIGHT,
-        }));
-
-        world.init();
-
-        world
-    };
-    pub static ref CAMERA: Camera = {
-        let look_from = Point3::new(13.0, 2.0, 3.0);
-        let look_at = Point3::new(0.0, 0.0, 0.0);
-        let view_up = Vector3::new(0.0, 1.0, 0.0);
-        let fov = 20.0;
-        let dist_to_focus = 10.0;
-        let aperture = 0.1;
-
-        Camera::new(
-            look_from,
-            look_at,
-            view_up,
-            fov,
-            ASPECT_RATIO,
-            aperture,
-            dist_to_focus,
-        )
+            material: material_center,
+        })),
+        transform: Transform::translate(&Vector3::new(0.0, 1.0, 0.0)),
+    }));
+
+    world.add(Hittable::Sphere(Sphere {
+        position: Point3::new(-4.0, 1.0, 0.0),
+        radius: 1.0,
+        material: material_left,
+    }));
+
+    world.add(Hittable::Sphere(Sphere {
+        position: Point3::new(4.0, 1.0, 0.0),
+        radius: 1.0,
+        material: material_right,
+    }));
+
+    let look_from = Point3::new(13.0, 2.0, 3.0);
+    let look_at = Point3::new(0.0, 0.0, 0.0);
+    let view_up = Vector3::new(0.0, 1.0, 0.0);
+    let fov = 20.0;
+    let dist_to_focus = 10.0;
+    let aperture = 0.1;
+
+    let camera = PerspectiveCamera::new(
+        look_from,
+        look_at,
+        view_up,
+        fov,
+        aspect_ratio,
+        aperture,
+        dist_to_focus,
+    )?;
+
+    let mut scene = Scene::new(world, materials, LightList::default(), camera)?;
+
+    scene.animation.camera = Some(CameraAnimation {
+        look_from: Track::new(vec![
+            keyframe(0.0, look_from),
+            keyframe(48.0, Point3::new(9.0, 1.6, 2.0)),
+        ])?,
+        look_at: Track::constant(look_at),
+        view_up,
+        fov: Track::constant(fov),
+        aspect_ratio,
+        aperture,
+        focus_distance: Track::new(vec![keyframe(0.0, dist_to_focus), keyframe(48.0, 7.0)])?,
+        lens: ThinLens::default(),
+    });
+
+    // Slows down towards the top and speeds up towards the ground
+    let bounce = |time, height, interpolation| Keyframe {
+        time,
+        value: Pose {
+            translation: Vector3::new(0.0, height, 0.0),
+            ..Pose::default()
+        },
+        interpolation: Interpolation::Bezier(interpolation),
     };
+    let (ease_out, ease_in) = ([0.0, 0.0, 0.58, 1.0], [0.42, 0.0, 1.0, 1.0]);
+    scene.animation.instances.push((
+        bouncing_sphere,
+        Track::new(vec![
+            bounce(0.0, 1.0, ease_out),
+            bounce(12.0, 2.0, ease_in),
+            bounce(24.0, 1.0, ease_out),
+            bounce(36.0, 2.0, ease_in),
+            bounce(48.0, 1.0, ease_out),
+        ])?,
+    ));
+
+    Ok(scene)
+}
+
+/// Eases in and out
+fn keyframe<T>(time: Float, value: T) -> Keyframe<T> {
+    Keyframe {
+        time,
+        value,
+        interpolation: Interpolation::Bezier([0.42, 0.0, 0.58, 1.0]),
+    }
 }
@@ -0,0 +1,162 @@
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
+
+thread_local! {
+    /// Counters of the current thread, so that recording never needs a lock or an atomic
+    static THREAD_STATS: RefCell<RenderStats> = RefCell::new(RenderStats::default());
+    /// Whether `record` does anything on the current thread, so that renders without statistics don't pay for them
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Turns recording on or off for the current thread
+pub fn set_enabled(enabled: bool) {
+    ENABLED.with(|cell| cell.set(enabled));
+}
+
+/// Adds to the counters of the current thread if recording is on
+#[inline]
+pub fn record<F: FnOnce(&mut RenderStats)>(f: F) {
+    if ENABLED.with(Cell::get) {
+        THREAD_STATS.with(|stats| f(&mut stats.borrow_mut()));
+    }
+}
+
+/// Takes the counters of the current thread, leaving them at zero
+pub fn take() -> RenderStats {
+    THREAD_STATS.with(|stats| std::mem::take(&mut *stats.borrow_mut()))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RenderStats {
+    pub camera_rays: u64,
+    pub shadow_rays: u64,
+    pub indirect_rays: u64,
+    /// Calls to `HittableList::hit`, which can be less than the rays above if a path is cut short
+    pub bvh_traversals: u64,
+    pub bvh_nodes_visited: u64,
+    pub primitive_tests: u64,
+    /// Number of paths by how many surfaces they hit before leaving the scene or being terminated
+    pub path_lengths: Vec<u64>,
+    pub russian_roulette_terminations: u64,
+    pub bvh_build_time: Duration,
+    pub render_time: Duration,
+    pub output_time: Duration,
+}
+
+impl RenderStats {
+    pub fn record_path_length(&mut self, length: usize) {
+        if self.path_lengths.len() <= length {
+            self.path_lengths.resize(length + 1, 0);
+        }
+        self.path_lengths[length] += 1;
+    }
+
+    /// Adds the counters of `other`, the timings are left alone as they are measured once per render
+    pub fn merge(&mut self, other: &RenderStats) {
+        self.camera_rays += other.camera_rays;
+        self.shadow_rays += other.shadow_rays;
+        self.indirect_rays += other.indirect_rays;
+        self.bvh_traversals += other.bvh_traversals;
+        self.bvh_nodes_visited += other.bvh_nodes_visited;
+        self.primitive_tests += other.primitive_tests;
+        self.russian_roulette_terminations += other.russian_roulette_terminations;
+
+        if self.path_lengths.len() < other.path_lengths.len() {
+            self.path_lengths.resize(other.path_lengths.len(), 0);
+        }
+        for (count, other) in self.path_lengths.iter_mut().zip(&other.path_lengths) {
+            *count += other;
+        }
+    }
+
+    pub fn total_rays(&self) -> u64 {
+        self.camera_rays + self.shadow_rays + self.indirect_rays
+    }
+
+    pub fn rays_per_second(&self) -> f64 {
+        if self.render_time.is_zero() {
+            return 0.0;
+        }
+        self.total_rays() as f64 / self.render_time.as_secs_f64()
+    }
+
+    fn per_traversal(&self, count: u64) -> f64 {
+        count as f64 / u64::max(self.bvh_traversals, 1) as f64
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+
+        report += &format!(
+            "Rays: {} camera, {} shadow, {} indirect ({:.2} M rays/s)\n",
+            self.camera_rays,
+            self.shadow_rays,
+            self.indirect_rays,
+            self.rays_per_second() / 1_000_000.0
+        );
+        report += &format!(
+            "BVH: {:.2} nodes visited and {:.2} primitive tests per ray\n",
+            self.per_traversal(self.bvh_nodes_visited),
+            self.per_traversal(self.primitive_tests)
+        );
+        report += &format!(
+            "Russian roulette terminated {} paths\n",
+            self.russian_roulette_terminations
+        );
+
+        report += "Path lengths:\n";
+        let paths: u64 = self.path_lengths.iter().sum();
+        for (length, &count) in self.path_lengths.iter().enumerate() {
+            report += &format!(
+                "    {:>3}: {:>12} ({:5.2}%)\n",
+                length,
+                count,
+                100.0 * count as f64 / u64::max(paths, 1) as f64
+            );
+        }
+
+        report += &format!(
+            "Time: {:.3}s BVH build, {:.3}s render, {:.3}s output\n",
+            self.bvh_build_time.as_secs_f64(),
+            self.render_time.as_secs_f64(),
+            self.output_time.as_secs_f64()
+        );
+
+        report
+    }
+
+    pub fn to_json(&self) -> String {
+        let path_lengths: Vec<String> = self.path_lengths.iter().map(u64::to_string).collect();
+
+        format!(
+            concat!(
+                "{{\n",
+                "  \"camera_rays\": {},\n",
+                "  \"shadow_rays\": {},\n",
+                "  \"indirect_rays\": {},\n",
+                "  \"rays_per_second\": {},\n",
+                "  \"bvh_traversals\": {},\n",
+                "  \"bvh_nodes_visited\": {},\n",
+                "  \"primitive_tests\": {},\n",
+                "  \"path_lengths\": [{}],\n",
+                "  \"russian_roulette_terminations\": {},\n",
+                "  \"bvh_build_seconds\": {},\n",
+                "  \"render_seconds\": {},\n",
+                "  \"output_seconds\": {}\n",
+                "}}\n"
+            ),
+            self.camera_rays,
+            self.shadow_rays,
+            self.indirect_rays,
+            self.rays_per_second(),
+            self.bvh_traversals,
+            self.bvh_nodes_visited,
+            self.primitive_tests,
+            path_lengths.join(", "),
+            self.russian_roulette_terminations,
+            self.bvh_build_time.as_secs_f64(),
+            self.render_time.as_secs_f64(),
+            self.output_time.as_secs_f64()
+        )
+    }
+}
@@ -0,0 +1,113 @@
+use super::hittable::Interaction;
+use super::vector::*;
+use super::{Error, Float, Result};
+use std::sync::Arc;
+
+/// A colour that varies over a surface, multiplied into its material's reflectance
+#[derive(Clone)]
+pub enum Texture {
+    /// The colour the surface carries at the hit, like a mesh's vertex colours. White where it has none
+    VertexColor,
+    /// Looked up with the hit's uv
+    Image(Arc<ImageTexture>),
+    Product(Box<Texture>, Box<Texture>),
+}
+
+impl Texture {
+    pub fn evaluate(&self, interaction: &Interaction) -> Color3 {
+        match self {
+            Texture::VertexColor => interaction.color.unwrap_or(Color3::new(1.0, 1.0, 1.0)),
+            Texture::Image(image) => image.lookup(interaction.uv),
+            Texture::Product(a, b) => a.evaluate(interaction) * b.evaluate(interaction),
+        }
+    }
+}
+
+/// What an image texture shows outside of 0 to 1 along u or v
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Wrap {
+    Repeat,
+    /// Repeats, flipped every other time
+    MirroredRepeat,
+    /// Carries on with the pixels at the edge
+    Clamp,
+}
+
+impl Wrap {
+    /// The pixel shown at `i` of `size`
+    fn pixel(self, i: i64, size: usize) -> usize {
+        let size = size as i64;
+        let i = match self {
+            Wrap::Repeat => i.rem_euclid(size),
+            Wrap::MirroredRepeat => {
+                let i = i.rem_euclid(2 * size);
+                if i < size {
+                    i
+                } else {
+                    2 * size - 1 - i
+                }
+            }
+            Wrap::Clamp => i.clamp(0, size - 1),
+        };
+        i as usize
+    }
+}
+
+/// Linear colours with the top row first, so that uv (0, 0) is the top left corner as in glTF.
+/// `wrap` says what's shown outside of 0 to 1 along u and v
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color3>,
+    wrap: [Wrap; 2],
+}
+
+impl ImageTexture {
+    pub fn new(
+        width: usize,
+        height: usize,
+        pixels: Vec<Color3>,
+        wrap: [Wrap; 2],
+    ) -> Result<ImageTexture> {
+        if width == 0 || height == 0 || pixels.len() != width * height {
+            return Err(Error::invalid_parameter(format!(
+                "a {}x{} image texture needs {} pixels, got {}",
+                width,
+                height,
+                width * height,
+                pixels.len()
+            )));
+        }
+        Ok(ImageTexture {
+            width,
+            height,
+            pixels,
+            wrap,
+        })
+    }
+
+    /// Bilinearly filtered between the centres of the 4 nearest pixels
+    pub fn lookup(&self, uv: [Float; 2]) -> Color3 {
+        let x = uv[0] * self.width as Float - 0.5;
+        let y = uv[1] * self.height as Float - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (dx, dy) = (x - x0, y - y0);
+
+        let pixel = |x: Float, y: Float| {
+            let x = self.wrap[0].pixel(x as i64, self.width);
+            let y = self.wrap[1].pixel(y as i64, self.height);
+            self.pixels[y * self.width + x]
+        };
+        (1.0 - dy) * ((1.0 - dx) * pixel(x0, y0) + dx * pixel(x0 + 1.0, y0))
+            + dy * ((1.0 - dx) * pixel(x0, y0 + 1.0) + dx * pixel(x0 + 1.0, y0 + 1.0))
+    }
+}
+
+/// From an sRGB encoded value from 0 to 1 to a linear one
+pub fn srgb_to_linear(value: Float) -> Float {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
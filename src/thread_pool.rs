@@ -1,89 +1,158 @@
-use std::sync::mpsc;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
 
-type Job<T> = Box<dyn Fn(T) + 'static + Send>;
+#[cfg(test)]
+mod tests;
 
-pub struct ThreadPool<T: Send + 'static> {
-    senders: Vec<mpsc::Sender<T>>,
-    receiver: mpsc::Receiver<usize>,
-    workers: Vec<Worker>,
-    available_workers: Vec<usize>,
-    command_que: Vec<T>,
-    pub size: usize,
+/// A unit of work for the `ThreadPool`
+pub trait Job: Send + Sized {
+    /// Splits the job into smaller jobs so that idle threads have something to steal near the end,
+    /// gives the job back if it is too small to be split
+    fn split(self) -> Result<Vec<Self>, Self>;
 }
 
-impl<T: Send + 'static> ThreadPool<T> {
-    pub fn new<F>(size: usize, job: F) -> Self
-    where
-        F: Fn() -> Job<T>,
-    {
-        let mut senders = Vec::with_capacity(size);
-        let mut workers = Vec::with_capacity(size);
+/// Shared flag to stop a render early. Jobs that are already running are expected to check it themselves
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
 
-        let (tx_finish, rx_finish) = mpsc::channel();
+/// Work stealing pool: every worker takes jobs from the front of its own queue
+/// and once that is empty steals from the back of the others.
+/// Jobs are dealt out round robin so the order they are given in is roughly the order they run in.
+pub struct ThreadPool<T: Job> {
+    queues: Vec<Mutex<VecDeque<T>>>,
+    /// Jobs waiting in a queue
+    queued: AtomicUsize,
+    /// Jobs that are queued or running
+    pending: AtomicUsize,
+    cancel: CancellationToken,
+    /// Idle workers wait on this until a job is split, a job finishes or the pool is cancelled
+    idle: Condvar,
+    idle_lock: Mutex<()>,
+}
+
+impl<T: Job> ThreadPool<T> {
+    pub fn new(size: usize, jobs: Vec<T>, cancel: CancellationToken) -> Self {
+        let mut queues: Vec<VecDeque<T>> = (0..size).map(|_| VecDeque::new()).collect();
+        let count = jobs.len();
 
-        for id in 0..size {
-            let (tx, rx) = mpsc::channel();
-            workers.push(Worker::new(rx, tx_finish.clone(), job(), id));
-            senders.push(tx);
+        for (i, job) in jobs.into_iter().enumerate() {
+            queues[i % size].push_back(job);
         }
 
         ThreadPool {
-            senders,
-            receiver: rx_finish,
-            workers,
-            available_workers: (0..size).collect(),
-            command_que: vec![],
-            size,
+            queues: queues.into_iter().map(Mutex::new).collect(),
+            queued: AtomicUsize::new(count),
+            pending: AtomicUsize::new(count),
+            cancel,
+            idle: Condvar::new(),
+            idle_lock: Mutex::new(()),
         }
     }
 
-    pub fn push_que(&mut self, work: T) {
-        self.command_que.push(work);
+    /// Runs every job on its own set of threads, returns once they are all done or the pool was cancelled
+    pub fn execute<F>(&self, run: F)
+    where
+        F: Fn(T) + Sync,
+    {
+        thread::scope(|scope| {
+            for id in 0..self.queues.len() {
+                let run = &run;
+                scope.spawn(move || self.worker(id, run));
+            }
+        });
     }
 
-    pub fn execute_que(&mut self) {
-        while !self.command_que.is_empty() {
-            let argument = self.command_que.pop().unwrap();
-            let available = if !self.available_workers.is_empty() {
-                self.available_workers.pop().unwrap()
+    fn worker<F>(&self, id: usize, run: &F)
+    where
+        F: Fn(T),
+    {
+        while !self.cancel.is_cancelled() && self.pending.load(Ordering::Acquire) > 0 {
+            let job = match self.pop(id) {
+                Some(job) => job,
+                None => {
+                    self.wait_for_work();
+                    continue;
+                }
+            };
+
+            // Split once there aren't enough jobs left to keep every thread busy
+            let job = if self.queued.load(Ordering::Acquire) < self.queues.len() {
+                match job.split() {
+                    Ok(mut jobs) => {
+                        let first = jobs.remove(0);
+                        let extra = jobs.len();
+
+                        self.pending.fetch_add(extra, Ordering::AcqRel);
+                        self.queued.fetch_add(extra, Ordering::AcqRel);
+                        {
+                            let mut queue = self.queues[id].lock().unwrap();
+                            for job in jobs.into_iter().rev() {
+                                queue.push_front(job);
+                            }
+                        }
+                        self.wake_idle();
+
+                        first
+                    }
+                    Err(job) => job,
+                }
             } else {
-                self.receiver.recv().unwrap()
+                job
             };
-            self.senders[available].send(argument).unwrap();
+
+            run(job);
+
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+            // Whoever is waiting might have been waiting for the last job, or for the cancelled render to stop
+            self.wake_idle();
         }
     }
-}
 
-impl<T: Send + 'static> Drop for ThreadPool<T> {
-    fn drop(&mut self) {
-        for worker in &mut self.workers {
-            if let Some(thread) = worker.thread.take() {
-                thread.join().unwrap();
-            }
+    /// Blocks while everything left is running, as one of those jobs might still be split
+    fn wait_for_work(&self) {
+        let guard = self.idle_lock.lock().unwrap();
+        // Checked under the lock, which `wake_idle` also takes, so that a wake up can't be missed
+        if self.queued.load(Ordering::Acquire) == 0
+            && self.pending.load(Ordering::Acquire) > 0
+            && !self.cancel.is_cancelled()
+        {
+            let _guard = self.idle.wait(guard).unwrap();
         }
     }
-}
 
-struct Worker {
-    thread: Option<thread::JoinHandle<()>>,
-}
+    fn wake_idle(&self) {
+        let _guard = self.idle_lock.lock().unwrap();
+        self.idle.notify_all();
+    }
 
-impl Worker {
-    fn new<T: Send + 'static>(
-        receiver: mpsc::Receiver<T>,
-        sender: mpsc::Sender<usize>,
-        work: Job<T>,
-        id: usize,
-    ) -> Self {
-        let thread = thread::spawn(move || {
-            for arg in receiver {
-                work(arg);
-                sender.send(id).unwrap();
-            }
+    fn pop(&self, id: usize) -> Option<T> {
+        let size = self.queues.len();
+
+        // Only ever hold one lock at a time so that two thieves can't deadlock
+        let own = self.queues[id].lock().unwrap().pop_front();
+        let job = own.or_else(|| {
+            (1..size)
+                .find_map(|offset| self.queues[(id + offset) % size].lock().unwrap().pop_back())
         });
-        Worker {
-            thread: Some(thread),
+
+        if job.is_some() {
+            self.queued.fetch_sub(1, Ordering::AcqRel);
         }
+
+        job
     }
 }
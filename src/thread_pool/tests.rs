@@ -0,0 +1,82 @@
+//! Runs made up jobs through the pool and checks that each piece of work runs exactly once
+
+use super::*;
+
+/// Work on `start..end`, split in half down to single items
+#[derive(Debug)]
+struct Range {
+    start: usize,
+    end: usize,
+}
+
+impl Job for Range {
+    fn split(self) -> Result<Vec<Range>, Range> {
+        if self.end - self.start < 2 {
+            return Err(self);
+        }
+        let middle = (self.start + self.end) / 2;
+        Ok(vec![
+            Range {
+                start: self.start,
+                end: middle,
+            },
+            Range {
+                start: middle,
+                end: self.end,
+            },
+        ])
+    }
+}
+
+fn ranges(count: usize, length: usize) -> Vec<Range> {
+    (0..count)
+        .map(|i| Range {
+            start: i * length,
+            end: (i + 1) * length,
+        })
+        .collect()
+}
+
+#[test]
+fn every_item_runs_once() {
+    for threads in [1, 2, 7] {
+        for (count, length) in [(0, 1), (1, 64), (5, 3), (100, 16)] {
+            let runs: Vec<AtomicUsize> = (0..count * length).map(|_| AtomicUsize::new(0)).collect();
+            let pool =
+                ThreadPool::new(threads, ranges(count, length), CancellationToken::default());
+
+            pool.execute(|range| {
+                // Slow enough that the other threads run out of jobs and have to wait for splits
+                thread::sleep(std::time::Duration::from_micros(50));
+                for run in &runs[range.start..range.end] {
+                    run.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+
+            assert!(
+                runs.iter().all(|run| run.load(Ordering::Relaxed) == 1),
+                "{} threads didn't run {} jobs of {} items once each",
+                threads,
+                count,
+                length
+            );
+        }
+    }
+}
+
+#[test]
+fn cancelling_stops_the_pool() {
+    let cancel = CancellationToken::default();
+    let pool = ThreadPool::new(4, ranges(1000, 16), cancel.clone());
+    let ran = AtomicUsize::new(0);
+
+    pool.execute(|_| {
+        if ran.fetch_add(1, Ordering::Relaxed) == 10 {
+            cancel.cancel();
+        }
+        thread::sleep(std::time::Duration::from_micros(50));
+    });
+
+    // Only the jobs that were already running when it was cancelled still finish
+    assert!(ran.load(Ordering::Relaxed) < 20);
+}
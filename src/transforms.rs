@@ -1,7 +1,7 @@
 #![allow(dead_code)]
 
 use super::vector::*;
-use super::Float;
+use super::{Error, Float, Result};
 use std::ops::Mul;
 
 type Matrix = [Float; 16];
@@ -34,6 +34,19 @@ impl Transform {
             inv: transpose(self.inv),
         }
     }
+    /// From a matrix given row by row, `None` if it can't be inverted
+    pub fn from_matrix(rows: [[Float; 4]; 4]) -> Option<Transform> {
+        let mut mat = identity();
+        for (i, row) in rows.iter().enumerate() {
+            mat[i * 4..i * 4 + 4].copy_from_slice(row);
+        }
+
+        Some(Transform {
+            mat,
+            inv: inverse(mat)?,
+        })
+    }
+
     pub fn translate(delta: &Vector3) -> Transform {
         Transform {
             mat: [
@@ -112,11 +125,40 @@ impl Transform {
             inv: transpose(mat),
         }
     }
+    /// Counter-clockwise around `axis` looking down it, which doesn't need to be normalized
+    pub fn rotate(theta: Float, axis: &Vector3) -> Transform {
+        let Vector3 { x, y, z } = Vector3::unit_vector(*axis);
+        let theta = theta.to_radians();
+        let cos = Float::cos(theta);
+        let sin = Float::sin(theta);
+        let mat = [
+            x * x + (1.0 - x * x) * cos,
+            x * y * (1.0 - cos) - z * sin,
+            x * z * (1.0 - cos) + y * sin,
+            0.0,
+            x * y * (1.0 - cos) + z * sin,
+            y * y + (1.0 - y * y) * cos,
+            y * z * (1.0 - cos) - x * sin,
+            0.0,
+            x * z * (1.0 - cos) - y * sin,
+            y * z * (1.0 - cos) + x * sin,
+            z * z + (1.0 - z * z) * cos,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            1.0,
+        ];
+        Transform {
+            mat,
+            inv: transpose(mat),
+        }
+    }
 
     // I like nicely indented stuff
     #[allow(clippy::identity_op)]
     #[allow(clippy::erasing_op)]
-    pub fn look_at(look_from: &Point3, look_at: &Point3, up: &Vector3) -> Transform {
+    pub fn look_at(look_from: &Point3, look_at: &Point3, up: &Vector3) -> Result<Transform> {
         let mut transform = identity();
 
         transform[0 * 4 + 3] = look_from.x;
@@ -140,12 +182,19 @@ impl Transform {
         transform[2 * 4 + 2] = dir.z;
         transform[3 * 4 + 2] = 0.;
 
-        Transform {
-            mat: inverse(transform),
+        let mat = inverse(transform).ok_or_else(|| {
+            Error::invalid_parameter(
+                "look at direction must be non-zero and not parallel to the up vector",
+            )
+        })?;
+
+        Ok(Transform {
+            mat,
             inv: transform,
-        }
+        })
     }
 
+    /// `w` is 1 for points and 0 for directions, which translations leave alone
     pub fn apply(&self, vector: &Vector3, w: Float) -> Vector3 {
         let Vector3 { x, y, z } = vector;
 
@@ -156,12 +205,28 @@ impl Transform {
 
         // Is this more efficient? idk
         // Should check
-        if (wp - 1.0).abs() < Float::EPSILON {
+        if w == 0.0 || (wp - 1.0).abs() < Float::EPSILON {
             Vector3::new(xp, yp, zp)
         } else {
             Vector3::new(xp, yp, zp) / wp
         }
     }
+
+    /// Normals need the inverse transpose to stay perpendicular to surfaces under non-uniform scaling.
+    /// The result isn't normalized
+    pub fn apply_normal(&self, normal: &Vector3) -> Vector3 {
+        let Vector3 { x, y, z } = normal;
+
+        Vector3::new(
+            self.inv[0] * x + self.inv[4] * y + self.inv[8] * z,
+            self.inv[1] * x + self.inv[5] * y + self.inv[9] * z,
+            self.inv[2] * x + self.inv[6] * y + self.inv[10] * z,
+        )
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.mat.iter().chain(&self.inv).all(|x| x.is_finite())
+    }
 }
 
 impl Mul for Transform {
@@ -199,7 +264,7 @@ fn multiply(m1: Matrix, m2: Matrix) -> Matrix {
         for j in 0..4 {
             m[i * 4 + j] = 0.0;
             for k in 0..4 {
-                m[i * 4 + j] += m1[i * 4 + k] + m2[k * 4 + j];
+                m[i * 4 + j] += m1[i * 4 + k] * m2[k * 4 + j];
             }
         }
     }
@@ -207,7 +272,8 @@ fn multiply(m1: Matrix, m2: Matrix) -> Matrix {
     m
 }
 
-fn inverse(mat: Matrix) -> Matrix {
+/// `None` if the matrix is singular
+fn inverse(mat: Matrix) -> Option<Matrix> {
     let mut index_column = [0; 4];
     let mut index_row = [0; 4];
     let mut index_pivot = [0; 4];
@@ -225,7 +291,7 @@ fn inverse(mat: Matrix) -> Matrix {
                         row = j;
                         column = k;
                     } else if pivot > 1 {
-                        panic!("Singular matrix in MatrixInvert");
+                        return None;
                     }
                 }
             }
@@ -239,7 +305,7 @@ fn inverse(mat: Matrix) -> Matrix {
         index_row[i] = row;
         index_column[i] = column;
         if mat_inv[column * 4 + column] == 0.0 {
-            panic!("Singular matrix in MatrixInvert");
+            return None;
         }
 
         let pivot_inv = 1.0 / mat_inv[column * 4 + column];
@@ -264,10 +330,10 @@ fn inverse(mat: Matrix) -> Matrix {
     for j in (0..4).rev() {
         if index_row[j] != index_column[j] {
             for i in 0..4 {
-                mat_inv.swap(i * 4 + index_row[j], index_column[j]);
+                mat_inv.swap(i * 4 + index_row[j], i * 4 + index_column[j]);
             }
         }
     }
 
-    mat_inv
+    Some(mat_inv)
 }
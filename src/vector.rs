@@ -62,8 +62,9 @@ impl Vector3 {
         self.x.is_infinite() || self.y.is_infinite() || self.z.is_infinite()
     }
 
+    /// Unlike `f32::is_normal` zero is allowed
     #[inline]
-    pub fn is_normal(&self) -> bool {
+    pub fn is_finite(&self) -> bool {
         !self.is_nan() && !self.is_infinite()
     }
 
@@ -219,16 +220,38 @@ impl Neg for Vector3 {
     }
 }
 
+impl Vector3 {
+    /// Checked version of indexing, `None` unless `index` is 0, 1 or 2
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&Float> {
+        match index {
+            0 => Some(&self.x),
+            1 => Some(&self.y),
+            2 => Some(&self.z),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Float> {
+        match index {
+            0 => Some(&mut self.x),
+            1 => Some(&mut self.y),
+            2 => Some(&mut self.z),
+            _ => None,
+        }
+    }
+}
+
+/// Like slices, indexing out of bounds is a bug and panics. Use `get` when the index comes from input
 impl Index<usize> for Vector3 {
     type Output = Float;
 
     #[inline]
     fn index(&self, index: usize) -> &Self::Output {
-        match index {
-            0 => &self.x,
-            1 => &self.y,
-            2 => &self.z,
-            _ => panic!("Vector3 can only be indexed by 0, 1, or 2"),
+        match self.get(index) {
+            Some(value) => value,
+            None => panic!("Vector3 can only be indexed by 0, 1, or 2, got {}", index),
         }
     }
 }
@@ -236,11 +259,9 @@ impl Index<usize> for Vector3 {
 impl IndexMut<usize> for Vector3 {
     #[inline]
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        match index {
-            0 => &mut self.x,
-            1 => &mut self.y,
-            2 => &mut self.z,
-            _ => panic!("Vector3 can only be indexed by 0, 1, or 2"),
+        match self.get_mut(index) {
+            Some(value) => value,
+            None => panic!("Vector3 can only be indexed by 0, 1, or 2, got {}", index),
         }
     }
 }
@@ -0,0 +1,230 @@
+//! Checks keyframe interpolation and that instances end up where their transforms and animation tracks put them.
+
+use rustrt::transforms::Transform;
+use rustrt::*;
+
+mod common;
+use common::{assert_near, hit, material};
+
+fn keyframe<T>(time: Float, value: T, interpolation: Interpolation) -> Keyframe<T> {
+    Keyframe {
+        time,
+        value,
+        interpolation,
+    }
+}
+
+#[test]
+fn composed_transforms_and_their_inverse() {
+    let transform = Transform::translate(&Vector3::new(1.0, 2.0, 3.0))
+        * Transform::rotate_z(90.0)
+        * Transform::scale(&Vector3::new(2.0, 2.0, 2.0));
+
+    // Scaled, then rotated, then translated
+    let p = Point3::new(1.0, 0.0, 0.0);
+    assert_near(transform.apply(&p, 1.0), Point3::new(1.0, 4.0, 3.0));
+    assert_near(transform.inverse().apply(&transform.apply(&p, 1.0), 1.0), p);
+    assert_near(transform.apply(&p, 0.0), Vector3::new(0.0, 2.0, 0.0));
+}
+
+#[test]
+fn look_at_moves_the_target_in_front() {
+    let look_from = Point3::new(1.0, 2.0, 3.0);
+    let look_at = Point3::new(4.0, -2.0, 3.0);
+    let transform = Transform::look_at(&look_from, &look_at, &Vector3::new(0.0, 0.0, 1.0)).unwrap();
+
+    assert_near(transform.apply(&look_from, 1.0), Point3::new(0.0, 0.0, 0.0));
+    assert_near(transform.apply(&look_at, 1.0), Point3::new(0.0, 0.0, 5.0));
+    assert_near(
+        transform.inverse().apply(&Point3::new(0.0, 0.0, 5.0), 1.0),
+        look_at,
+    );
+}
+
+#[test]
+fn linear_track_holds_before_and_after_its_keyframes() {
+    let track = Track::new(vec![
+        keyframe(2.0, 1.0, Interpolation::Linear),
+        keyframe(4.0, 3.0, Interpolation::Linear),
+        keyframe(8.0, -1.0, Interpolation::Linear),
+    ])
+    .unwrap();
+
+    assert_eq!(track.at(0.0), 1.0);
+    assert_eq!(track.at(2.0), 1.0);
+    assert_eq!(track.at(3.0), 2.0);
+    assert_eq!(track.at(4.0), 3.0);
+    assert_eq!(track.at(7.0), 0.0);
+    assert_eq!(track.at(100.0), -1.0);
+}
+
+#[test]
+fn bezier_eases_in_and_out() {
+    let ease_in_out = Interpolation::Bezier([0.42, 0.0, 0.58, 1.0]);
+    let track = Track::new(vec![
+        keyframe(0.0, 0.0, ease_in_out),
+        keyframe(10.0, 1.0, ease_in_out),
+    ])
+    .unwrap();
+
+    assert!((track.at(5.0) - 0.5).abs() < 1e-4);
+    assert!(track.at(1.0) < 0.1 && track.at(9.0) > 0.9);
+    assert!((track.at(1.0) + track.at(9.0) - 1.0).abs() < 1e-4);
+    for i in 0..100 {
+        assert!(track.at(i as Float / 10.0) <= track.at((i + 1) as Float / 10.0));
+    }
+}
+
+#[test]
+fn invalid_tracks_are_rejected() {
+    let linear = Interpolation::Linear;
+    assert!(Track::<Float>::new(vec![]).is_err());
+    assert!(Track::new(vec![keyframe(1.0, 0.0, linear), keyframe(1.0, 1.0, linear)]).is_err());
+    assert!(Track::new(vec![keyframe(
+        0.0,
+        0.0,
+        Interpolation::Bezier([1.5, 0.0, 0.5, 1.0])
+    )])
+    .is_err());
+}
+
+#[test]
+fn instance_is_hit_where_its_transform_puts_it() {
+    let (materials, material) = material();
+
+    // A unit sphere stretched to 2 along x and moved to x = 5
+    let mut world = HittableList::default();
+    world.add(Hittable::Instance(Instance {
+        object: Box::new(Hittable::Sphere(Sphere {
+            position: Point3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material,
+        })),
+        transform: Transform::translate(&Vector3::new(5.0, 0.0, 0.0))
+            * Transform::scale(&Vector3::new(2.0, 1.0, 1.0)),
+    }));
+    world.validate(&materials).unwrap();
+    world.init();
+
+    let bound = world.bound();
+    assert_near(bound.p_min, Point3::new(3.0, -1.0, -1.0));
+    assert_near(bound.p_max, Point3::new(7.0, 1.0, 1.0));
+
+    let interaction = hit(
+        &world,
+        Point3::new(0.0, 0.0, 0.0),
+        Vector3::new(1.0, 0.0, 0.0),
+    )
+    .unwrap();
+    assert!((interaction.t - 3.0).abs() < 1e-4);
+    assert_near(interaction.normal, Vector3::new(-1.0, 0.0, 0.0));
+
+    // The normal of a stretched sphere isn't the scaled normal of the unit sphere
+    let on_surface = Point3::new(5.0 + Float::sqrt(2.0), Float::sqrt(0.5), 0.0);
+    let interaction = hit(
+        &world,
+        on_surface + Vector3::new(0.0, 10.0, 0.0),
+        Vector3::new(0.0, -1.0, 0.0),
+    )
+    .unwrap();
+    assert_near(interaction.p, on_surface);
+    assert_near(
+        interaction.normal,
+        Vector3::unit_vector(Vector3::new(0.5, 1.0, 0.0)),
+    );
+}
+
+#[test]
+fn set_time_moves_the_camera_and_instances() {
+    let (materials, material) = material();
+
+    let mut world = HittableList::default();
+    world.add(Hittable::Sphere(Sphere {
+        position: Point3::new(0.0, -100.0, 0.0),
+        radius: 99.0,
+        material,
+    }));
+    let instance = world.add(Hittable::Instance(Instance {
+        object: Box::new(Hittable::Sphere(Sphere {
+            position: Point3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material,
+        })),
+        transform: Transform::default(),
+    }));
+
+    let look_from = Point3::new(0.0, 0.0, 10.0);
+    let look_at = Point3::new(0.0, 0.0, 0.0);
+    let up = Vector3::new(0.0, 1.0, 0.0);
+    let camera = PerspectiveCamera::new(look_from, look_at, up, 40.0, 1.0, 0.0, 10.0).unwrap();
+    let mut scene = Scene::new(world, materials, LightList::default(), camera).unwrap();
+
+    let pose = |x| Pose {
+        translation: Vector3::new(x, 0.0, 0.0),
+        ..Pose::default()
+    };
+    scene.animation.instances.push((
+        instance,
+        Track::new(vec![
+            keyframe(0.0, pose(0.0), Interpolation::Linear),
+            keyframe(10.0, pose(20.0), Interpolation::Linear),
+        ])
+        .unwrap(),
+    ));
+    scene.animation.camera = Some(CameraAnimation {
+        look_from: Track::new(vec![
+            keyframe(0.0, look_from, Interpolation::Linear),
+            keyframe(
+                10.0,
+                look_from + Vector3::new(20.0, 0.0, 0.0),
+                Interpolation::Linear,
+            ),
+        ])
+        .unwrap(),
+        look_at: Track::new(vec![
+            keyframe(0.0, look_at, Interpolation::Linear),
+            keyframe(
+                10.0,
+                look_at + Vector3::new(20.0, 0.0, 0.0),
+                Interpolation::Linear,
+            ),
+        ])
+        .unwrap(),
+        view_up: up,
+        fov: Track::constant(40.0),
+        aspect_ratio: 1.0,
+        aperture: 0.0,
+        focus_distance: Track::constant(10.0),
+        lens: ThinLens::default(),
+    });
+
+    scene.set_time(5.0).unwrap();
+
+    let interaction = hit(
+        &scene.world,
+        Point3::new(10.0, 0.0, 10.0),
+        Vector3::new(0.0, 0.0, -1.0),
+    )
+    .unwrap();
+    assert_eq!(interaction.object_id, instance);
+    assert!((interaction.t - 9.0).abs() < 1e-4);
+    assert!(hit(
+        &scene.world,
+        Point3::new(0.0, 0.0, 10.0),
+        Vector3::new(0.0, 0.0, -1.0)
+    )
+    .is_none());
+
+    // The camera follows the sphere, so it's still in the middle of the image
+    let mut rng = rand::SeedableRng::seed_from_u64(0);
+    let ray = scene.camera.get_ray(0.5, 0.5, &mut rng).unwrap().ray;
+    assert_near(ray.origin, Point3::new(10.0, 0.0, 10.0));
+    assert_near(
+        Vector3::unit_vector(ray.direction),
+        Vector3::new(0.0, 0.0, -1.0),
+    );
+
+    // Animating anything other than an instance is a mistake
+    scene.animation.instances[0].0 = 1;
+    assert!(scene.set_time(0.0).is_err());
+}
@@ -0,0 +1,397 @@
+//! Checks where the cameras send the rays for known positions on the film.
+
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+use rustrt::*;
+
+mod common;
+use common::assert_near;
+
+const PI: Float = std::f64::consts::PI as Float;
+
+fn ray(camera: &dyn Camera, s: Float, t: Float) -> Option<Ray> {
+    let mut rng = SmallRng::seed_from_u64(0);
+    camera
+        .get_ray(s, t, &mut rng)
+        .map(|camera_ray| camera_ray.ray)
+}
+
+fn direction(camera: &dyn Camera, s: Float, t: Float) -> Vector3 {
+    Vector3::unit_vector(ray(camera, s, t).unwrap().direction)
+}
+
+/// Looks down -z from (0, 0, 5) with y up, so that right is +x
+fn look_from() -> Point3 {
+    Point3::new(0.0, 0.0, 5.0)
+}
+
+fn look_at() -> Point3 {
+    Point3::new(0.0, 0.0, 0.0)
+}
+
+fn up() -> Vector3 {
+    Vector3::new(0.0, 1.0, 0.0)
+}
+
+#[test]
+fn orthographic_rays_are_parallel_and_cover_the_height() {
+    let camera = OrthographicCamera::new(look_from(), look_at(), up(), 4.0, 2.0).unwrap();
+
+    let centre = ray(&camera, 0.5, 0.5).unwrap();
+    assert_near(centre.origin, look_from());
+    assert_near(
+        Vector3::unit_vector(centre.direction),
+        Vector3::new(0.0, 0.0, -1.0),
+    );
+
+    // 4 high and twice as wide, so the corners are 4 and 2 units off the centre
+    let corner = ray(&camera, 0.0, 0.0).unwrap();
+    assert_near(corner.origin, Point3::new(-4.0, -2.0, 5.0));
+    assert_near(
+        Vector3::unit_vector(corner.direction),
+        Vector3::new(0.0, 0.0, -1.0),
+    );
+    assert_near(
+        ray(&camera, 1.0, 1.0).unwrap().origin,
+        Point3::new(4.0, 2.0, 5.0),
+    );
+}
+
+#[test]
+fn fisheye_angles_grow_from_the_centre_of_the_image_circle() {
+    for &projection in &[FisheyeProjection::Equidistant, FisheyeProjection::Equisolid] {
+        let camera =
+            FisheyeCamera::new(look_from(), look_at(), up(), 180.0, 1.0, projection).unwrap();
+
+        assert_near(direction(&camera, 0.5, 0.5), Vector3::new(0.0, 0.0, -1.0));
+
+        // The edges of the circle are half the field of view away from the view direction
+        assert_near(direction(&camera, 1.0, 0.5), Vector3::new(1.0, 0.0, 0.0));
+        assert_near(direction(&camera, 0.5, 1.0), Vector3::new(0.0, 1.0, 0.0));
+        assert_near(direction(&camera, 0.0, 0.5), Vector3::new(-1.0, 0.0, 0.0));
+
+        // The corners are outside of the circle
+        assert!(ray(&camera, 0.0, 0.0).is_none());
+        assert!(ray(&camera, 1.0, 1.0).is_none());
+    }
+
+    // Halfway to the edge is a quarter of the field of view for equidistant, less for equisolid
+    let equidistant = FisheyeCamera::new(
+        look_from(),
+        look_at(),
+        up(),
+        180.0,
+        1.0,
+        FisheyeProjection::Equidistant,
+    )
+    .unwrap();
+    let angle = |camera: &FisheyeCamera| Float::acos(-direction(camera, 0.75, 0.5).z).to_degrees();
+    assert!((angle(&equidistant) - 45.0).abs() < 1e-2);
+
+    let equisolid = FisheyeCamera::new(
+        look_from(),
+        look_at(),
+        up(),
+        180.0,
+        1.0,
+        FisheyeProjection::Equisolid,
+    )
+    .unwrap();
+    let expected = 2.0 * Float::asin(0.5 * Float::sin(PI / 4.0)).to_degrees();
+    assert!((angle(&equisolid) - expected).abs() < 1e-2);
+}
+
+#[test]
+fn equirectangular_wraps_around_at_the_edges() {
+    let camera = EquirectangularCamera::new(look_from(), look_at(), up()).unwrap();
+
+    assert_near(direction(&camera, 0.5, 0.5), Vector3::new(0.0, 0.0, -1.0));
+    assert_near(direction(&camera, 0.75, 0.5), Vector3::new(1.0, 0.0, 0.0));
+    assert_near(direction(&camera, 0.25, 0.5), Vector3::new(-1.0, 0.0, 0.0));
+
+    // Both edges are at ±π, straight behind the camera
+    assert_near(direction(&camera, 0.0, 0.5), Vector3::new(0.0, 0.0, 1.0));
+    assert_near(direction(&camera, 1.0, 0.5), Vector3::new(0.0, 0.0, 1.0));
+
+    // The top and bottom rows are the poles
+    assert_near(direction(&camera, 0.3, 1.0), Vector3::new(0.0, 1.0, 0.0));
+    assert_near(direction(&camera, 0.8, 0.0), Vector3::new(0.0, -1.0, 0.0));
+
+    assert_near(ray(&camera, 0.1, 0.7).unwrap().origin, look_from());
+}
+
+/// A thin lens camera at the origin looking down -z with an aperture radius of 1 focused 10 away,
+/// so where a ray leaves the lens is the point the aperture sampled
+fn thin_lens(lens: ThinLens) -> PerspectiveCamera {
+    PerspectiveCamera::new(
+        look_at(),
+        Point3::new(0.0, 0.0, -1.0),
+        up(),
+        40.0,
+        1.0,
+        2.0,
+        10.0,
+    )
+    .unwrap()
+    .with_thin_lens(lens)
+    .unwrap()
+}
+
+/// Where a ray crosses the plane through `point` with `normal`
+fn meet(ray: &Ray, point: Point3, normal: Vector3) -> Point3 {
+    let t = Vector3::dot(&(point - ray.origin), &normal) / Vector3::dot(&ray.direction, &normal);
+    ray.at(t)
+}
+
+#[test]
+fn polygonal_apertures_are_sampled_evenly_inside_the_polygon() {
+    let blades = 6;
+    let rotation: Float = 15.0;
+    let camera = thin_lens(ThinLens {
+        aperture: Aperture::Polygon { blades, rotation },
+        ..ThinLens::default()
+    });
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut per_blade = vec![0; blades];
+    let mut furthest: Float = 0.0;
+    let samples = 60_000;
+    for _ in 0..samples {
+        let p = camera.get_ray(0.5, 0.5, &mut rng).unwrap().ray.origin;
+        let radius = Float::hypot(p.x, p.y);
+        furthest = furthest.max(radius);
+
+        // Inside the polygon is at most the apothem along the middle of the blade it's in
+        let angle = (Float::atan2(p.y, p.x) - rotation.to_radians()).rem_euclid(2.0 * PI);
+        let sector = 2.0 * PI / blades as Float;
+        let blade = usize::min((angle / sector) as usize, blades - 1);
+        per_blade[blade] += 1;
+        let from_middle = angle - (blade as Float + 0.5) * sector;
+        assert!(
+            radius * Float::cos(from_middle) <= Float::cos(sector / 2.0) + 1e-4,
+            "{:?} is outside of the aperture",
+            p
+        );
+    }
+
+    // Out to the corners, and every blade as often as the others
+    assert!(furthest > 0.98);
+    for count in per_blade {
+        let expected = samples as Float / blades as Float;
+        assert!((count as Float - expected).abs() < 0.05 * expected);
+    }
+}
+
+#[test]
+fn aperture_masks_are_sampled_by_brightness() {
+    // Row by row from the top: the top left lets 1 through and the bottom right 3, the rest is black
+    let mask = ApertureMask::new(2, 2, &[1.0, 0.0, 0.0, 3.0]).unwrap();
+    let camera = thin_lens(ThinLens {
+        aperture: Aperture::Mask(mask),
+        ..ThinLens::default()
+    });
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let (mut top_left, mut bottom_right) = (0, 0);
+    let samples = 40_000;
+    for _ in 0..samples {
+        let p = camera.get_ray(0.5, 0.5, &mut rng).unwrap().ray.origin;
+        assert!(p.x.abs() <= 1.0 && p.y.abs() <= 1.0);
+        match (p.x < 0.0, p.y > 0.0) {
+            (true, true) => top_left += 1,
+            (false, false) => bottom_right += 1,
+            _ => panic!("{:?} is in a black part of the mask", p),
+        }
+    }
+    assert!((top_left as Float / samples as Float - 0.25).abs() < 0.01);
+    assert!((bottom_right as Float / samples as Float - 0.75).abs() < 0.01);
+
+    assert!(ApertureMask::new(2, 2, &[0.0; 4]).is_err());
+    assert!(ApertureMask::new(2, 2, &[1.0; 3]).is_err());
+    assert!(ApertureMask::new(1, 1, &[-1.0]).is_err());
+}
+
+#[test]
+fn tilted_lenses_focus_on_a_tilted_plane() {
+    let tilt: Float = 20.0;
+    let camera = thin_lens(ThinLens {
+        tilt,
+        ..ThinLens::default()
+    });
+    // Turned from facing the camera towards its up direction, through the usual focus point
+    let normal = Vector3::unit_vector(Vector3::new(0.0, tilt.to_radians().tan(), 1.0));
+    let focus_point = Point3::new(0.0, 0.0, -10.0);
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut distances = vec![];
+    for &(s, t) in &[(0.5, 0.0), (0.5, 0.5), (0.5, 1.0), (0.1, 0.3), (0.9, 0.8)] {
+        // Rays through different parts of the lens only meet on the plane
+        let a = meet(
+            &camera.get_ray(s, t, &mut rng).unwrap().ray,
+            focus_point,
+            normal,
+        );
+        for _ in 0..10 {
+            let b = meet(
+                &camera.get_ray(s, t, &mut rng).unwrap().ray,
+                focus_point,
+                normal,
+            );
+            assert_near(a, b);
+        }
+        distances.push(-a.z);
+    }
+
+    // Closer at the bottom of the image than at the top
+    assert!(distances[0] < distances[1] && distances[1] < distances[2]);
+}
+
+#[test]
+fn chromatic_aberration_focuses_each_channel_at_its_own_distance() {
+    let aberration = 0.1;
+    let camera = thin_lens(ThinLens {
+        chromatic_aberration: aberration,
+        ..ThinLens::default()
+    });
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut sum = Color3::default();
+    let samples = 30_000;
+    for _ in 0..samples {
+        let camera_ray = camera.get_ray(0.5, 0.5, &mut rng).unwrap();
+        let weight = camera_ray.weight;
+        sum += weight;
+
+        // A single channel at three times the weight, red focused furthest and blue closest
+        let channel = (0..3).find(|&k| weight[k] != 0.0).unwrap();
+        assert_eq!(weight[channel], 3.0);
+        assert_eq!(weight.x + weight.y + weight.z, 3.0);
+        let distance = 10.0 * (1.0 + aberration * (1.0 - channel as Float));
+        let focus = meet(
+            &camera_ray.ray,
+            Point3::new(0.0, 0.0, -distance),
+            Vector3::new(0.0, 0.0, 1.0),
+        );
+        assert_near(focus, Point3::new(0.0, 0.0, -distance));
+    }
+
+    // White on average
+    let mean = sum / samples as Float;
+    assert!(
+        (mean - Color3::new(1.0, 1.0, 1.0)).length() < 0.05,
+        "{:?}",
+        mean
+    );
+}
+
+#[test]
+fn cats_eye_blocks_part_of_the_lens_towards_the_corners() {
+    let camera = thin_lens(ThinLens {
+        cats_eye: 1.0,
+        ..ThinLens::default()
+    });
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let blocked = |s: Float, t: Float, rng: &mut SmallRng| {
+        (0..1000)
+            .filter(|_| {
+                // Blocked rays still leave the camera so that the pixel's other outputs see the scene
+                let camera_ray = camera.get_ray(s, t, rng).unwrap();
+                camera_ray.weight.near_zero()
+            })
+            .count()
+    };
+
+    assert_eq!(blocked(0.5, 0.5, &mut rng), 0);
+    assert!(blocked(1.0, 1.0, &mut rng) > 100);
+}
+
+fn eyes(camera: &dyn Camera, rig: StereoRig) -> [Box<dyn Camera>; 2] {
+    camera.stereo_pair(&rig).unwrap()
+}
+
+#[test]
+fn off_axis_eyes_meet_at_the_convergence_distance() {
+    let camera = PerspectiveCamera::new(look_from(), look_at(), up(), 40.0, 1.5, 0.0, 5.0).unwrap();
+    let rig = StereoRig {
+        interocular_distance: 0.5,
+        convergence_distance: Some(8.0),
+    };
+    let [left, right] = eyes(&camera, rig);
+
+    assert_near(
+        ray(&*left, 0.5, 0.5).unwrap().origin,
+        Point3::new(-0.25, 0.0, 5.0),
+    );
+    assert_near(
+        ray(&*right, 0.5, 0.5).unwrap().origin,
+        Point3::new(0.25, 0.0, 5.0),
+    );
+
+    // The same point on both films sees the same point of the screen, but nowhere else
+    let screen = Point3::new(0.0, 0.0, -3.0);
+    let forward = Vector3::new(0.0, 0.0, 1.0);
+    for &(s, t) in &[(0.5, 0.5), (0.0, 0.0), (1.0, 0.3), (0.2, 0.9)] {
+        let (l, r) = (ray(&*left, s, t).unwrap(), ray(&*right, s, t).unwrap());
+        assert_near(meet(&l, screen, forward), meet(&r, screen, forward));
+
+        let closer = Point3::new(0.0, 0.0, 0.0);
+        assert!((meet(&l, closer, forward) - meet(&r, closer, forward)).length() > 0.1);
+    }
+
+    // Parallel eyes look the same way everywhere
+    let [left, right] = eyes(
+        &camera,
+        StereoRig {
+            interocular_distance: 0.5,
+            convergence_distance: None,
+        },
+    );
+    for &(s, t) in &[(0.5, 0.5), (0.0, 1.0)] {
+        assert_near(direction(&*left, s, t), direction(&*right, s, t));
+    }
+}
+
+#[test]
+fn omni_directional_eyes_sit_beside_the_view_ray() {
+    let camera = EquirectangularCamera::new(look_from(), look_at(), up()).unwrap();
+    let parallel = StereoRig {
+        interocular_distance: 0.5,
+        convergence_distance: None,
+    };
+    let [left, right] = eyes(&camera, parallel);
+
+    for &(s, t) in &[(0.5, 0.5), (0.1, 0.5), (0.8, 0.3), (0.3, 0.9)] {
+        let view = direction(&camera, s, t);
+        for (eye, side) in [(&left, -1.0), (&right, 1.0)] {
+            let ray = ray(&**eye, s, t).unwrap();
+            assert_near(Vector3::unit_vector(ray.direction), view);
+
+            // Half the distance between the eyes to the side, less towards the poles, and never along the ray
+            let offset = ray.origin - look_from();
+            let latitude = (t - 0.5) * PI;
+            assert!((offset.length() - 0.25 * Float::cos(latitude)).abs() < 1e-4);
+            assert!(Vector3::dot(&offset, &view).abs() < 1e-4);
+            // The right eye is to the right when looking along the ray
+            let right_of_view = Vector3::cross(&view, &up());
+            assert!(side * Vector3::dot(&offset, &right_of_view) > 0.0);
+        }
+    }
+
+    // Converged eyes see the same point at the convergence distance
+    let [left, right] = eyes(
+        &camera,
+        StereoRig {
+            interocular_distance: 0.5,
+            convergence_distance: Some(4.0),
+        },
+    );
+    for &(s, t) in &[(0.5, 0.5), (0.1, 0.5), (0.8, 0.3)] {
+        let target = look_from() + 4.0 * direction(&camera, s, t);
+        for eye in [&left, &right] {
+            let ray = ray(&**eye, s, t).unwrap();
+            let to_target = Vector3::unit_vector(target - ray.origin);
+            assert_near(Vector3::unit_vector(ray.direction), to_target);
+        }
+    }
+}
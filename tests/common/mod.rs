@@ -0,0 +1,47 @@
+//! Helpers shared by the integration tests. Each test file only uses some of them
+
+#![allow(dead_code)]
+
+use rustrt::*;
+use std::path::PathBuf;
+
+/// One grey diffuse material in a list of its own
+pub fn material() -> (MaterialList, MaterialId) {
+    let mut materials = MaterialList::default();
+    let material = materials.add(Material::from(ReflectanceModel::Diffuse(Diffuse {
+        albedo: Color3::new(0.5, 0.5, 0.5),
+    })));
+    (materials, material)
+}
+
+/// The nearest hit in `world` along a ray that starts just past `origin`
+pub fn hit(world: &HittableList, origin: Point3, direction: Vector3) -> Option<Interaction> {
+    let mut interaction = Interaction::default();
+    world
+        .hit(
+            &Ray { origin, direction },
+            0.001,
+            Float::INFINITY,
+            &mut interaction,
+        )
+        .then_some(interaction)
+}
+
+/// How far apart two vectors can be for `near` and `assert_near`. Points go through a few transforms,
+/// like a glTF node hierarchy, before they are compared, and their rounding in `Float` adds up
+pub const TOLERANCE: Float = 1e-3;
+
+pub fn near(a: Vector3, b: Vector3) -> bool {
+    (a - b).length() < TOLERANCE
+}
+
+pub fn assert_near(a: Vector3, b: Vector3) {
+    assert!(near(a, b), "{:?} != {:?}", a, b);
+}
+
+/// A directory of the test's own under the system's temporary one, named after `name`
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rustrt_{}", name));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,105 @@
+//! Denoises made up films and checks that noise goes away while edges in the albedo and normals stay
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use rustrt::*;
+
+const SIZE: usize = 32;
+const SAMPLES_PER_PIXEL: usize = 8;
+
+/// Each pixel averages noisy samples of `color` that are off by up to half of it,
+/// seeing `albedo` and `normal` at a depth of 5
+fn film(pixel: impl Fn(usize, usize) -> (Color3, Color3, Vector3)) -> Film {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut film = Film::new(SIZE, SIZE);
+
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let (color, albedo, normal) = pixel(x, y);
+            let aov = AovSample {
+                albedo,
+                normal,
+                depth: 5.0,
+                ..Default::default()
+            };
+
+            let mut pixel = Pixel::default();
+            for _ in 0..SAMPLES_PER_PIXEL {
+                let noise = 1.0 + rng.gen_range(-0.5..0.5);
+                pixel.add_sample(color * noise, &aov);
+            }
+            pixel.resolve();
+            film.set(x, y, pixel);
+        }
+    }
+
+    film
+}
+
+/// Root mean square error of the luminance of the pixels in columns `xs` against `expected`
+fn error(film: &Film, xs: std::ops::Range<usize>, expected: Float) -> Float {
+    let mut sum = 0.0;
+    let mut count = 0;
+    for y in 0..SIZE {
+        for x in xs.clone() {
+            sum += (film.get(x, y).color.luminance() - expected).powi(2);
+            count += 1;
+        }
+    }
+    Float::sqrt(sum / count as Float)
+}
+
+#[test]
+fn flat_noise_is_smoothed_out() {
+    let grey = Color3::new(0.5, 0.5, 0.5);
+    let mut film = film(|_, _| (grey, grey, Vector3::new(0.0, 0.0, 1.0)));
+
+    let noisy = error(&film, 0..SIZE, 0.5);
+    denoise::denoise(&mut film);
+    let denoised = error(&film, 0..SIZE, 0.5);
+
+    assert!(
+        denoised < noisy / 4.0,
+        "the error only went from {} to {}",
+        noisy,
+        denoised
+    );
+}
+
+#[test]
+fn edges_in_the_normals_and_albedo_are_kept() {
+    // Dark on the left and bright on the right, only told apart by the normal
+    let mut normal_edge = film(|x, _| {
+        let white = Color3::new(1.0, 1.0, 1.0);
+        if x < SIZE / 2 {
+            (0.2 * white, white, Vector3::new(0.0, 0.0, 1.0))
+        } else {
+            (0.8 * white, white, Vector3::new(1.0, 0.0, 0.0))
+        }
+    });
+    // The same, only told apart by the albedo
+    let mut albedo_edge = film(|x, _| {
+        let grey = if x < SIZE / 2 { 0.2 } else { 0.8 };
+        let grey = Color3::new(grey, grey, grey);
+        (grey, grey, Vector3::new(0.0, 0.0, 1.0))
+    });
+
+    for film in [&mut normal_edge, &mut albedo_edge] {
+        let left = SIZE / 2 - 2..SIZE / 2;
+        let right = SIZE / 2..SIZE / 2 + 2;
+        let noisy = [
+            error(film, left.clone(), 0.2),
+            error(film, right.clone(), 0.8),
+        ];
+        denoise::denoise(film);
+
+        // Blurring across the edge would pull the columns next to it towards the other side
+        let denoised = [error(film, left, 0.2), error(film, right, 0.8)];
+        assert!(
+            denoised[0] < noisy[0] / 2.0 && denoised[1] < noisy[1] / 2.0,
+            "the columns next to the edge went from being off by {:?} to {:?}",
+            noisy,
+            denoised
+        );
+    }
+}
@@ -0,0 +1,374 @@
+//! Writes the same little scene as a `.gltf` with its buffer and texture in files next to it and as a `.glb`
+//! with everything inside, then checks that the node transforms, materials, textures, camera and lights come through
+
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+use rustrt::*;
+use std::path::PathBuf;
+
+mod common;
+use common::{near, temp_dir};
+
+/// A quad from -1 to 1 in the xy plane facing +z, with uvs running down from its top left corner
+/// and a colour per corner
+const POSITIONS: [[f32; 3]; 4] = [
+    [-1.0, -1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+];
+const UVS: [[f32; 2]; 4] = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+const COLORS: [[f32; 3]; 4] = [
+    [1.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0],
+    [0.0, 0.0, 1.0],
+    [1.0, 1.0, 1.0],
+];
+const INDICES: [u16; 6] = [0, 1, 2, 0, 2, 3];
+/// Offsets of the positions, normals, uvs, colours and indices in the buffer, and its length
+const OFFSETS: [usize; 6] = [0, 48, 96, 128, 176, 188];
+
+/// 2x2 sRGB pixels, top row first: red, green, blue and a grey of 128
+const TEXTURE: [u8; 12] = [255, 0, 0, 0, 255, 0, 0, 0, 255, 128, 128, 128];
+
+fn buffer() -> Vec<u8> {
+    let mut bytes = vec![];
+    let floats = POSITIONS
+        .iter()
+        .flatten()
+        .chain([0.0, 0.0, 1.0].iter().cycle().take(12))
+        .chain(UVS.iter().flatten())
+        .chain(COLORS.iter().flatten());
+    for value in floats {
+        bytes.extend(value.to_le_bytes());
+    }
+    for index in INDICES {
+        bytes.extend(index.to_le_bytes());
+    }
+    assert_eq!(bytes.len(), OFFSETS[5]);
+    bytes
+}
+
+fn png() -> Vec<u8> {
+    let mut bytes = vec![];
+    image::codecs::png::PngEncoder::new(&mut bytes)
+        .encode(&TEXTURE, 2, 2, image::ColorType::Rgb8)
+        .unwrap();
+    bytes
+}
+
+/// Three quads under a node 5 units down -z: a textured diffuse one scaled by 2, a metal one with vertex colours
+/// 10 units to the right and a glass one 10 units to the left. A camera 1 unit up looks down -z,
+/// and there's a point light, a spot light pointing down and a directional light
+fn json(buffer: &str, image: &str, texture_view: &str, camera: bool) -> String {
+    let [positions, normals, uvs, colors, indices, length] = OFFSETS;
+    let view = |offset: usize, end: usize| {
+        format!(
+            r#"{{"buffer": 0, "byteOffset": {}, "byteLength": {}}}"#,
+            offset,
+            end - offset
+        )
+    };
+    let camera_node = match camera {
+        true => r#", {"camera": 0, "translation": [0, 1, 0]}"#,
+        false => r#", {"name": "no camera"}"#,
+    };
+    format!(
+        r#"{{
+    "asset": {{"version": "2.0"}},
+    "extensionsUsed": ["KHR_lights_punctual", "KHR_materials_transmission", "KHR_materials_ior"],
+    "scene": 0,
+    "scenes": [{{"nodes": [0, 4, 5, 6, 7, 8]}}],
+    "nodes": [
+        {{"translation": [0, 0, -5], "children": [1, 2, 3]}},
+        {{"mesh": 0, "scale": [2, 2, 2]}},
+        {{"mesh": 1, "translation": [10, 0, 0]}},
+        {{"mesh": 2, "matrix": [1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, -10, 0, 0, 1]}}
+        {camera_node},
+        {{"translation": [0, 3, 0], "extensions": {{"KHR_lights_punctual": {{"light": 0}}}}}},
+        {{"translation": [0, 4, 0], "rotation": [-0.70710678, 0, 0, 0.70710678],
+          "extensions": {{"KHR_lights_punctual": {{"light": 1}}}}}},
+        {{"extensions": {{"KHR_lights_punctual": {{"light": 2}}}}}},
+        {{"mesh": 0, "scale": [0, 0, 0]}}
+    ],
+    "meshes": [
+        {{"primitives": [{{"attributes": {{"POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2}}, "indices": 4, "material": 0}}]}},
+        {{"primitives": [{{"attributes": {{"POSITION": 0, "NORMAL": 1, "COLOR_0": 3}}, "indices": 4, "material": 1}}]}},
+        {{"primitives": [{{"attributes": {{"POSITION": 0}}, "indices": 4, "material": 2}}]}}
+    ],
+    "materials": [
+        {{"pbrMetallicRoughness": {{"baseColorTexture": {{"index": 0}}, "metallicFactor": 0}}}},
+        {{"pbrMetallicRoughness": {{"baseColorFactor": [0.9, 0.5, 0.1, 1], "metallicFactor": 1, "roughnessFactor": 0.2}}}},
+        {{"extensions": {{"KHR_materials_transmission": {{"transmissionFactor": 1}}, "KHR_materials_ior": {{"ior": 1.33}}}}}}
+    ],
+    "textures": [{{"source": 0}}],
+    "images": [{image}],
+    "cameras": [{{"type": "perspective", "perspective": {{"yfov": 0.5, "znear": 0.1}}}}],
+    "extensions": {{"KHR_lights_punctual": {{"lights": [
+        {{"type": "point", "color": [1, 0.5, 0.25], "intensity": 683}},
+        {{"type": "spot", "intensity": 1366, "spot": {{"innerConeAngle": 0.2, "outerConeAngle": 0.4}}}},
+        {{"type": "directional", "intensity": 68.3}}
+    ]}}}},
+    "accessors": [
+        {{"bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3", "min": [-1, -1, 0], "max": [1, 1, 0]}},
+        {{"bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC3"}},
+        {{"bufferView": 2, "componentType": 5126, "count": 4, "type": "VEC2"}},
+        {{"bufferView": 3, "componentType": 5126, "count": 4, "type": "VEC3"}},
+        {{"bufferView": 4, "componentType": 5123, "count": 6, "type": "SCALAR"}}
+    ],
+    "bufferViews": [{}, {}, {}, {}, {}{texture_view}],
+    "buffers": [{buffer}]
+}}"#,
+        view(positions, normals),
+        view(normals, uvs),
+        view(uvs, colors),
+        view(colors, indices),
+        view(indices, length),
+        camera_node = camera_node,
+        image = image,
+        texture_view = texture_view,
+        buffer = buffer,
+    )
+}
+
+/// With the buffer and texture in their own files
+fn write_gltf(name: &str, camera: bool) -> PathBuf {
+    let dir = temp_dir(&format!("gltf_{}", name));
+    std::fs::write(dir.join("scene.bin"), buffer()).unwrap();
+    std::fs::write(dir.join("texture.png"), png()).unwrap();
+    let buffer = format!(r#"{{"byteLength": {}, "uri": "scene.bin"}}"#, OFFSETS[5]);
+    let json = json(&buffer, r#"{"uri": "texture.png"}"#, "", camera);
+
+    let path = dir.join("scene.gltf");
+    std::fs::write(&path, json).unwrap();
+    path
+}
+
+/// With the texture in the binary chunk after the geometry
+fn write_glb(name: &str) -> PathBuf {
+    let mut binary = buffer();
+    let png = png();
+    let texture_view = format!(
+        r#", {{"buffer": 0, "byteOffset": {}, "byteLength": {}}}"#,
+        binary.len(),
+        png.len()
+    );
+    binary.extend(&png);
+    while !binary.len().is_multiple_of(4) {
+        binary.push(0);
+    }
+    let buffer = format!(r#"{{"byteLength": {}}}"#, binary.len());
+    let mut json = json(
+        &buffer,
+        r#"{"bufferView": 5, "mimeType": "image/png"}"#,
+        &texture_view,
+        true,
+    )
+    .into_bytes();
+    while !json.len().is_multiple_of(4) {
+        json.push(b' ');
+    }
+
+    let mut glb = b"glTF".to_vec();
+    glb.extend(2u32.to_le_bytes());
+    glb.extend((12 + 8 + json.len() as u32 + 8 + binary.len() as u32).to_le_bytes());
+    glb.extend((json.len() as u32).to_le_bytes());
+    glb.extend(b"JSON");
+    glb.extend(json);
+    glb.extend((binary.len() as u32).to_le_bytes());
+    glb.extend(b"BIN\0");
+    glb.extend(binary);
+
+    let path = temp_dir(&format!("gltf_{}", name)).join("scene.glb");
+    std::fs::write(&path, glb).unwrap();
+    path
+}
+
+/// Every ray the tests shoot at the scene should hit something
+fn hit(scene: &Scene, origin: Point3, direction: Vector3) -> Interaction {
+    common::hit(&scene.world, origin, direction)
+        .unwrap_or_else(|| panic!("{:?} towards {:?} should hit", origin, direction))
+}
+
+fn check_scene(scene: &Scene) {
+    // The textured quad spans -2 to 2 at z = -5. Each corner is the middle of one of the texture's pixels
+    let origin = Point3::new(0.0, 0.0, 0.0);
+    let top_left = hit(scene, origin, Vector3::new(-1.0, 1.0, -5.0));
+    assert!(near(top_left.p, Point3::new(-1.0, 1.0, -5.0)));
+    assert!(near(top_left.normal, Vector3::new(0.0, 0.0, 1.0)));
+    let material = &scene.materials[top_left.material.unwrap()];
+    assert!(matches!(
+        material.reflectance_model,
+        ReflectanceModel::Diffuse(_)
+    ));
+    assert!(near(material.albedo(&top_left), Color3::new(1.0, 0.0, 0.0)));
+    let top_right = hit(scene, origin, Vector3::new(1.0, 1.0, -5.0));
+    assert!(near(
+        material.albedo(&top_right),
+        Color3::new(0.0, 1.0, 0.0)
+    ));
+    // sRGB 128 is a linear 0.2158
+    let bottom_right = hit(scene, origin, Vector3::new(1.0, -1.0, -5.0));
+    let grey = material.albedo(&bottom_right);
+    assert!(
+        near(grey, Color3::new(0.2158, 0.2158, 0.2158)),
+        "{:?}",
+        grey
+    );
+
+    let down = Vector3::new(0.0, 0.0, -1.0);
+    let metal = hit(scene, Point3::new(10.5, 0.0, 0.0), down);
+    assert!((metal.t - 5.0).abs() < 1e-4);
+    assert!(near(metal.color.unwrap(), Color3::new(0.25, 0.25, 0.5)));
+    match &scene.materials[metal.material.unwrap()] {
+        Material {
+            reflectance_model: ReflectanceModel::Metal(Metal { albedo, fuzziness }),
+            texture: Some(Texture::VertexColor),
+        } => {
+            assert!(near(*albedo, Color3::new(0.9, 0.5, 0.1)));
+            assert_eq!(*fuzziness, 0.2);
+        }
+        _ => panic!("the second quad should be metal tinted by its vertex colours"),
+    }
+
+    let glass = hit(scene, Point3::new(-10.0, 0.0, 0.0), down);
+    assert!(near(glass.p, Point3::new(-10.0, 0.0, -5.0)));
+    match scene.materials[glass.material.unwrap()].reflectance_model {
+        ReflectanceModel::Dielectric(Dielectric {
+            index_of_refraction,
+        }) => assert_eq!(index_of_refraction, 1.33),
+        _ => panic!("the third quad should be glass"),
+    }
+
+    // Lights are in lumens, watts are 683 of them
+    let lights: Vec<&Light> = scene.lights.iter().collect();
+    assert_eq!(lights.len(), 3);
+    match lights[0] {
+        Light::Point(light) => {
+            assert!(near(light.position, Point3::new(0.0, 3.0, 0.0)));
+            assert!(near(light.intensity, Color3::new(1.0, 0.5, 0.25)));
+        }
+        _ => panic!("the first light should be a point light"),
+    }
+    match lights[1] {
+        Light::Spot(light) => {
+            assert!(near(light.position, Point3::new(0.0, 4.0, 0.0)));
+            assert!(near(light.direction, Vector3::new(0.0, -1.0, 0.0)));
+            assert!(near(light.intensity, Color3::new(2.0, 2.0, 2.0)));
+        }
+        _ => panic!("the second light should be a spot light"),
+    }
+    match lights[2] {
+        Light::Directional(light) => {
+            assert!(near(light.direction, Vector3::new(0.0, 0.0, -1.0)));
+            assert!(near(light.radiance, Color3::new(0.1, 0.1, 0.1)));
+        }
+        _ => panic!("the third light should be a directional light"),
+    }
+}
+
+fn center_ray(scene: &Scene) -> Ray {
+    let mut rng = SmallRng::seed_from_u64(0);
+    scene.camera.get_ray(0.5, 0.5, &mut rng).unwrap().ray
+}
+
+#[test]
+fn reads_gltf_with_external_files() {
+    let scene = read_gltf(&write_gltf("external", true), 1.5).unwrap();
+    check_scene(&scene);
+
+    let ray = center_ray(&scene);
+    assert!(near(ray.origin, Point3::new(0.0, 1.0, 0.0)));
+    assert!(near(
+        Vector3::unit_vector(ray.direction),
+        Vector3::new(0.0, 0.0, -1.0)
+    ));
+}
+
+#[test]
+fn reads_glb() {
+    let scene = read_gltf(&write_glb("binary"), 1.5).unwrap();
+    check_scene(&scene);
+    assert!(near(center_ray(&scene).origin, Point3::new(0.0, 1.0, 0.0)));
+}
+
+#[test]
+fn makes_up_a_camera_that_sees_everything() {
+    let scene = read_gltf(&write_gltf("no_camera", false), 1.5).unwrap();
+
+    // The quads span -11 to 11 in x, so the camera looks at the middle from in front
+    let ray = center_ray(&scene);
+    assert!(ray.origin.z > 11.0);
+    assert!(near(
+        Vector3::unit_vector(ray.direction),
+        Vector3::new(0.0, 0.0, -1.0)
+    ));
+    let mut rng = SmallRng::seed_from_u64(0);
+    for (s, t) in [(0.05, 0.5), (0.95, 0.5)] {
+        let ray = scene.camera.get_ray(s, t, &mut rng).unwrap().ray;
+        let x = ray.at((ray.origin.z + 5.0) / -ray.direction.z).x;
+        assert!(x.abs() > 11.0, "the edge of the view is at x = {}", x);
+    }
+}
+
+#[test]
+fn rejects_broken_files() {
+    let missing = temp_dir("gltf_missing").join("missing.gltf");
+    assert!(read_gltf(&missing, 1.5).is_err());
+
+    let path = write_gltf("broken", true);
+    let json = std::fs::read_to_string(&path).unwrap();
+    let broken = [
+        json.replace(r#""version": "2.0""#, r#""version": 2"#),
+        json.replace(
+            r#""indices": 4, "material": 0"#,
+            r#""indices": 9, "material": 0"#,
+        ),
+        json.replace("texture.png", "missing.png"),
+        json[..json.len() / 2].to_string(),
+    ];
+    for text in broken {
+        std::fs::write(&path, &text).unwrap();
+        assert!(
+            read_gltf(&path, 1.5).is_err(),
+            "{} should be rejected",
+            text
+        );
+    }
+}
+
+#[test]
+fn reads_the_uv_set_and_wrap_modes_of_the_texture() {
+    let path = write_gltf("tex_coord", true);
+    let json = std::fs::read_to_string(&path)
+        .unwrap()
+        .replace(r#""TEXCOORD_0": 2"#, r#""TEXCOORD_1": 2"#)
+        .replace(
+            r#""baseColorTexture": {"index": 0}"#,
+            r#""baseColorTexture": {"index": 0, "texCoord": 1}"#,
+        )
+        .replace(
+            r#""textures": [{"source": 0}]"#,
+            r#""textures": [{"source": 0, "sampler": 0}],
+    "samplers": [{"wrapS": 33071, "wrapT": 33648}]"#,
+        );
+    std::fs::write(&path, json).unwrap();
+    let scene = read_gltf(&path, 1.5).unwrap();
+    check_scene(&scene);
+
+    let top_left = hit(
+        &scene,
+        Point3::new(0.0, 0.0, 0.0),
+        Vector3::new(-1.0, 1.0, -5.0),
+    );
+    let image = match &scene.materials[top_left.material.unwrap()].texture {
+        Some(Texture::Image(image)) => image,
+        _ => panic!("the first quad should only have an image texture"),
+    };
+    // Clamped left of the red pixel, and mirrored above it
+    let red = Color3::new(1.0, 0.0, 0.0);
+    assert!(near(image.lookup([-1.0, 0.25]), red));
+    assert!(near(image.lookup([0.25, -0.25]), red));
+    // And below the bottom row it's the bottom row again
+    assert!(near(image.lookup([0.25, 1.25]), Color3::new(0.0, 0.0, 1.0)));
+}
@@ -0,0 +1,204 @@
+//! Renders small canonical scenes and compares them against the reference images in `tests/golden`.
+//!
+//! The renders are deterministic, but the tolerances are loose enough that a change to how samples are drawn
+//! (which only changes the noise) passes while a change to what the images converge to fails.
+//! After an intended change to the look, write new references with `UPDATE_GOLDEN=1 cargo test --test golden`.
+
+use rustrt::*;
+use std::path::{Path, PathBuf};
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 48;
+const SAMPLES_PER_PIXEL: usize = 64;
+
+/// Root mean square error of the 8 bit sRGB values, scaled to 0 to 1.
+/// Mostly measures noise, so it only catches gross errors
+const MAX_RMSE: f64 = 0.04;
+/// Mean of the perceptual error below, 0 to 1.
+/// Rendering with another seed gives at most 0.01 and making every diffuse material 10% darker at least 0.02
+const MAX_PERCEPTUAL_ERROR: f64 = 0.015;
+
+fn settings() -> RenderSettings {
+    RenderSettings {
+        width: WIDTH,
+        height: HEIGHT,
+        samples_per_pixel: SAMPLES_PER_PIXEL,
+        num_threads: 4,
+        ..RenderSettings::default()
+    }
+}
+
+fn reference_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name))
+}
+
+fn check(name: &str, scene: fn(Float) -> Result<Scene>) {
+    let settings = settings();
+    let scene = scene(settings.aspect_ratio()).unwrap();
+    let film = render(&scene, &settings).unwrap();
+    let reference_path = reference_path(name);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        film.write_png(&reference_path).unwrap();
+        return;
+    }
+
+    let reference = image::open(&reference_path)
+        .unwrap_or_else(|error| {
+            panic!(
+                "couldn't read {}, create it with UPDATE_GOLDEN=1: {}",
+                reference_path.display(),
+                error
+            )
+        })
+        .to_rgba8();
+    assert_eq!(
+        (reference.width() as usize, reference.height() as usize),
+        (WIDTH, HEIGHT),
+        "{} has the wrong size",
+        reference_path.display()
+    );
+
+    let rendered = film.to_rgba8();
+    let reference = reference.into_raw();
+
+    let rmse = rmse(&rendered, &reference);
+    let perceptual = perceptual_error(&rendered, &reference);
+
+    if rmse > MAX_RMSE || perceptual > MAX_PERCEPTUAL_ERROR {
+        let failure = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.png", name));
+        film.write_png(&failure).unwrap();
+        panic!(
+            "{} doesn't match its reference: RMSE {:.4} (max {}), perceptual error {:.4} (max {}), render written to {}",
+            name,
+            rmse,
+            MAX_RMSE,
+            perceptual,
+            MAX_PERCEPTUAL_ERROR,
+            failure.display()
+        );
+    }
+}
+
+fn rmse(a: &[u8], b: &[u8]) -> f64 {
+    let (sum, count) = a
+        .chunks(4)
+        .zip(b.chunks(4))
+        .flat_map(|(a, b)| (0..3).map(move |k| (a[k] as f64 - b[k] as f64) / 255.0))
+        .fold((0.0, 0), |(sum, count), difference| {
+            (sum + difference * difference, count + 1)
+        });
+
+    (sum / count as f64).sqrt()
+}
+
+/// A simplified take on FLIP: both images are blurred slightly, like the eye does at a normal viewing distance,
+/// and compared per pixel with the HyAB distance in CIELAB, which is then normalised to 0 to 1 and averaged.
+fn perceptual_error(a: &[u8], b: &[u8]) -> f64 {
+    let a = blur(&to_lab(a));
+    let b = blur(&to_lab(b));
+
+    // HyAB distance between black and white
+    const MAX_DISTANCE: f64 = 100.0;
+
+    let sum: f64 = a
+        .iter()
+        .zip(&b)
+        .map(|(a, b)| {
+            let hy_ab =
+                (a[0] - b[0]).abs() + ((a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt();
+            f64::min(hy_ab / MAX_DISTANCE, 1.0)
+        })
+        .sum();
+
+    sum / a.len() as f64
+}
+
+fn to_lab(image: &[u8]) -> Vec<[f64; 3]> {
+    let linear = |value: u8| {
+        let value = value as f64 / 255.0;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let f = |t: f64| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            t * 24389.0 / 27.0 / 116.0 + 16.0 / 116.0
+        }
+    };
+
+    image
+        .chunks(4)
+        .map(|pixel| {
+            let (r, g, b) = (linear(pixel[0]), linear(pixel[1]), linear(pixel[2]));
+            // sRGB to XYZ relative to the D65 white point
+            let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.9505;
+            let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+            let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.089;
+
+            [
+                116.0 * f(y) - 16.0,
+                500.0 * (f(x) - f(y)),
+                200.0 * (f(y) - f(z)),
+            ]
+        })
+        .collect()
+}
+
+fn blur(image: &[[f64; 3]]) -> Vec<[f64; 3]> {
+    const GAUSSIAN: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+    let mut blurred = vec![[0.0; 3]; image.len()];
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let mut sum = [0.0; 3];
+            let mut sum_weight = 0.0;
+            for (j, &gy) in GAUSSIAN.iter().enumerate() {
+                for (i, &gx) in GAUSSIAN.iter().enumerate() {
+                    let (qx, qy) = (x as isize + i as isize - 2, y as isize + j as isize - 2);
+                    if qx < 0 || qy < 0 || qx >= WIDTH as isize || qy >= HEIGHT as isize {
+                        continue;
+                    }
+                    let q = image[qy as usize * WIDTH + qx as usize];
+                    for k in 0..3 {
+                        sum[k] += gx * gy * q[k];
+                    }
+                    sum_weight += gx * gy;
+                }
+            }
+            blurred[y * WIDTH + x] = [
+                sum[0] / sum_weight,
+                sum[1] / sum_weight,
+                sum[2] / sum_weight,
+            ];
+        }
+    }
+
+    blurred
+}
+
+#[test]
+fn cornell_box_matches_reference() {
+    check("cornell_box", cornell_box);
+}
+
+#[test]
+fn glass_sphere_matches_reference() {
+    check("glass_sphere", glass_sphere);
+}
+
+#[test]
+fn metal_spheres_matches_reference() {
+    check("metal_spheres", metal_spheres);
+}
+
+#[test]
+fn random_spheres_matches_reference() {
+    check("random_spheres", random_spheres);
+}
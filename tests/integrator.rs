@@ -0,0 +1,286 @@
+//! Renders scenes whose exact result is known and checks that `ray_color` converges to it.
+//!
+//! Samples are traced one at a time with `trace_sample` so that the expected value can be compared against the
+//! mean with a bound derived from the samples' own variance, rather than against a fixed tolerance.
+
+use rustrt::*;
+
+const WIDTH: usize = 16;
+const HEIGHT: usize = 12;
+const SAMPLES_PER_PIXEL: usize = 64;
+
+/// How many standard errors the mean may be away from the exact value.
+/// The samples are deterministic, so this only has to be wide enough for the seeds in use
+const MAX_STANDARD_ERRORS: f64 = 4.0;
+
+fn settings() -> RenderSettings {
+    RenderSettings {
+        width: WIDTH,
+        height: HEIGHT,
+        samples_per_pixel: SAMPLES_PER_PIXEL,
+        // Deep enough that cutting paths off is far below the noise, so only Russian roulette ends them
+        max_depth: 256,
+        // The white furnace checks that Russian roulette ended some paths
+        collect_stats: true,
+        ..RenderSettings::default()
+    }
+}
+
+fn camera(look_from: Point3, look_at: Point3) -> PerspectiveCamera {
+    PerspectiveCamera::new(
+        look_from,
+        look_at,
+        Vector3::new(0.0, 1.0, 0.0),
+        40.0,
+        WIDTH as Float / HEIGHT as Float,
+        0.0,
+        1.0,
+    )
+    .unwrap()
+}
+
+fn diffuse(materials: &mut MaterialList, albedo: Color3) -> MaterialId {
+    materials.add(Material::from(ReflectanceModel::Diffuse(Diffuse {
+        albedo,
+    })))
+}
+
+fn sphere(world: &mut HittableList, position: Point3, radius: Float, material: MaterialId) {
+    world.add(Hittable::Sphere(Sphere {
+        position,
+        radius,
+        material,
+    }));
+}
+
+/// Every sample of every pixel, in no particular order
+fn trace(scene: &Scene) -> Vec<Color3> {
+    let settings = settings();
+    let mut samples = Vec::with_capacity(WIDTH * HEIGHT * SAMPLES_PER_PIXEL);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            for sample in 0..SAMPLES_PER_PIXEL {
+                let mut aov = AovSample::default();
+                samples.push(trace_sample(scene, &settings, x, y, sample, &mut aov, None));
+            }
+        }
+    }
+    samples
+}
+
+/// Checks that the mean of every channel of `samples` is within the statistical bound of `expected`
+fn assert_converges_to(name: &str, samples: &[Color3], expected: Color3) {
+    for channel in 0..3 {
+        let n = samples.len() as f64;
+        let mean = samples.iter().map(|s| s[channel] as f64).sum::<f64>() / n;
+        let variance = samples
+            .iter()
+            .map(|s| (s[channel] as f64 - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1.0);
+        let standard_error = (variance / n).sqrt();
+
+        // The float error of the path throughput bounds it when there is no noise at all
+        let bound = f64::max(MAX_STANDARD_ERRORS * standard_error, 1e-4);
+        let expected = expected[channel] as f64;
+        assert!(
+            (mean - expected).abs() <= bound,
+            "{}: channel {} converges to {:.5} instead of {:.5}, the bound is {:.5} with a standard error of {:.5}",
+            name,
+            channel,
+            mean,
+            expected,
+            bound,
+            standard_error
+        );
+    }
+}
+
+/// White materials of every kind in a white environment: whatever the path, the light can only be
+/// reflected and never absorbed, so the whole image must be 1.
+/// The spheres overlap and touch so that paths bounce between them long enough for Russian roulette.
+#[test]
+fn white_furnace() {
+    let mut materials = MaterialList::default();
+    let mut world = HittableList::default();
+
+    let white = diffuse(&mut materials, Color3::new(1.0, 1.0, 1.0));
+    let mirror = materials.add(Material::from(ReflectanceModel::Metal(Metal {
+        albedo: Color3::new(1.0, 1.0, 1.0),
+        fuzziness: 0.0,
+    })));
+    let glass = materials.add(Material::from(ReflectanceModel::Dielectric(Dielectric {
+        index_of_refraction: 1.5,
+    })));
+
+    sphere(&mut world, Point3::new(0.0, -100.5, 0.0), 100.0, white);
+    sphere(&mut world, Point3::new(0.0, 0.0, 0.0), 0.5, white);
+    sphere(&mut world, Point3::new(0.9, 0.0, 0.0), 0.5, white);
+    sphere(&mut world, Point3::new(-0.9, 0.0, 0.0), 0.5, mirror);
+    sphere(&mut world, Point3::new(0.4, 0.0, 0.8), 0.4, glass);
+
+    let mut scene = Scene::new(
+        world,
+        materials,
+        LightList::default(),
+        camera(Point3::new(0.0, 1.5, 3.0), Point3::new(0.0, 0.0, 0.0)),
+    )
+    .unwrap();
+    scene.environment = Environment::Constant(Color3::new(1.0, 1.0, 1.0));
+
+    // trace_sample only counts on threads that turned statistics on
+    stats::set_enabled(true);
+    stats::take();
+    let samples = trace(&scene);
+    let stats = stats::take();
+    assert!(
+        stats.russian_roulette_terminations > 0,
+        "no path was long enough for Russian roulette"
+    );
+
+    assert_converges_to("white furnace", &samples, Color3::new(1.0, 1.0, 1.0));
+}
+
+/// A lone convex diffuse sphere can't see itself, so every path either misses it and sees the sky,
+/// or hits it once and sees the sky attenuated by the albedo, without any noise
+#[test]
+fn diffuse_sphere_under_constant_sky() {
+    let sky = Color3::new(0.9, 0.7, 1.3);
+    let albedo = Color3::new(0.8, 0.5, 0.2);
+
+    let mut materials = MaterialList::default();
+    let mut world = HittableList::default();
+    let material = diffuse(&mut materials, albedo);
+    sphere(&mut world, Point3::new(0.0, 0.0, 0.0), 1.0, material);
+
+    let mut scene = Scene::new(
+        world,
+        materials,
+        LightList::default(),
+        camera(Point3::new(0.0, 0.0, 4.0), Point3::new(0.0, 0.0, 0.0)),
+    )
+    .unwrap();
+    scene.environment = Environment::Constant(sky);
+
+    let samples = trace(&scene);
+    let hit = sky * albedo;
+    let (hits, misses): (Vec<Color3>, Vec<Color3>) = samples
+        .into_iter()
+        .partition(|sample| (*sample - hit).length() < (*sample - sky).length());
+
+    assert!(!hits.is_empty() && !misses.is_empty());
+    assert_converges_to("sphere", &hits, hit);
+    assert_converges_to("sky", &misses, sky);
+}
+
+/// The inside of a closed diffuse sphere of radius `r` and albedo `a` with a point light of intensity `i` at
+/// its centre. The light directly irradiates the wall with `i / r^2` everywhere and every point of the wall
+/// sees all of it, so the radiosity `b` is `a (i / r^2 + b)` and the radiance is `b / π` in every direction,
+/// wherever the camera is.
+#[test]
+fn inside_diffuse_sphere_lit_from_centre() {
+    let radius = 2.0;
+    let albedo = Color3::new(0.8, 0.5, 0.2);
+    let intensity = Color3::new(3.0, 2.0, 1.0);
+
+    let mut materials = MaterialList::default();
+    let mut world = HittableList::default();
+    let material = diffuse(&mut materials, albedo);
+    sphere(&mut world, Point3::new(0.0, 0.0, 0.0), radius, material);
+
+    let mut lights = LightList::default();
+    lights.add(Light::Point(PointLight {
+        position: Point3::new(0.0, 0.0, 0.0),
+        intensity,
+    }));
+
+    let mut scene = Scene::new(
+        world,
+        materials,
+        lights,
+        camera(Point3::new(0.5, 0.3, 0.8), Point3::new(-1.0, 0.0, 0.0)),
+    )
+    .unwrap();
+    scene.environment = Environment::Constant(Color3::default());
+
+    let samples = trace(&scene);
+    let irradiance = intensity / (radius * radius);
+    let expected = Color3::new(
+        albedo.x * irradiance.x / (1.0 - albedo.x),
+        albedo.y * irradiance.y / (1.0 - albedo.y),
+        albedo.z * irradiance.z / (1.0 - albedo.z),
+    ) / std::f32::consts::PI;
+
+    assert_converges_to("inside sphere", &samples, expected);
+}
+
+/// Rays that miss the sphere see an infinite environment and are thrown away. Every pixel has to be the
+/// mean of the samples that are left, not darkened by the ones that aren't
+#[test]
+fn invalid_samples_are_left_out_of_the_mean() {
+    let mut materials = MaterialList::default();
+    let mut world = HittableList::default();
+    let grey = diffuse(&mut materials, Color3::new(0.5, 0.5, 0.5));
+    sphere(&mut world, Point3::new(0.0, 0.0, 0.0), 1.0, grey);
+
+    // Lit by the point light only, as paths end at the first hit
+    let mut lights = LightList::default();
+    lights.add(Light::Point(PointLight {
+        position: Point3::new(0.0, 0.0, 3.0),
+        intensity: Color3::new(1.0, 1.0, 1.0),
+    }));
+    let mut scene = Scene::new(
+        world,
+        materials,
+        lights,
+        camera(Point3::new(0.0, 0.0, 3.0), Point3::new(0.0, 0.0, 0.0)),
+    )
+    .unwrap();
+    scene.environment = Environment::Constant(Color3::new(
+        Float::INFINITY,
+        Float::INFINITY,
+        Float::INFINITY,
+    ));
+
+    let settings = RenderSettings {
+        max_depth: 1,
+        ..settings()
+    };
+    let film = render(&scene, &settings).unwrap();
+    assert!(!film.diagnostics.is_empty());
+
+    let mut partly_invalid = 0;
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let valid: Vec<Color3> = (0..SAMPLES_PER_PIXEL)
+                .map(|sample| {
+                    let mut aov = AovSample::default();
+                    trace_sample(&scene, &settings, x, y, sample, &mut aov, None)
+                })
+                .filter(|color| color.is_finite())
+                .collect();
+            if valid.is_empty() {
+                assert!(film.get(x, y).color.near_zero());
+                continue;
+            }
+            if valid.len() < SAMPLES_PER_PIXEL {
+                partly_invalid += 1;
+            }
+
+            let mean = valid
+                .iter()
+                .fold(Color3::default(), |sum, &color| sum + color)
+                / valid.len() as Float;
+            let color = film.get(x, y).color;
+            assert!(
+                (color - mean).length() < 1e-5,
+                "pixel ({}, {}) is {:?} instead of {:?}",
+                x,
+                y,
+                color,
+                mean
+            );
+        }
+    }
+    assert!(partly_invalid > 0);
+}
@@ -0,0 +1,312 @@
+//! Reads small pbrt-v3 scenes and checks where their shapes, materials, lights and camera end up.
+//! pbrt's world is left handed, so everything comes out mirrored in x
+
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+use rustrt::*;
+use std::path::PathBuf;
+
+mod common;
+use common::{hit, near, temp_dir};
+
+const SCENE: &str = r#"# The camera looks down +z from 5 units back, so +x is on the right of the image
+LookAt 0 0 -5  0 0 0  0 1 0
+Camera "perspective" "float fov" [45]
+Film "image" "integer xresolution" [200] "integer yresolution" [100] "string filename" "out.exr"
+Sampler "halton" "integer pixelsamples" 8
+Integrator "path" "integer maxdepth" [3]
+PixelFilter "gaussian"
+
+WorldBegin
+LightSource "point" "rgb I" [1 2 3] "point from" [0 5 0]
+LightSource "distant" "point from" [0 0 0] "point to" [0 -1 0]
+    "rgb L" [2 2 2] "rgb scale" [0.5 0.5 0.5]
+
+AttributeBegin
+    Translate 2 0 0
+    Material "metal" "rgb eta" [2 2 2] "rgb k" [0 0 0] "float roughness" 0.3
+    Shape "sphere" "float radius" 0.5
+AttributeEnd
+AttributeBegin
+    Material "glass" "float index" 1.33
+    Rotate 90 0 0 1
+    Translate 2 0 0
+    Shape "sphere" "float radius" 0.5
+AttributeEnd
+
+MakeNamedMaterial "red" "string type" "matte" "rgb Kd" [0.8 0.1 0.1]
+NamedMaterial "red"
+Include "floor.pbrt"
+Shape "plymesh" "string filename" "roof.ply"
+WorldEnd
+"#;
+
+const FLOOR: &str = r#"Shape "trianglemesh" "integer indices" [0 1 2 0 2 3]
+    "point P" [-10 -1 -10  10 -1 -10  10 -1 10  -10 -1 10]
+"#;
+
+const ROOF: &str = "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+-1 5 -1
+1 5 -1
+0 5 1
+3 0 1 2
+";
+
+/// Writes `files` to a directory of their own and returns the path of the first
+fn write(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = temp_dir(&format!("pbrt_{}", name));
+    for (file, text) in files {
+        std::fs::write(dir.join(file), text).unwrap();
+    }
+    dir.join(files[0].0)
+}
+
+/// With a unit direction, perspective cameras scale it by their focus distance
+fn camera_ray(scene: &Scene, s: Float, t: Float) -> Ray {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let ray = scene.camera.get_ray(s, t, &mut rng).unwrap().ray;
+    Ray {
+        origin: ray.origin,
+        direction: Vector3::unit_vector(ray.direction),
+    }
+}
+
+#[test]
+fn reads_shapes_materials_lights_and_settings() {
+    let path = write(
+        "scene",
+        &[
+            ("scene.pbrt", SCENE),
+            ("floor.pbrt", FLOOR),
+            ("roof.ply", ROOF),
+        ],
+    );
+    let (scene, settings) = read_pbrt(&path).unwrap();
+    assert_eq!((settings.width, settings.height), (200, 100));
+    assert_eq!(settings.samples_per_pixel, 8);
+    assert_eq!(settings.max_depth, 3);
+
+    // The fov is across the shorter side, so the half width of the view is 2 tan(22.5°) per unit ahead.
+    // The metal sphere's centre is 0.4 per unit ahead to the right, that fraction of the way to the edge
+    let s = 0.5 + 0.4 / (2.0 * (22.5 as Float).to_radians().tan()) / 2.0;
+    let ray = camera_ray(&scene, s, 0.5);
+    assert!(near(ray.origin, Point3::new(0.0, 0.0, -5.0)));
+    let metal = hit(&scene.world, ray.origin, ray.direction).unwrap();
+    assert!(((metal.p - Point3::new(-2.0, 0.0, 0.0)).length() - 0.5).abs() < 1e-3);
+    match &scene.materials[metal.material.unwrap()].reflectance_model {
+        ReflectanceModel::Metal(Metal { albedo, fuzziness }) => {
+            // (eta - 1)² / (eta + 1)² at normal incidence
+            assert!(near(*albedo, Color3::new(1.0, 1.0, 1.0) / 9.0));
+            assert_eq!(*fuzziness, 0.3);
+        }
+        _ => panic!("the sphere on the right should be metal"),
+    }
+    let ray = camera_ray(&scene, 1.0 - s, 0.5);
+    assert!(hit(&scene.world, ray.origin, ray.direction).is_none());
+
+    // Rotated after being moved, so the glass sphere is 2 units up
+    let glass = hit(
+        &scene.world,
+        Point3::new(0.0, 2.0, -5.0),
+        Vector3::new(0.0, 0.0, 1.0),
+    )
+    .unwrap();
+    assert!((glass.t - 4.5).abs() < 1e-4);
+    match scene.materials[glass.material.unwrap()].reflectance_model {
+        ReflectanceModel::Dielectric(Dielectric {
+            index_of_refraction,
+        }) => assert_eq!(index_of_refraction, 1.33),
+        _ => panic!("the sphere above should be glass"),
+    }
+
+    // The included floor and the PLY roof share the named material
+    let origin = Point3::new(0.0, 0.0, 0.0);
+    let floor = hit(&scene.world, origin, Vector3::new(0.0, -1.0, 0.0)).unwrap();
+    assert!((floor.t - 1.0).abs() < 1e-4);
+    let roof = hit(
+        &scene.world,
+        Point3::new(0.0, 4.0, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+    )
+    .unwrap();
+    assert!((roof.t - 1.0).abs() < 1e-4);
+    assert_eq!(floor.material, roof.material);
+    let red = scene.materials[floor.material.unwrap()].albedo(&floor);
+    assert!(near(red, Color3::new(0.8, 0.1, 0.1)));
+
+    let lights: Vec<&Light> = scene.lights.iter().collect();
+    assert_eq!(lights.len(), 2);
+    match lights[0] {
+        Light::Point(light) => {
+            assert!(near(light.position, Point3::new(0.0, 5.0, 0.0)));
+            assert!(near(light.intensity, Color3::new(1.0, 2.0, 3.0)));
+        }
+        _ => panic!("the first light should be a point light"),
+    }
+    match lights[1] {
+        Light::Directional(light) => {
+            assert!(near(light.direction, Vector3::new(0.0, -1.0, 0.0)));
+            assert!(near(light.radiance, Color3::new(1.0, 1.0, 1.0)));
+        }
+        _ => panic!("the second light should be a directional light"),
+    }
+
+    // Without an infinite light the background is black
+    match scene.environment {
+        Environment::Constant(radiance) => assert!(near(radiance, Color3::new(0.0, 0.0, 0.0))),
+        _ => panic!("the environment should be black"),
+    }
+}
+
+#[test]
+fn orthographic_camera_and_defaults() {
+    let scene = r#"LookAt 0 0 0  0 0 1  0 1 0
+Camera "orthographic"
+Film "image" "integer xresolution" 400 "integer yresolution" 200
+WorldBegin
+LightSource "infinite" "rgb L" [0.5 0.5 0.5]
+TransformBegin
+    Transform [1 0 0 0  0 1 0 0  0 0 1 0  0 0 10 1]
+    CoordinateSystem "ahead"
+TransformEnd
+CoordSysTransform "ahead"
+Shape "sphere"
+WorldEnd
+"#;
+    let (scene, settings) = read_pbrt(&write("orthographic", &[("scene.pbrt", scene)])).unwrap();
+    assert_eq!(settings.samples_per_pixel, 16);
+    assert_eq!(settings.max_depth, 5);
+
+    // The screen spans 2 vertically and 4 across, with pbrt's +x on the right
+    let center = camera_ray(&scene, 0.5, 0.5);
+    assert!(near(center.origin, Point3::new(0.0, 0.0, 0.0)));
+    assert!(near(center.direction, Vector3::new(0.0, 0.0, 1.0)));
+    assert!(near(
+        camera_ray(&scene, 1.0, 0.5).origin,
+        Point3::new(-2.0, 0.0, 0.0)
+    ));
+    assert!(near(
+        camera_ray(&scene, 0.5, 1.0).origin,
+        Point3::new(0.0, 1.0, 0.0)
+    ));
+
+    // The unit sphere the named coordinate system put 10 units ahead, in the default grey matte
+    let sphere = hit(&scene.world, center.origin, center.direction).unwrap();
+    assert!((sphere.t - 9.0).abs() < 1e-4);
+    let grey = scene.materials[sphere.material.unwrap()].albedo(&sphere);
+    assert!(near(grey, Color3::new(0.5, 0.5, 0.5)));
+
+    match scene.environment {
+        Environment::Constant(radiance) => assert!(near(radiance, Color3::new(0.5, 0.5, 0.5))),
+        _ => panic!("the infinite light should be a constant environment"),
+    }
+}
+
+#[test]
+fn realistic_camera_reads_its_lens_next_to_the_scene() {
+    let scene = r#"LookAt 0 0 0  0 0 1  0 1 0
+Camera "realistic" "string lensfile" "dgauss.dat" "float aperturediameter" 2 "float focusdistance" 5
+Film "image" "integer xresolution" 300 "integer yresolution" 200 "float diagonal" 43.3
+WorldBegin
+WorldEnd
+"#;
+    let path = write(
+        "realistic",
+        &[
+            ("scene.pbrt", scene),
+            ("dgauss.dat", include_str!("../lenses/dgauss.50mm.dat")),
+        ],
+    );
+    let (scene, _) = read_pbrt(&path).unwrap();
+
+    // Rays from the centre of the film leave the front of the lens and meet again where it's focused
+    let mut rng = SmallRng::seed_from_u64(0);
+    for _ in 0..100 {
+        let ray = match scene.camera.get_ray(0.5, 0.5, &mut rng) {
+            Some(camera_ray) => camera_ray.ray,
+            None => continue,
+        };
+        assert!(ray.origin.z > 0.0 && ray.origin.z < 0.1, "{:?}", ray.origin);
+        let focus = ray.at((5.0 - ray.origin.z) / ray.direction.z);
+        assert!(near(focus, Point3::new(0.0, 0.0, 5.0)), "{:?}", focus);
+    }
+
+    let broken = [
+        r#"Camera "realistic""#,
+        r#"Camera "realistic" "string lensfile" "missing.dat""#,
+        r#"Camera "realistic" "string lensfile" "dgauss.dat" "float aperturediameter" 100"#,
+        r#"Camera "realistic" "string lensfile" "dgauss.dat" "float focusdistance" 0.01"#,
+    ];
+    for camera in broken {
+        let text = format!("{}\nWorldBegin\nWorldEnd\n", camera);
+        let path = write(
+            "realistic_broken",
+            &[
+                ("scene.pbrt", &text),
+                ("dgauss.dat", include_str!("../lenses/dgauss.50mm.dat")),
+            ],
+        );
+        assert!(read_pbrt(&path).is_err(), "{} should be rejected", camera);
+    }
+}
+
+#[test]
+fn rejects_unsupported_and_broken_files() {
+    let broken = [
+        r#"Shape "cone""#,
+        r#"AreaLightSource "diffuse" "rgb L" [1 1 1]"#,
+        r#"Material "matte" "texture Kd" "checks""#,
+        r#"Material "matte" "spectrum Kd" [300 0.3 800 0.3]"#,
+        r#"Material "matte" "rgb Kd" [0.5 0.5]"#,
+        r#"Material "none""#,
+        r#"LightSource "infinite" "string mapname" "sky.exr""#,
+        r#"LightSource "point" "rgb I" [nan 1 1]"#,
+        r#"LightSource "spot" "float coneangle" [-5]"#,
+        r#"LightSource "distant" "point from" [1 1 1] "point to" [1 1 1]"#,
+        r#"AttributeBegin"#,
+        r#"AttributeEnd"#,
+        r#"AttributeBegin TransformEnd"#,
+        r#"Translate 1 2"#,
+        r#"Rotate 90 0 0 1 1"#,
+        r#"Rotate 90 0 0 0"#,
+        r#"Transform [1 0 0 0  0 1 0 0  0 0 1 0  0 0 0]"#,
+        r#"Transform [0 0 0 0  0 0 0 0  0 0 0 0  0 0 0 1]"#,
+        r#"NamedMaterial "missing""#,
+        r#"CoordSysTransform "missing""#,
+        r#"Shape "sphere" "float radius" [1 2]"#,
+        r#"Shape "sphere" "float radius" -1"#,
+        r#"Shape "sphere" "float phimax" 180"#,
+        r#"Shape "trianglemesh" "point P" [0 0 0  1 0 0  0 1 0  1 1 1]"#,
+        r#"Shape "trianglemesh" "integer indices" [0 1 5] "point P" [0 0 0  1 0 0  0 1 0]"#,
+        r#"Shape "trianglemesh" "integer indices" [0 1 2.5] "point P" [0 0 0  1 0 0  0 1 0]"#,
+        r#"Shape "plymesh" "string filename" "missing.ply""#,
+        r#"Include "missing.pbrt""#,
+        r#"Include "scene.pbrt""#,
+        r#"Camera "realistic""#,
+        r#"Shape "sphere" "float radius"#,
+        r#"Shape "sphere" "radius" 2"#,
+        r#"[ 1 2 3 ]"#,
+    ];
+    for directive in broken {
+        let text = format!("WorldBegin\n{}\nWorldEnd\n", directive);
+        let path = write("broken", &[("scene.pbrt", &text)]);
+        assert!(
+            read_pbrt(&path).is_err(),
+            "{} should be rejected",
+            directive
+        );
+    }
+
+    // Errors point at the line
+    let path = write("line", &[("scene.pbrt", "WorldBegin\n\nShape \"cone\"\n")]);
+    let error = read_pbrt(&path).err().unwrap().to_string();
+    assert!(error.contains("line 3"), "{}", error);
+}
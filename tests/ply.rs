@@ -0,0 +1,183 @@
+//! Reads the same square in all three PLY formats and checks the vertex colours where rays hit it
+
+use rustrt::*;
+
+/// A unit square in the xy plane as one polygon, with a colour per corner
+const POSITIONS: [[f32; 3]; 4] = [
+    [0.0, 0.0, 0.0],
+    [1.0, 0.0, 0.0],
+    [1.0, 1.0, 0.0],
+    [0.0, 1.0, 0.0],
+];
+const COLORS: [[u8; 3]; 4] = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+
+fn header(format: &str) -> String {
+    format!(
+        "ply\n\
+         format {} 1.0\n\
+         comment made for the tests\n\
+         element vertex 4\n\
+         property float x\n\
+         property float y\n\
+         property float z\n\
+         property float nx\n\
+         property float ny\n\
+         property float nz\n\
+         property float s\n\
+         property float t\n\
+         property uchar red\n\
+         property uchar green\n\
+         property uchar blue\n\
+         property list uchar int unused\n\
+         element face 1\n\
+         property list uchar int vertex_indices\n\
+         element edge 1\n\
+         property int vertex1\n\
+         property int vertex2\n\
+         end_header\n",
+        format
+    )
+}
+
+fn ascii() -> Vec<u8> {
+    let mut text = header("ascii");
+    for (p, c) in POSITIONS.iter().zip(&COLORS) {
+        text += &format!(
+            "{} {} {} 0 0 1 {} {} {} {} {} 2 7 8\n",
+            p[0], p[1], p[2], p[0], p[1], c[0], c[1], c[2]
+        );
+    }
+    text += "4 0 1 2 3\n0 1\n";
+    text.into_bytes()
+}
+
+fn binary(big_endian: bool) -> Vec<u8> {
+    let format = match big_endian {
+        true => "binary_big_endian",
+        false => "binary_little_endian",
+    };
+    let mut bytes = header(format).into_bytes();
+    let float = |bytes: &mut Vec<u8>, value: f32| match big_endian {
+        true => bytes.extend(value.to_be_bytes()),
+        false => bytes.extend(value.to_le_bytes()),
+    };
+    let int = |bytes: &mut Vec<u8>, value: i32| match big_endian {
+        true => bytes.extend(value.to_be_bytes()),
+        false => bytes.extend(value.to_le_bytes()),
+    };
+
+    for (p, c) in POSITIONS.iter().zip(&COLORS) {
+        for &value in p.iter().chain(&[0.0, 0.0, 1.0, p[0], p[1]]) {
+            float(&mut bytes, value);
+        }
+        bytes.extend(c);
+        bytes.push(2);
+        int(&mut bytes, 7);
+        int(&mut bytes, 8);
+    }
+    bytes.push(4);
+    for index in 0..4 {
+        int(&mut bytes, index);
+    }
+    int(&mut bytes, 0);
+    int(&mut bytes, 1);
+    bytes
+}
+
+fn check_square(mesh: &TriangleMesh) {
+    assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+    let colors = mesh.colors.as_ref().unwrap();
+    let uvs = mesh.uvs.as_ref().unwrap();
+    let normals = mesh.normals.as_ref().unwrap();
+    for i in 0..4 {
+        let [x, y, z] = POSITIONS[i];
+        assert!((mesh.positions[i] - Point3::new(x, y, z)).near_zero());
+        assert!((normals[i] - Vector3::new(0.0, 0.0, 1.0)).near_zero());
+        assert_eq!(uvs[i], [x, y]);
+        let [r, g, b] = COLORS[i].map(|c| c as Float / 255.0);
+        assert!((colors[i] - Color3::new(r, g, b)).near_zero());
+    }
+}
+
+#[test]
+fn reads_every_format() {
+    check_square(&parse_ply("ascii.ply", &ascii()).unwrap());
+    check_square(&parse_ply("little.ply", &binary(false)).unwrap());
+    check_square(&parse_ply("big.ply", &binary(true)).unwrap());
+}
+
+#[test]
+fn reads_windows_line_endings() {
+    let text = String::from_utf8(ascii()).unwrap().replace('\n', "\r\n");
+    check_square(&parse_ply("crlf.ply", text.as_bytes()).unwrap());
+}
+
+#[test]
+fn vertex_colors_are_interpolated() {
+    let mut materials = MaterialList::default();
+    let material = materials.add(Material {
+        texture: Some(Texture::VertexColor),
+        ..Material::from(ReflectanceModel::Diffuse(Diffuse {
+            albedo: Color3::new(1.0, 1.0, 1.0),
+        }))
+    });
+    let mesh =
+        Hittable::Mesh(Mesh::new(parse_ply("big.ply", &binary(true)).unwrap(), material).unwrap());
+    mesh.validate(&materials).unwrap();
+
+    let hit = |x: Float, y: Float| {
+        let ray = Ray {
+            origin: Point3::new(x, y, 1.0),
+            direction: Vector3::new(0.0, 0.0, -1.0),
+        };
+        let mut interaction = Interaction::default();
+        assert!(mesh.hit(&ray, 0.001, Float::INFINITY, &mut interaction));
+        interaction
+    };
+
+    // At a corner it's the corner's colour, and halfway along an edge the average
+    let corner = hit(0.999, 0.001);
+    assert!((corner.color.unwrap() - Color3::new(0.0, 1.0, 0.0)).length() < 0.01);
+    assert!((corner.uv[0] - 0.999).abs() < 1e-4 && (corner.uv[1] - 0.001).abs() < 1e-4);
+    let edge = hit(0.5, 0.0001);
+    assert!((edge.color.unwrap() - Color3::new(0.5, 0.5, 0.0)).length() < 0.01);
+    assert!((edge.normal - Vector3::new(0.0, 0.0, 1.0)).near_zero());
+    assert!((edge.t - 1.0).abs() < 1e-4);
+
+    let albedo = materials[material].albedo(&edge);
+    assert!((albedo - Color3::new(0.5, 0.5, 0.0)).length() < 0.01);
+
+    let ray = Ray {
+        origin: Point3::new(1.5, 0.5, 1.0),
+        direction: Vector3::new(0.0, 0.0, -1.0),
+    };
+    assert!(!mesh.hit(&ray, 0.001, Float::INFINITY, &mut Interaction::default()));
+}
+
+#[test]
+fn rejects_broken_files() {
+    let ascii = String::from_utf8(ascii()).unwrap();
+    let broken = [
+        ascii.replacen("ply", "obj", 1),
+        ascii.replace("format ascii", "format utf8"),
+        ascii.replace("end_header\n", ""),
+        ascii.replace("property float z\n", ""),
+        ascii.replace("property uchar red", "property color red"),
+        ascii.replace("4 0 1 2 3", "4 0 1 2 9"),
+        ascii.replace("4 0 1 2 3", "2 0 1"),
+        ascii.replace("0 0 1 0 0 255 0 0", "0 0 1 0 0 red 0 0"),
+        ascii[..ascii.len() - 10].to_string(),
+        ascii.replace("element vertex 4", "element vertex 1000000000000"),
+        ascii.replace("element face 1", "element face 1000000000000"),
+    ];
+    for text in broken {
+        assert!(
+            parse_ply("broken.ply", text.as_bytes()).is_err(),
+            "{} should be rejected",
+            text
+        );
+    }
+
+    let binary = binary(false);
+    assert!(parse_ply("short.ply", &binary[..binary.len() - 3]).is_err());
+}
@@ -0,0 +1,608 @@
+//! Shoots random rays at every kind of shape and checks that the hits are on the surface, inside the bound
+//! and have a unit normal perpendicular to it and a uv from 0 to 1.
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use rustrt::transforms::Transform;
+use rustrt::*;
+
+mod common;
+use common::material;
+
+const RAYS: usize = 10_000;
+const EPSILON: Float = 1e-3;
+
+fn random_in(bound: &bounds::Bounds3, rng: &mut SmallRng) -> Point3 {
+    Point3::new(
+        rng.gen_range(bound.p_min.x..=bound.p_max.x),
+        rng.gen_range(bound.p_min.y..=bound.p_max.y),
+        rng.gen_range(bound.p_min.z..=bound.p_max.z),
+    )
+}
+
+/// `surface` is the implicit function of the shape, 0 on its surface, and `gradient` its normal there
+fn check_hits(
+    shape: Hittable,
+    materials: &MaterialList,
+    surface: impl Fn(Point3) -> Float,
+    gradient: impl Fn(Point3) -> Vector3,
+) {
+    check_hits_with_normal_tolerance(shape, materials, surface, gradient, EPSILON);
+}
+
+fn check_hits_with_normal_tolerance(
+    shape: Hittable,
+    materials: &MaterialList,
+    surface: impl Fn(Point3) -> Float,
+    gradient: impl Fn(Point3) -> Vector3,
+    normal_tolerance: Float,
+) {
+    shape.validate(materials).unwrap();
+    let bound = shape.bound();
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut hits = 0;
+
+    for _ in 0..RAYS {
+        // From all around towards somewhere inside the bound
+        let origin = bound.center() + 3.0 * bound.diagonal().length() * Vector3::random(&mut rng);
+        let target = random_in(&bound, &mut rng);
+        let ray = Ray {
+            origin,
+            direction: target - origin,
+        };
+
+        let mut interaction = Interaction::default();
+        if !shape.hit(&ray, 0.001, Float::INFINITY, &mut interaction) {
+            continue;
+        }
+        hits += 1;
+
+        let p = interaction.p;
+        assert!(surface(p).abs() < EPSILON, "{:?} isn't on the surface", p);
+        assert!((p - ray.at(interaction.t)).length() < EPSILON);
+        assert!(
+            (0..3).all(|axis| bound.p_min[axis] - EPSILON <= p[axis]
+                && p[axis] <= bound.p_max[axis] + EPSILON),
+            "{:?} is outside of the bound {:?}",
+            p,
+            bound
+        );
+        assert!((interaction.normal.length() - 1.0).abs() < EPSILON);
+        let expected = Vector3::unit_vector(gradient(p));
+        assert!(
+            Vector3::cross(&interaction.normal, &expected).length() < normal_tolerance,
+            "normal {:?} at {:?} should be along {:?}",
+            interaction.normal,
+            p,
+            expected
+        );
+        assert!(interaction
+            .uv
+            .iter()
+            .all(|uv| (-EPSILON..=1.0 + EPSILON).contains(uv)));
+
+        // Nothing is nearer
+        let mut nearer = Interaction::default();
+        assert!(!shape.hit(&ray, 0.001, interaction.t - EPSILON, &mut nearer));
+    }
+
+    assert!(hits > RAYS / 10, "only {} of {} rays hit", hits, RAYS);
+}
+
+#[test]
+fn sphere() {
+    let (materials, material) = material();
+    let position = Point3::new(1.0, 2.0, 3.0);
+    check_hits(
+        Hittable::Sphere(Sphere {
+            position,
+            radius: 2.0,
+            material,
+        }),
+        &materials,
+        |p| (p - position).length() - 2.0,
+        |p| p - position,
+    );
+}
+
+#[test]
+fn quad() {
+    let (materials, material) = material();
+    let quad = Quad {
+        origin: Point3::new(1.0, 0.0, 0.0),
+        u: Vector3::new(1.0, 1.0, 0.0),
+        v: Vector3::new(0.0, 0.5, 2.0),
+        material,
+    };
+    let normal = Vector3::cross(&quad.u, &quad.v);
+    let origin = quad.origin;
+    check_hits(
+        Hittable::Quad(quad),
+        &materials,
+        |p| Vector3::dot(&(p - origin), &normal),
+        |_| normal,
+    );
+}
+
+#[test]
+fn axis_aligned_quad() {
+    let (_, material) = material();
+    let quad = Quad::axis_aligned(
+        Point3::new(-1.0, 2.0, -3.0),
+        Point3::new(1.0, 2.0, 3.0),
+        material,
+    )
+    .unwrap();
+    let normal = Vector3::unit_vector(Vector3::cross(&quad.u, &quad.v));
+    assert!((normal - Vector3::new(0.0, 1.0, 0.0)).near_zero());
+
+    let bound = quad.bound();
+    assert!((bound.p_min - Point3::new(-1.0, 2.0, -3.0)).length() < EPSILON);
+    assert!((bound.p_max - Point3::new(1.0, 2.0, 3.0)).length() < EPSILON);
+
+    assert!(Quad::axis_aligned(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(1.0, 1.0, 1.0),
+        material
+    )
+    .is_err());
+}
+
+#[test]
+fn disk() {
+    let (materials, material) = material();
+    check_hits(
+        Hittable::Disk(Disk {
+            height: 0.5,
+            radius: 2.0,
+            inner_radius: 0.5,
+            phi_max: 270.0,
+            material,
+        }),
+        &materials,
+        |p| p.z - 0.5,
+        |_| Vector3::new(0.0, 0.0, 1.0),
+    );
+}
+
+#[test]
+fn cylinder() {
+    let (materials, material) = material();
+    check_hits(
+        Hittable::Cylinder(Cylinder {
+            radius: 1.5,
+            z_min: -1.0,
+            z_max: 2.0,
+            phi_max: 200.0,
+            material,
+        }),
+        &materials,
+        |p| Float::sqrt(p.x * p.x + p.y * p.y) - 1.5,
+        |p| Vector3::new(p.x, p.y, 0.0),
+    );
+}
+
+#[test]
+fn cone() {
+    let (materials, material) = material();
+    check_hits(
+        Hittable::Cone(Cone {
+            height: 2.0,
+            radius: 1.0,
+            phi_max: 360.0,
+            material,
+        }),
+        &materials,
+        |p| Float::sqrt(p.x * p.x + p.y * p.y) - 0.5 * (2.0 - p.z),
+        |p| Vector3::new(p.x, p.y, 0.5 * Float::sqrt(p.x * p.x + p.y * p.y)),
+    );
+}
+
+#[test]
+fn paraboloid() {
+    let (materials, material) = material();
+    // z = x² + y²
+    check_hits(
+        Hittable::Paraboloid(Paraboloid {
+            radius: 1.0,
+            z_min: 0.2,
+            z_max: 1.0,
+            phi_max: 300.0,
+            material,
+        }),
+        &materials,
+        |p| p.x * p.x + p.y * p.y - p.z,
+        |p| Vector3::new(2.0 * p.x, 2.0 * p.y, -1.0),
+    );
+}
+
+#[test]
+fn instanced_cylinder() {
+    let (materials, material) = material();
+    // Stands upright along y at x = 3
+    let transform = Transform::translate(&Vector3::new(3.0, 0.0, 0.0)) * Transform::rotate_x(-90.0);
+    check_hits(
+        Hittable::Instance(Instance {
+            object: Box::new(Hittable::Cylinder(Cylinder {
+                radius: 1.0,
+                z_min: 0.0,
+                z_max: 2.0,
+                phi_max: 360.0,
+                material,
+            })),
+            transform,
+        }),
+        &materials,
+        |p| Float::sqrt((p.x - 3.0) * (p.x - 3.0) + p.z * p.z) - 1.0,
+        |p| Vector3::new(p.x - 3.0, 0.0, p.z),
+    );
+}
+
+#[test]
+fn planes_are_hit_alongside_the_bvh() {
+    let (materials, material) = material();
+    let mut world = HittableList::default();
+    let ground = world.add(Hittable::Plane(Plane {
+        point: Point3::new(0.0, 0.0, 0.0),
+        normal: Vector3::new(0.0, 1.0, 0.0),
+        material,
+    }));
+    let sphere = world.add(Hittable::Sphere(Sphere {
+        position: Point3::new(0.0, 1.0, 0.0),
+        radius: 0.5,
+        material,
+    }));
+    world.validate(&materials).unwrap();
+    world.init();
+
+    // Planes are left out of the world's bound
+    assert!(world.bound().p_min.is_finite() && world.bound().p_max.is_finite());
+
+    let hit = |origin: Point3| {
+        let mut interaction = Interaction::default();
+        let ray = Ray {
+            origin,
+            direction: Vector3::new(0.0, -1.0, 0.0),
+        };
+        assert!(world.hit(&ray, 0.001, Float::INFINITY, &mut interaction));
+        interaction
+    };
+
+    let above_sphere = hit(Point3::new(0.0, 5.0, 0.0));
+    assert_eq!(above_sphere.object_id, sphere);
+    assert!((above_sphere.t - 3.5).abs() < EPSILON);
+
+    let beside_sphere = hit(Point3::new(2.0, 5.0, 0.0));
+    assert_eq!(beside_sphere.object_id, ground);
+    assert!((beside_sphere.t - 5.0).abs() < EPSILON);
+    assert!((beside_sphere.uv[0].abs() + beside_sphere.uv[1].abs() - 2.0).abs() < EPSILON);
+
+    // Between the sphere and the ground
+    let under_sphere = hit(Point3::new(0.0, 0.25, 0.0));
+    assert_eq!(under_sphere.object_id, ground);
+}
+
+fn check_sdf(node: SdfNode) {
+    let (materials, material) = material();
+    let gradient = |p: Point3| {
+        let h = 1e-3;
+        let mut gradient = Vector3::default();
+        for axis in 0..3 {
+            let mut offset = Vector3::default();
+            offset[axis] = h;
+            gradient[axis] = node.distance(p + offset) - node.distance(p - offset);
+        }
+        gradient
+    };
+    // Both normals are from differences, which disagree a little where the surface curves tightly
+    check_hits_with_normal_tolerance(
+        Hittable::Sdf(Sdf::new(node.clone(), material).unwrap()),
+        &materials,
+        |p| node.distance(p),
+        gradient,
+        1e-2,
+    );
+}
+
+#[test]
+fn sdf_torus() {
+    check_sdf(SdfNode::Torus {
+        major_radius: 1.0,
+        minor_radius: 0.25,
+    });
+}
+
+#[test]
+fn sdf_smooth_union() {
+    check_sdf(SdfNode::SmoothUnion {
+        a: Box::new(SdfNode::Sphere { radius: 0.5 }),
+        b: Box::new(SdfNode::Translate {
+            offset: Vector3::new(0.7, 0.2, 0.0),
+            node: Box::new(SdfNode::RoundedBox {
+                half_extents: Vector3::new(0.3, 0.4, 0.5),
+                rounding: 0.1,
+            }),
+        }),
+        smoothing: 0.3,
+    });
+}
+
+#[test]
+fn sdf_intersection() {
+    let (materials, material) = material();
+    // A cube with its corners cut off by the sphere
+    let shape = Hittable::Sdf(
+        Sdf::new(
+            SdfNode::Intersection(
+                Box::new(SdfNode::Sphere { radius: 0.6 }),
+                Box::new(SdfNode::RoundedBox {
+                    half_extents: Vector3::new(0.5, 0.5, 0.5),
+                    rounding: 0.0,
+                }),
+            ),
+            material,
+        )
+        .unwrap(),
+    );
+    shape.validate(&materials).unwrap();
+
+    let hit = |direction: Vector3| {
+        let ray = Ray {
+            origin: -5.0 * direction,
+            direction,
+        };
+        let mut interaction = Interaction::default();
+        assert!(shape.hit(&ray, 0.001, Float::INFINITY, &mut interaction));
+        interaction
+    };
+
+    let face = hit(Vector3::new(1.0, 0.0, 0.0));
+    assert!((face.p - Point3::new(-0.5, 0.0, 0.0)).length() < EPSILON);
+    assert!((face.normal - Vector3::new(-1.0, 0.0, 0.0)).length() < EPSILON);
+
+    let corner = hit(Vector3::unit_vector(Vector3::new(1.0, 1.0, 1.0)));
+    assert!((corner.p.length() - 0.6).abs() < EPSILON);
+    assert!((corner.normal + Vector3::unit_vector(Vector3::new(1.0, 1.0, 1.0))).length() < EPSILON);
+}
+
+#[test]
+fn sdf_twisted_repeated_boxes() {
+    check_sdf(SdfNode::Twist {
+        rate: 1.5,
+        node: Box::new(SdfNode::Repeat {
+            spacing: Vector3::new(1.0, 1.0, 1.0),
+            copies: [1, 2, 0],
+            node: Box::new(SdfNode::RoundedBox {
+                half_extents: Vector3::new(0.3, 0.3, 0.3),
+                rounding: 0.1,
+            }),
+        }),
+    });
+}
+
+#[test]
+fn sdf_mandelbulb() {
+    let (materials, material) = material();
+    let mandelbulb = Hittable::Sdf(
+        Sdf::new(
+            SdfNode::Mandelbulb {
+                power: 8.0,
+                iterations: 8,
+            },
+            material,
+        )
+        .unwrap(),
+    );
+    mandelbulb.validate(&materials).unwrap();
+
+    // Straight at the centre from all around
+    let mut rng = SmallRng::seed_from_u64(0);
+    for _ in 0..100 {
+        let origin = 5.0 * Vector3::unit_vector(Vector3::random(&mut rng));
+        let ray = Ray {
+            origin,
+            direction: -origin,
+        };
+        let mut interaction = Interaction::default();
+        assert!(mandelbulb.hit(&ray, 0.001, Float::INFINITY, &mut interaction));
+        assert!(interaction.p.length() < 1.3);
+        assert!((interaction.normal.length() - 1.0).abs() < EPSILON);
+    }
+}
+
+#[test]
+fn invalid_sdfs() {
+    let (_, material) = material();
+    for node in [
+        SdfNode::Sphere { radius: -1.0 },
+        SdfNode::RoundedBox {
+            half_extents: Vector3::new(1.0, 0.1, 1.0),
+            rounding: 0.2,
+        },
+        SdfNode::Mandelbulb {
+            power: 1.0,
+            iterations: 8,
+        },
+        SdfNode::SmoothUnion {
+            a: Box::new(SdfNode::Sphere { radius: 1.0 }),
+            b: Box::new(SdfNode::Sphere { radius: 1.0 }),
+            smoothing: 0.0,
+        },
+    ] {
+        assert!(Sdf::new(node, material).is_err());
+    }
+}
+
+fn sphere_at(x: Float, radius: Float, material: MaterialId) -> Box<Hittable> {
+    Box::new(Hittable::Sphere(Sphere {
+        position: Point3::new(x, 0.0, 0.0),
+        radius,
+        material,
+    }))
+}
+
+fn hit_along(shape: &Hittable, origin: Point3, direction: Vector3) -> Option<Interaction> {
+    let ray = Ray { origin, direction };
+    let mut interaction = Interaction::default();
+    shape
+        .hit(&ray, 0.001, Float::INFINITY, &mut interaction)
+        .then_some(interaction)
+}
+
+#[test]
+fn csg_bitten_sphere() {
+    let (materials, material) = material();
+    let bite = Point3::new(0.8, 0.0, 0.0);
+    check_hits(
+        Hittable::Csg(Csg {
+            operation: CsgOperation::Difference,
+            a: sphere_at(0.0, 1.0, material),
+            b: sphere_at(bite.x, 0.5, material),
+        }),
+        &materials,
+        |p| Float::max(p.length() - 1.0, 0.5 - (p - bite).length()),
+        |p| {
+            if (p.length() - 1.0).abs() < ((p - bite).length() - 0.5).abs() {
+                p
+            } else {
+                p - bite
+            }
+        },
+    );
+}
+
+#[test]
+fn csg_lens() {
+    let (materials, material) = material();
+    let lens = Hittable::Csg(Csg {
+        operation: CsgOperation::Intersection,
+        a: sphere_at(-0.5, 1.0, material),
+        b: sphere_at(0.5, 1.0, material),
+    });
+    lens.validate(&materials).unwrap();
+    let bound = lens.bound();
+    assert!((bound.p_min.x + 0.5).abs() < EPSILON && (bound.p_max.x - 0.5).abs() < EPSILON);
+
+    let side = hit_along(
+        &lens,
+        Point3::new(5.0, 0.0, 0.0),
+        Vector3::new(-1.0, 0.0, 0.0),
+    )
+    .unwrap();
+    assert!((side.p - Point3::new(0.5, 0.0, 0.0)).length() < EPSILON);
+    assert!((side.normal - Vector3::new(1.0, 0.0, 0.0)).length() < EPSILON);
+
+    let rim = hit_along(
+        &lens,
+        Point3::new(0.0, 5.0, 0.0),
+        Vector3::new(0.0, -1.0, 0.0),
+    )
+    .unwrap();
+    assert!((rim.p.y - Float::sqrt(0.75)).abs() < EPSILON);
+
+    // Through one sphere but not the other
+    assert!(hit_along(
+        &lens,
+        Point3::new(1.2, 5.0, 0.0),
+        Vector3::new(0.0, -1.0, 0.0)
+    )
+    .is_none());
+}
+
+#[test]
+fn csg_hollow_sphere() {
+    let mut materials = MaterialList::default();
+    let mut diffuse = || {
+        materials.add(Material::from(ReflectanceModel::Diffuse(Diffuse {
+            albedo: Color3::new(0.5, 0.5, 0.5),
+        })))
+    };
+    let (outer, inner) = (diffuse(), diffuse());
+    let hollow = Hittable::Csg(Csg {
+        operation: CsgOperation::Difference,
+        a: sphere_at(0.0, 1.0, outer),
+        b: sphere_at(0.0, 0.8, inner),
+    });
+    hollow.validate(&materials).unwrap();
+
+    let down = Vector3::new(0.0, 0.0, -1.0);
+    let outside = hit_along(&hollow, Point3::new(0.0, 0.0, 5.0), down).unwrap();
+    assert!((outside.t - 4.0).abs() < EPSILON);
+    assert_eq!(outside.material, Some(outer));
+    assert!((outside.normal - Vector3::new(0.0, 0.0, 1.0)).length() < EPSILON);
+
+    // From within the wall the hole's surface faces into the hole
+    let wall = hit_along(&hollow, Point3::new(0.0, 0.0, 0.9), down).unwrap();
+    assert!((wall.p.z - 0.8).abs() < EPSILON);
+    assert_eq!(wall.material, Some(inner));
+    assert!((wall.normal - Vector3::new(0.0, 0.0, -1.0)).length() < EPSILON);
+
+    // And from within the hole the next surface is the far side of it
+    let hole = hit_along(&hollow, Point3::new(0.0, 0.0, 0.0), down).unwrap();
+    assert!((hole.p.z + 0.8).abs() < EPSILON);
+    assert!((hole.normal - Vector3::new(0.0, 0.0, 1.0)).length() < EPSILON);
+}
+
+#[test]
+fn csg_union_hides_inner_surfaces() {
+    let (materials, material) = material();
+    let union = Hittable::Instance(Instance {
+        object: Box::new(Hittable::Csg(Csg {
+            operation: CsgOperation::Union,
+            a: sphere_at(-0.5, 1.0, material),
+            b: sphere_at(0.5, 1.0, material),
+        })),
+        transform: Transform::translate(&Vector3::new(0.0, 2.0, 0.0)),
+    });
+    union.validate(&materials).unwrap();
+
+    let right = Vector3::new(1.0, 0.0, 0.0);
+    let near = hit_along(&union, Point3::new(-5.0, 2.0, 0.0), right).unwrap();
+    assert!((near.p.x + 1.5).abs() < EPSILON);
+    let far = hit_along(&union, near.p, right).unwrap();
+    assert!((far.p.x - 1.5).abs() < EPSILON);
+    assert!((far.normal - right).length() < EPSILON);
+}
+
+#[test]
+fn csg_needs_closed_operands() {
+    let (materials, material) = material();
+    let csg = Hittable::Csg(Csg {
+        operation: CsgOperation::Union,
+        a: sphere_at(0.0, 1.0, material),
+        b: Box::new(Hittable::Quad(Quad {
+            origin: Point3::new(0.0, 0.0, 0.0),
+            u: Vector3::new(1.0, 0.0, 0.0),
+            v: Vector3::new(0.0, 1.0, 0.0),
+            material,
+        })),
+    });
+    assert!(csg.validate(&materials).is_err());
+}
+
+#[test]
+fn csg_hollow_sdf_sphere() {
+    let (materials, material) = material();
+    let ball = |radius| {
+        Box::new(Hittable::Sdf(
+            Sdf::new(SdfNode::Sphere { radius }, material).unwrap(),
+        ))
+    };
+    let hollow = Hittable::Csg(Csg {
+        operation: CsgOperation::Difference,
+        a: ball(1.0),
+        b: ball(0.8),
+    });
+    hollow.validate(&materials).unwrap();
+
+    // Each surface is found once, in order, however close sphere tracing stops to it
+    let down = Vector3::new(0.0, 0.0, -1.0);
+    let mut origin = Point3::new(0.0, 0.0, 5.0);
+    for &(z, normal_z) in &[(1.0, 1.0), (0.8, -1.0), (-0.8, 1.0), (-1.0, -1.0)] {
+        let hit = hit_along(&hollow, origin, down).unwrap();
+        assert!((hit.p.z - z).abs() < 1e-3, "{} instead of {}", hit.p.z, z);
+        assert!((hit.normal.z - normal_z).abs() < 1e-2);
+        origin = hit.p;
+    }
+    assert!(hit_along(&hollow, origin, down).is_none());
+}
@@ -0,0 +1,51 @@
+//! Checks that every tile order covers the whole grid
+
+use rustrt::*;
+
+#[test]
+fn every_order_visits_every_tile_once() {
+    for order in [TileOrder::RowMajor, TileOrder::Spiral, TileOrder::Hilbert] {
+        for (columns, rows) in [(1, 1), (1, 7), (7, 1), (4, 4), (5, 3), (3, 8), (17, 10)] {
+            let tiles = order.tiles(columns, rows);
+            assert_eq!(
+                tiles.len(),
+                columns * rows,
+                "{:?} {}x{}",
+                order,
+                columns,
+                rows
+            );
+
+            let mut visited = vec![false; columns * rows];
+            for (i, j) in tiles {
+                assert!(
+                    i < columns && j < rows,
+                    "{:?} went outside to ({}, {})",
+                    order,
+                    i,
+                    j
+                );
+                assert!(
+                    !visited[j * columns + i],
+                    "{:?} visited ({}, {}) twice",
+                    order,
+                    i,
+                    j
+                );
+                visited[j * columns + i] = true;
+            }
+        }
+    }
+}
+
+#[test]
+fn spiral_starts_in_the_middle_and_hilbert_only_takes_single_steps() {
+    assert_eq!(TileOrder::Spiral.tiles(5, 3)[0], (2, 1));
+
+    // On a power of two grid the Hilbert curve only ever moves to a neighbouring tile
+    let tiles = TileOrder::Hilbert.tiles(8, 8);
+    for pair in tiles.windows(2) {
+        let ((i0, j0), (i1, j1)) = (pair[0], pair[1]);
+        assert_eq!(i0.abs_diff(i1) + j0.abs_diff(j1), 1, "{:?}", pair);
+    }
+}